@@ -6,21 +6,9 @@ use std::fs::File;
 use std::path::Path;
  
  
-#[cfg(target_os = "macos")]
-fn register_gl_api(file: &mut File) {
-    Registry::new(Api::Gl, (3, 3), Profile::Core, Fallbacks::All, [])
-        .write_bindings(GlobalGenerator, file)
-        .unwrap();
-}
- 
-#[cfg(target_os = "windows")]
-fn register_gl_api(file: &mut File) {
-    Registry::new(Api::Gl, (4, 6), Profile::Core, Fallbacks::All, [])
-        .write_bindings(GlobalGenerator, file)
-        .unwrap();
-}
- 
-#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+// Generate bindings for the newest OpenGL on every platform. Functions the driver
+// does not provide stay unloaded, and the renderer checks the context version at
+// runtime before using newer features.
 fn register_gl_api(file: &mut File) {
     Registry::new(Api::Gl, (4, 6), Profile::Core, Fallbacks::All, [])
         .write_bindings(GlobalGenerator, file)
@@ -0,0 +1,40 @@
+// The light arrays are sized by the MAX_POINT_LIGHTS and MAX_SPOT_LIGHTS
+// definitions. The cutoffs of a spot light are the cosines of its cone angles.
+struct DirLight {
+    vec3 direction;
+    vec3 ambient;
+    vec3 diffuse;
+    vec3 specular;
+};
+
+struct PointLight {
+    vec3 position;
+    float constant;
+    vec3 ambient;
+    float linear;
+    vec3 diffuse;
+    float quadratic;
+    vec3 specular;
+};
+
+struct SpotLight {
+    vec3 position;
+    float cutoff;
+    vec3 direction;
+    float outer_cutoff;
+    vec3 ambient;
+    float constant;
+    vec3 diffuse;
+    float linear;
+    vec3 specular;
+    float quadratic;
+};
+
+layout (std140) uniform Lights {
+    int point_light_count;
+    int spot_light_count;
+    bool has_dir_light;
+    DirLight dir_light;
+    PointLight point_lights[MAX_POINT_LIGHTS];
+    SpotLight spot_lights[MAX_SPOT_LIGHTS];
+};
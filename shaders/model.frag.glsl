@@ -1,12 +1,95 @@
 #version 330
+in vec3 FragPos;
+in vec3 Normal;
 in vec2 TexCoords;
+in vec2 TexCoords2;
+in vec4 VertexColor;
 
 uniform sampler2D texture_diffuse1;
+uniform sampler2D texture_specular1;
+uniform sampler2D texture_emission1;
+uniform float material_shininess;
+// The emission map holds baked lighting, which meshes with a second set of
+// texture coordinates map with those instead of their material coordinates.
+uniform bool has_tex_coords2;
+
+#include "include/camera.glsl"
+#include "include/lights.glsl"
 
 out vec4 FragColor;
 
 
-void main() {
-    FragColor = texture(texture_diffuse1, TexCoords);
+struct Surface {
+    vec3 diffuse;
+    vec3 specular;
+    vec3 normal;
+    vec3 view_dir;
+};
+
+float attenuation(float constant, float linear, float quadratic, float distance) {
+    return 1.0 / (constant + linear * distance + quadratic * (distance * distance));
+}
+
+// The ambient term, plus the diffuse and specular terms scaled by `intensity`.
+vec3 blinn_phong(vec3 light_dir, vec3 ambient, vec3 diffuse, vec3 specular, float intensity, Surface surface) {
+    vec3 halfway_dir = normalize(light_dir + surface.view_dir);
+    float diffuse_factor = max(dot(surface.normal, light_dir), 0.0);
+    float specular_factor = 0.0;
+    if (diffuse_factor > 0.0) {
+        specular_factor = pow(max(dot(surface.normal, halfway_dir), 0.0), material_shininess);
+    }
+
+    return ambient * surface.diffuse +
+        intensity * (diffuse * diffuse_factor * surface.diffuse + specular * specular_factor * surface.specular);
 }
 
+vec3 dir_light_color(DirLight light, Surface surface) {
+    vec3 light_dir = normalize(-light.direction);
+
+    return blinn_phong(light_dir, light.ambient, light.diffuse, light.specular, 1.0, surface);
+}
+
+vec3 point_light_color(PointLight light, Surface surface) {
+    vec3 to_light = light.position - FragPos;
+    float distance = length(to_light);
+    vec3 light_dir = to_light / distance;
+    vec3 color = blinn_phong(light_dir, light.ambient, light.diffuse, light.specular, 1.0, surface);
+
+    return attenuation(light.constant, light.linear, light.quadratic, distance) * color;
+}
+
+vec3 spot_light_color(SpotLight light, Surface surface) {
+    vec3 to_light = light.position - FragPos;
+    float distance = length(to_light);
+    vec3 light_dir = to_light / distance;
+    // Fade from full strength at the inner cone to nothing at the outer cone.
+    float theta = dot(light_dir, normalize(-light.direction));
+    float epsilon = max(light.cutoff - light.outer_cutoff, 0.0001);
+    float intensity = clamp((theta - light.outer_cutoff) / epsilon, 0.0, 1.0);
+    vec3 color = blinn_phong(light_dir, light.ambient, light.diffuse, light.specular, intensity, surface);
+
+    return attenuation(light.constant, light.linear, light.quadratic, distance) * color;
+}
+
+void main() {
+    vec4 base_color = VertexColor * texture(texture_diffuse1, TexCoords);
+    Surface surface;
+    surface.diffuse = base_color.rgb;
+    surface.specular = texture(texture_specular1, TexCoords).rgb;
+    surface.normal = normalize(Normal);
+    surface.view_dir = normalize(camera_position - FragPos);
+
+    vec2 emission_coords = has_tex_coords2 ? TexCoords2 : TexCoords;
+    vec3 color = texture(texture_emission1, emission_coords).rgb;
+    if (has_dir_light) {
+        color += dir_light_color(dir_light, surface);
+    }
+    for (int i = 0; i < min(point_light_count, MAX_POINT_LIGHTS); i++) {
+        color += point_light_color(point_lights[i], surface);
+    }
+    for (int i = 0; i < min(spot_light_count, MAX_SPOT_LIGHTS); i++) {
+        color += spot_light_color(spot_lights[i], surface);
+    }
+
+    FragColor = vec4(color, base_color.a);
+}
@@ -1,17 +1,53 @@
 #version 330
+#ifdef PACKED_VERTICES
+layout (location = 0) in vec4 aPos;
+layout (location = 1) in vec2 aNormal;
+layout (location = 2) in vec2 aTexCoords;
+layout (location = 3) in vec2 aTangent;
+#else
 layout (location = 0) in vec3 aPos;
 layout (location = 1) in vec3 aNormal;
 layout (location = 2) in vec2 aTexCoords;
+#endif
+layout (location = 5) in vec4 aColor;
+layout (location = 6) in vec2 aTexCoords2;
 
+out vec3 FragPos;
+out vec3 Normal;
 out vec2 TexCoords;
+out vec2 TexCoords2;
+out vec4 VertexColor;
+#ifdef PACKED_VERTICES
+out vec3 Tangent;
+out vec3 Bitangent;
+#endif
 
 uniform mat4 model;
-uniform mat4 view;
-uniform mat4 projection;
+
+#include "include/camera.glsl"
+#ifdef PACKED_VERTICES
+uniform vec3 position_offset;
+uniform vec3 position_scale;
+
+#include "include/octahedral.glsl"
+#endif
 
 
 void main() {
+#ifdef PACKED_VERTICES
+    vec3 position = position_offset + position_scale * aPos.xyz;
+    vec3 normal = octahedral_decode(aNormal);
+    Tangent = octahedral_decode(aTangent);
+    Bitangent = sign(aPos.w) * cross(normal, Tangent);
+#else
+    vec3 position = aPos;
+    vec3 normal = aNormal;
+#endif
+    vec4 world_position = model * vec4(position, 1.0);
+    FragPos = world_position.xyz;
+    Normal = mat3(transpose(inverse(model))) * normal;
     TexCoords = aTexCoords;
-    gl_Position = projection * view * model * vec4 (aPos, 1.0);
+    TexCoords2 = aTexCoords2;
+    VertexColor = aColor;
+    gl_Position = projection * view * world_position;
 }
-
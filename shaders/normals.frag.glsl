@@ -0,0 +1,9 @@
+#version 330
+uniform vec3 normal_color;
+
+out vec4 FragColor;
+
+
+void main() {
+    FragColor = vec4 (normal_color, 1.0);
+}
@@ -0,0 +1,26 @@
+#version 330
+layout (triangles) in;
+layout (line_strip, max_vertices = 6) out;
+
+in VS_OUT {
+    vec3 normal;
+} gs_in[];
+
+uniform float normal_length;
+
+#include "include/camera.glsl"
+
+
+void emit_normal(int index) {
+    gl_Position = projection * gl_in[index].gl_Position;
+    EmitVertex();
+    gl_Position = projection * (gl_in[index].gl_Position + vec4 (gs_in[index].normal * normal_length, 0.0));
+    EmitVertex();
+    EndPrimitive();
+}
+
+void main() {
+    emit_normal(0);
+    emit_normal(1);
+    emit_normal(2);
+}
@@ -0,0 +1,18 @@
+#version 330
+layout (location = 0) in vec3 aPos;
+layout (location = 1) in vec3 aNormal;
+
+out VS_OUT {
+    vec3 normal;
+} vs_out;
+
+uniform mat4 model;
+
+#include "include/camera.glsl"
+
+
+void main() {
+    mat3 normal_matrix = mat3 (transpose(inverse(view * model)));
+    vs_out.normal = normalize(normal_matrix * aNormal);
+    gl_Position = view * model * vec4 (aPos, 1.0);
+}
@@ -0,0 +1,15 @@
+#version 330
+layout (location = 0) in vec3 aPos;
+layout (location = 1) in vec3 aNormal;
+
+uniform mat4 model;
+uniform float outline_width;
+
+#include "include/camera.glsl"
+
+
+void main() {
+    vec3 position = aPos + normalize(aNormal) * outline_width;
+    gl_Position = projection * view * model * vec4 (position, 1.0);
+}
+
@@ -0,0 +1,11 @@
+#version 330
+in vec4 VertexColor;
+
+uniform vec3 primitive_color;
+
+out vec4 FragColor;
+
+
+void main() {
+    FragColor = VertexColor * vec4 (primitive_color, 1.0);
+}
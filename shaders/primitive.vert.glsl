@@ -0,0 +1,17 @@
+#version 330
+layout (location = 0) in vec3 aPos;
+layout (location = 5) in vec4 aColor;
+
+out vec4 VertexColor;
+
+uniform mat4 model;
+uniform float point_size;
+
+#include "include/camera.glsl"
+
+
+void main() {
+    VertexColor = aColor;
+    gl_PointSize = point_size;
+    gl_Position = projection * view * model * vec4 (aPos, 1.0);
+}
@@ -0,0 +1,503 @@
+use crate::geometry::{
+    vector_key,
+};
+use crate::model::{
+    Mesh,
+    Model,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use std::collections::{
+    HashMap,
+};
+use std::fmt;
+
+
+/// The geometric and topological properties of a mesh. Topology is computed over
+/// positions rather than vertex indices, so vertices split along UV seams or hard
+/// edges count as one vertex.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct MeshAnalysis {
+    pub mesh_name: String,
+    /// The number of distinct vertex positions.
+    pub vertex_count: usize,
+    pub edge_count: usize,
+    /// The number of triangles with three distinct positions.
+    pub triangle_count: usize,
+    pub surface_area: f32,
+    /// The volume enclosed by the mesh, positive when the triangles face outwards.
+    /// Only meaningful for closed, consistently oriented meshes.
+    pub signed_volume: f32,
+    /// The center of mass of the enclosed volume for closed meshes, and the center
+    /// of the surface otherwise.
+    pub centroid: [f32; 3],
+    /// Edges used by exactly one triangle.
+    pub boundary_edge_count: usize,
+    /// Edges used by more than two triangles.
+    pub non_manifold_edge_count: usize,
+    /// Vertices whose triangles do not form a single fan.
+    pub non_manifold_vertex_count: usize,
+    /// Each loop of boundary edges, as the index of one vertex at each position.
+    pub boundary_loops: Vec<Vec<u32>>,
+    /// The mesh has no boundary edges.
+    pub is_closed: bool,
+    /// Every edge has at most two triangles, and every vertex a single fan of them.
+    pub is_manifold: bool,
+    /// Every edge shared by two triangles is traversed in opposite directions by them.
+    pub is_consistently_oriented: bool,
+    pub connected_components: usize,
+    /// `V - E + F` over positions, edges and triangles.
+    pub euler_characteristic: i64,
+    /// The total number of handles, available when the mesh is closed, manifold and
+    /// consistently oriented.
+    pub genus: Option<u32>,
+}
+
+impl MeshAnalysis {
+    /// Determine whether the mesh bounds a solid, so that its volume is meaningful.
+    pub fn is_watertight(&self) -> bool {
+        self.is_closed && self.is_manifold && self.is_consistently_oriented
+    }
+}
+
+impl fmt::Display for MeshAnalysis {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f,
+            "Analysis of mesh \"{}\" ({} vertices, {} edges, {} triangles):",
+            self.mesh_name, self.vertex_count, self.edge_count, self.triangle_count
+        )?;
+        writeln!(f, "  surface area: {}", self.surface_area)?;
+        writeln!(f, "  signed volume: {}", self.signed_volume)?;
+        writeln!(f, "  centroid: {:?}", self.centroid)?;
+        writeln!(f, "  boundary edges: {} in {} loops", self.boundary_edge_count, self.boundary_loops.len())?;
+        writeln!(f, "  non-manifold edges: {}", self.non_manifold_edge_count)?;
+        writeln!(f, "  non-manifold vertices: {}", self.non_manifold_vertex_count)?;
+        writeln!(f, "  closed: {}", self.is_closed)?;
+        writeln!(f, "  manifold: {}", self.is_manifold)?;
+        writeln!(f, "  consistently oriented: {}", self.is_consistently_oriented)?;
+        writeln!(f, "  connected components: {}", self.connected_components)?;
+        writeln!(f, "  Euler characteristic: {}", self.euler_characteristic)?;
+        match self.genus {
+            Some(genus) => writeln!(f, "  genus: {}", genus),
+            None => writeln!(f, "  genus: undefined"),
+        }
+    }
+}
+
+/// A disjoint set forest over the integers `0..size`.
+struct DisjointSet {
+    parents: Vec<usize>,
+}
+
+impl DisjointSet {
+    fn new(size: usize) -> DisjointSet {
+        DisjointSet {
+            parents: (0..size).collect(),
+        }
+    }
+
+    fn find(&mut self, mut element: usize) -> usize {
+        while self.parents[element] != element {
+            self.parents[element] = self.parents[self.parents[element]];
+            element = self.parents[element];
+        }
+
+        element
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let root_a = self.find(a);
+        let root_b = self.find(b);
+        if root_a != root_b {
+            self.parents[root_a] = root_b;
+        }
+    }
+
+    fn count_roots(&mut self, elements: &[usize]) -> usize {
+        let mut roots: Vec<usize> = elements.iter().map(|&element| self.find(element)).collect();
+        roots.sort();
+        roots.dedup();
+
+        roots.len()
+    }
+}
+
+/// Follow the boundary edges of a mesh around each hole. Each boundary edge is
+/// directed the way its triangle traverses it.
+fn boundary_loops(boundary_edges: &[(usize, usize)]) -> Vec<Vec<usize>> {
+    let mut outgoing: HashMap<usize, Vec<usize>> = HashMap::new();
+    for (i, &(from, _)) in boundary_edges.iter().enumerate() {
+        outgoing.entry(from).or_insert_with(Vec::new).push(i);
+    }
+
+    let mut used = vec![false; boundary_edges.len()];
+    let mut loops = vec![];
+    for start in 0..boundary_edges.len() {
+        if used[start] {
+            continue;
+        }
+        used[start] = true;
+        let first = boundary_edges[start].0;
+        let mut current = boundary_edges[start].1;
+        let mut boundary_loop = vec![first];
+        while current != first {
+            boundary_loop.push(current);
+            let next = outgoing.get(&current)
+                .and_then(|edges| edges.iter().find(|&&edge| !used[edge]).copied());
+            match next {
+                Some(edge) => {
+                    used[edge] = true;
+                    current = boundary_edges[edge].1;
+                }
+                // The boundary is not a simple loop, so report the open chain.
+                None => break,
+            }
+        }
+        loops.push(boundary_loop);
+    }
+
+    loops
+}
+
+/// Compute the surface area, volume and topology of a mesh.
+pub fn analyze_mesh(mesh: &Mesh) -> MeshAnalysis {
+    // Weld the vertices by position.
+    let mut position_lookup = HashMap::new();
+    let mut representatives = vec![];
+    let position_ids: Vec<usize> = mesh.vertices.iter()
+        .enumerate()
+        .map(|(i, vertex)| {
+            *position_lookup.entry(vector_key(&vertex.position)).or_insert_with(|| {
+                representatives.push(i as u32);
+                representatives.len() - 1
+            })
+        })
+        .collect();
+    let vertex_count = representatives.len();
+
+    let mut triangles = vec![];
+    for indices in mesh.vertex_indices.chunks_exact(3) {
+        if indices.iter().any(|&index| index as usize >= mesh.vertices.len()) {
+            continue;
+        }
+        let triangle = [
+            position_ids[indices[0] as usize],
+            position_ids[indices[1] as usize],
+            position_ids[indices[2] as usize],
+        ];
+        if triangle[0] == triangle[1] || triangle[1] == triangle[2] || triangle[2] == triangle[0] {
+            continue;
+        }
+        triangles.push((triangle, [indices[0], indices[1], indices[2]]));
+    }
+
+    let mut area = 0.0_f64;
+    let mut volume = 0.0_f64;
+    let mut area_moment = [0.0_f64; 3];
+    let mut volume_moment = [0.0_f64; 3];
+    for (_, indices) in triangles.iter() {
+        let p = [
+            mesh.vertices[indices[0] as usize].position,
+            mesh.vertices[indices[1] as usize].position,
+            mesh.vertices[indices[2] as usize].position,
+        ];
+        let p: Vec<[f64; 3]> = p.iter().map(|v| [v.x as f64, v.y as f64, v.z as f64]).collect();
+        let edge1 = [p[1][0] - p[0][0], p[1][1] - p[0][1], p[1][2] - p[0][2]];
+        let edge2 = [p[2][0] - p[0][0], p[2][1] - p[0][1], p[2][2] - p[0][2]];
+        let cross = [
+            edge1[1] * edge2[2] - edge1[2] * edge2[1],
+            edge1[2] * edge2[0] - edge1[0] * edge2[2],
+            edge1[0] * edge2[1] - edge1[1] * edge2[0],
+        ];
+        let triangle_area = 0.5 * f64::sqrt(cross[0] * cross[0] + cross[1] * cross[1] + cross[2] * cross[2]);
+        // The signed volume of the tetrahedron spanned by the triangle and the origin.
+        let triangle_volume = (
+            p[0][0] * (p[1][1] * p[2][2] - p[1][2] * p[2][1]) +
+            p[0][1] * (p[1][2] * p[2][0] - p[1][0] * p[2][2]) +
+            p[0][2] * (p[1][0] * p[2][1] - p[1][1] * p[2][0])
+        ) / 6.0;
+        area += triangle_area;
+        volume += triangle_volume;
+        for axis in 0..3 {
+            let sum = p[0][axis] + p[1][axis] + p[2][axis];
+            area_moment[axis] += triangle_area * sum / 3.0;
+            volume_moment[axis] += triangle_volume * sum / 4.0;
+        }
+    }
+
+    // Count the triangles on each edge, and the directions they traverse it in.
+    let mut edges: HashMap<(usize, usize), (usize, usize)> = HashMap::new();
+    for (triangle, _) in triangles.iter() {
+        for k in 0..3 {
+            let a = triangle[k];
+            let b = triangle[(k + 1) % 3];
+            let key = if a < b { (a, b) } else { (b, a) };
+            let counts = edges.entry(key).or_insert((0, 0));
+            if a < b {
+                counts.0 += 1;
+            } else {
+                counts.1 += 1;
+            }
+        }
+    }
+    let mut boundary_edges = vec![];
+    let mut non_manifold_edge_count = 0;
+    let mut is_consistently_oriented = true;
+    for (&(a, b), &(forward, backward)) in edges.iter() {
+        match forward + backward {
+            1 => boundary_edges.push(if forward == 1 { (a, b) } else { (b, a) }),
+            2 => is_consistently_oriented &= forward == 1,
+            _ => non_manifold_edge_count += 1,
+        }
+    }
+    boundary_edges.sort();
+
+    // A vertex is manifold when the triangles around it are connected to each other
+    // through the edges meeting at the vertex.
+    let mut vertex_triangles = vec![vec![]; vertex_count];
+    for (t, (triangle, _)) in triangles.iter().enumerate() {
+        for &position in triangle.iter() {
+            vertex_triangles[position].push(t);
+        }
+    }
+    let mut non_manifold_vertex_count = 0;
+    for (position, incident) in vertex_triangles.iter().enumerate() {
+        if incident.len() < 2 {
+            continue;
+        }
+        let mut fans = DisjointSet::new(incident.len());
+        let mut spokes: HashMap<usize, usize> = HashMap::new();
+        for (i, &t) in incident.iter().enumerate() {
+            for &other in triangles[t].0.iter().filter(|&&other| other != position) {
+                match spokes.get(&other) {
+                    Some(&j) => fans.union(i, j),
+                    None => {
+                        spokes.insert(other, i);
+                    }
+                }
+            }
+        }
+        let elements: Vec<usize> = (0..incident.len()).collect();
+        if fans.count_roots(&elements) > 1 {
+            non_manifold_vertex_count += 1;
+        }
+    }
+
+    let mut components = DisjointSet::new(vertex_count);
+    for (triangle, _) in triangles.iter() {
+        components.union(triangle[0], triangle[1]);
+        components.union(triangle[1], triangle[2]);
+    }
+    let used_positions: Vec<usize> = (0..vertex_count)
+        .filter(|&position| !vertex_triangles[position].is_empty())
+        .collect();
+    let connected_components = components.count_roots(&used_positions);
+
+    let euler_characteristic = vertex_count as i64 - edges.len() as i64 + triangles.len() as i64;
+    let is_closed = boundary_edges.is_empty();
+    let is_manifold = non_manifold_edge_count == 0 && non_manifold_vertex_count == 0;
+    // Each closed orientable surface of genus g has Euler characteristic 2 - 2g.
+    let genus = if is_closed && is_manifold && is_consistently_oriented {
+        let handles = 2 * connected_components as i64 - euler_characteristic;
+        if handles >= 0 && handles % 2 == 0 { Some((handles / 2) as u32) } else { None }
+    } else {
+        None
+    };
+
+    let centroid = if is_closed && volume.abs() > 0.0 {
+        [volume_moment[0] / volume, volume_moment[1] / volume, volume_moment[2] / volume]
+    } else if area > 0.0 {
+        [area_moment[0] / area, area_moment[1] / area, area_moment[2] / area]
+    } else {
+        [0.0; 3]
+    };
+
+    let loops = boundary_loops(&boundary_edges).iter()
+        .map(|boundary_loop| {
+            boundary_loop.iter().map(|&position| representatives[position]).collect()
+        })
+        .collect();
+
+    MeshAnalysis {
+        mesh_name: mesh.name.clone(),
+        vertex_count: vertex_count,
+        edge_count: edges.len(),
+        triangle_count: triangles.len(),
+        surface_area: area as f32,
+        signed_volume: volume as f32,
+        centroid: [centroid[0] as f32, centroid[1] as f32, centroid[2] as f32],
+        boundary_edge_count: boundary_edges.len(),
+        non_manifold_edge_count: non_manifold_edge_count,
+        non_manifold_vertex_count: non_manifold_vertex_count,
+        boundary_loops: loops,
+        is_closed: is_closed,
+        is_manifold: is_manifold,
+        is_consistently_oriented: is_consistently_oriented,
+        connected_components: connected_components,
+        euler_characteristic: euler_characteristic,
+        genus: genus,
+    }
+}
+
+/// Analyze every mesh in a model.
+pub fn analyze_model(model: &Model) -> Vec<MeshAnalysis> {
+    model.meshes.iter().map(analyze_mesh).collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives;
+    use cglinalg::{
+        Vector3,
+    };
+    use std::f32::consts::{
+        PI,
+    };
+
+
+    fn assert_close(result: f32, expected: f32, tolerance: f32) {
+        assert!(
+            (result - expected).abs() <= tolerance * expected.abs(),
+            "expected {} within a relative tolerance of {}, got {}", expected, tolerance, result
+        );
+    }
+
+    /// Combine two meshes into one mesh, offsetting the second mesh by `offset`.
+    fn union(mesh1: &Mesh, mesh2: &Mesh, offset: Vector3<f32>) -> Mesh {
+        let mut vertices = mesh1.vertices.clone();
+        let mut vertex_indices = mesh1.vertex_indices.clone();
+        let base = vertices.len() as u32;
+        vertices.extend(mesh2.vertices.iter().map(|vertex| {
+            let mut vertex = *vertex;
+            vertex.position += offset;
+            vertex
+        }));
+        vertex_indices.extend(mesh2.vertex_indices.iter().map(|&index| base + index));
+
+        Mesh::new(String::from("union"), vertices, vertex_indices, HashMap::new())
+    }
+
+    #[test]
+    fn test_cube() {
+        let analysis = analyze_mesh(&primitives::cube(2.0));
+
+        assert_close(analysis.surface_area, 24.0, 1e-6);
+        assert_close(analysis.signed_volume, 8.0, 1e-6);
+        assert_eq!(analysis.centroid, [0.0, 0.0, 0.0]);
+        assert_eq!(analysis.vertex_count, 8);
+        assert_eq!(analysis.edge_count, 18);
+        assert_eq!(analysis.triangle_count, 12);
+        assert!(analysis.is_watertight());
+        assert_eq!(analysis.connected_components, 1);
+        assert_eq!(analysis.euler_characteristic, 2);
+        assert_eq!(analysis.genus, Some(0));
+    }
+
+    #[test]
+    fn test_icosahedron() {
+        // An icosahedron inscribed in a sphere of radius r has edge length
+        // a = 4r / sqrt(10 + 2 sqrt(5)), area 5 sqrt(3) a^2 and volume 5 (3 + sqrt(5)) a^3 / 12.
+        let radius = 2.0_f32;
+        let edge = 4.0 * radius / f32::sqrt(10.0 + 2.0 * f32::sqrt(5.0));
+        let analysis = analyze_mesh(&primitives::icosphere(radius, 0));
+
+        assert_close(analysis.surface_area, 5.0 * f32::sqrt(3.0) * edge * edge, 1e-5);
+        assert_close(analysis.signed_volume, 5.0 * (3.0 + f32::sqrt(5.0)) * edge * edge * edge / 12.0, 1e-5);
+        assert_eq!(analysis.vertex_count, 12);
+        assert_eq!(analysis.edge_count, 30);
+        assert_eq!(analysis.triangle_count, 20);
+        assert_eq!(analysis.genus, Some(0));
+    }
+
+    #[test]
+    fn test_icosphere_approaches_sphere() {
+        let radius = 2.0_f32;
+        let analysis = analyze_mesh(&primitives::icosphere(radius, 4));
+
+        assert_close(analysis.surface_area, 4.0 * PI * radius * radius, 5e-3);
+        assert_close(analysis.signed_volume, 4.0 * PI * radius * radius * radius / 3.0, 5e-3);
+        assert!(analysis.signed_volume < 4.0 * PI * radius * radius * radius / 3.0);
+        assert!(analysis.is_watertight());
+        assert_eq!(analysis.euler_characteristic, 2);
+        for &coordinate in analysis.centroid.iter() {
+            assert!(coordinate.abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn test_plane_has_one_boundary_loop() {
+        let analysis = analyze_mesh(&primitives::plane(1.0, 1.0, 3, 2));
+
+        assert!(!analysis.is_closed);
+        assert!(analysis.is_manifold);
+        assert!(analysis.is_consistently_oriented);
+        assert_eq!(analysis.boundary_loops.len(), 1);
+        assert_eq!(analysis.boundary_loops[0].len(), 2 * (3 + 2));
+        assert_eq!(analysis.boundary_edge_count, 2 * (3 + 2));
+        assert_eq!(analysis.euler_characteristic, 1);
+        assert_eq!(analysis.genus, None);
+        assert_close(analysis.surface_area, 1.0, 1e-6);
+    }
+
+    #[test]
+    fn test_torus_has_genus_one() {
+        let analysis = analyze_mesh(&primitives::torus(2.0, 0.5, 64, 32));
+
+        assert!(analysis.is_watertight());
+        assert_eq!(analysis.euler_characteristic, 0);
+        assert_eq!(analysis.genus, Some(1));
+        assert_eq!(analysis.connected_components, 1);
+        // A torus has area 4 pi^2 R r and volume 2 pi^2 R r^2.
+        assert_close(analysis.surface_area, 4.0 * PI * PI * 2.0 * 0.5, 0.01);
+        assert_close(analysis.signed_volume, 2.0 * PI * PI * 2.0 * 0.5 * 0.5, 0.01);
+    }
+
+    #[test]
+    fn test_union_has_two_components() {
+        let cube = primitives::cube(1.0);
+        let sphere = primitives::uv_sphere(0.5, 12, 6);
+        let analysis = analyze_mesh(&union(&cube, &sphere, Vector3::new(3.0, 0.0, 0.0)));
+
+        assert_eq!(analysis.connected_components, 2);
+        assert_eq!(analysis.euler_characteristic, 4);
+        assert_eq!(analysis.genus, Some(0));
+        assert!(analysis.is_watertight());
+        let cube_volume = analyze_mesh(&cube).signed_volume;
+        let sphere_volume = analyze_mesh(&sphere).signed_volume;
+        assert_close(analysis.signed_volume, cube_volume + sphere_volume, 1e-5);
+    }
+
+    #[test]
+    fn test_inverted_cube_has_negative_volume() {
+        let mut cube = primitives::cube(2.0);
+        for triangle in cube.vertex_indices.chunks_exact_mut(3) {
+            triangle.swap(1, 2);
+        }
+        let analysis = analyze_mesh(&cube);
+
+        assert_close(analysis.signed_volume, -8.0, 1e-6);
+        assert!(analysis.is_consistently_oriented);
+    }
+
+    #[test]
+    fn test_non_manifold_edge() {
+        // Three triangles sharing the edge from vertex 0 to vertex 1.
+        let mut vertices = primitives::plane(1.0, 1.0, 1, 1).vertices;
+        vertices.truncate(2);
+        let mut vertex = vertices[0];
+        for position in [Vector3::new(0.0, 1.0, 0.0), Vector3::new(0.0, -1.0, 0.0), Vector3::new(0.0, 0.0, 1.0)].iter() {
+            vertex.position = *position;
+            vertices.push(vertex);
+        }
+        let mesh = Mesh::new(String::from("fin"), vertices, vec![0, 1, 2, 1, 0, 3, 0, 1, 4], HashMap::new());
+        let analysis = analyze_mesh(&mesh);
+
+        assert_eq!(analysis.non_manifold_edge_count, 1);
+        assert!(!analysis.is_manifold);
+        assert_eq!(analysis.genus, None);
+    }
+}
@@ -1,11 +1,14 @@
 #![allow(dead_code)]
 use crate::gl;
 use gl::types::{
+    GLbitfield,
     GLboolean, 
     GLchar, 
     GLenum, 
     GLfloat, 
     GLint, 
+    GLintptr,
+    GLsizeiptr,
     GLubyte, 
     GLuint,
     GLvoid,
@@ -42,20 +45,32 @@ use std::sync::mpsc::{
 };
 use log::{
     info, 
-    error
+    error,
+    warn,
 };
-use std::ptr;
+use std::cell::{
+    RefCell,
+};
+use std::collections::{
+    HashMap,
+    HashSet,
+};
+use std::marker::{
+    PhantomData,
+};
+use std::ops;
 use std::fmt;
 use std::io;
 use std::mem;
 use std::path::{
     Path
 };
+use std::rc::{
+    Rc,
+    Weak,
+};
 
 
-// 256 Kilobytes.
-const MAX_SHADER_LENGTH: usize = 262144;
-
 const FPS_COUNTER_REFRESH_PERIOD_SECONDS: f64 = 0.5;
 
 // OpenGL extension constants.
@@ -149,35 +164,95 @@ fn gl_params() -> GLParameters {
     }
 }
 
-/// Helper function to convert GLSL types to storage sizes
-fn type_size(gl_type: GLenum) -> usize {
+/// Helper function to convert GLSL types to storage sizes. Returns `None` for types
+/// this table does not know, such as the double precision and image types.
+fn type_size(gl_type: GLenum) -> Option<usize> {
+    match gl_type {
+        gl::FLOAT             => Some(1 * mem::size_of::<GLfloat>()),
+        gl::FLOAT_VEC2        => Some(2 * mem::size_of::<GLfloat>()),
+        gl::FLOAT_VEC3        => Some(3 * mem::size_of::<GLfloat>()),
+        gl::FLOAT_VEC4        => Some(4 * mem::size_of::<GLfloat>()),
+        gl::INT               => Some(1 * mem::size_of::<GLint>()),
+        gl::INT_VEC2          => Some(2 * mem::size_of::<GLint>()),
+        gl::INT_VEC3          => Some(3 * mem::size_of::<GLint>()),
+        gl::INT_VEC4          => Some(4 * mem::size_of::<GLint>()),
+        gl::UNSIGNED_INT      => Some(1 * mem::size_of::<GLuint>()),
+        gl::UNSIGNED_INT_VEC2 => Some(2 * mem::size_of::<GLuint>()),
+        gl::UNSIGNED_INT_VEC3 => Some(3 * mem::size_of::<GLuint>()),
+        gl::UNSIGNED_INT_VEC4 => Some(4 * mem::size_of::<GLuint>()),
+        gl::BOOL              => Some(1 * mem::size_of::<GLboolean>()),
+        gl::BOOL_VEC2         => Some(2 * mem::size_of::<GLboolean>()),
+        gl::BOOL_VEC3         => Some(3 * mem::size_of::<GLboolean>()),
+        gl::BOOL_VEC4         => Some(4 * mem::size_of::<GLboolean>()),
+        gl::FLOAT_MAT2        => Some(4 * mem::size_of::<GLfloat>()),
+        gl::FLOAT_MAT2x3      => Some(6 * mem::size_of::<GLfloat>()),
+        gl::FLOAT_MAT2x4      => Some(8 * mem::size_of::<GLfloat>()),
+        gl::FLOAT_MAT3        => Some(9 * mem::size_of::<GLfloat>()),
+        gl::FLOAT_MAT3x2      => Some(6 * mem::size_of::<GLfloat>()),
+        gl::FLOAT_MAT3x4      => Some(12 * mem::size_of::<GLfloat>()),
+        gl::FLOAT_MAT4        => Some(16 * mem::size_of::<GLfloat>()),
+        gl::FLOAT_MAT4x2      => Some(8 * mem::size_of::<GLfloat>()),
+        gl::FLOAT_MAT4x3      => Some(12 * mem::size_of::<GLfloat>()),
+        // Samplers are set through their texture unit.
+        _ if is_sampler_type(gl_type) => Some(mem::size_of::<GLint>()),
+        _ => None,
+    }
+}
+
+/// Whether a GLSL type is one of the sampler types, such as `sampler2D`.
+fn is_sampler_type(gl_type: GLenum) -> bool {
+    match gl_type {
+        gl::SAMPLER_1D | gl::SAMPLER_2D | gl::SAMPLER_3D | gl::SAMPLER_CUBE |
+        gl::SAMPLER_1D_SHADOW | gl::SAMPLER_2D_SHADOW | gl::SAMPLER_CUBE_SHADOW |
+        gl::SAMPLER_1D_ARRAY | gl::SAMPLER_2D_ARRAY |
+        gl::SAMPLER_1D_ARRAY_SHADOW | gl::SAMPLER_2D_ARRAY_SHADOW |
+        gl::SAMPLER_2D_MULTISAMPLE | gl::SAMPLER_2D_MULTISAMPLE_ARRAY |
+        gl::SAMPLER_2D_RECT | gl::SAMPLER_2D_RECT_SHADOW | gl::SAMPLER_BUFFER |
+        gl::INT_SAMPLER_1D | gl::INT_SAMPLER_2D | gl::INT_SAMPLER_3D | gl::INT_SAMPLER_CUBE |
+        gl::INT_SAMPLER_1D_ARRAY | gl::INT_SAMPLER_2D_ARRAY | gl::INT_SAMPLER_BUFFER |
+        gl::UNSIGNED_INT_SAMPLER_1D | gl::UNSIGNED_INT_SAMPLER_2D |
+        gl::UNSIGNED_INT_SAMPLER_3D | gl::UNSIGNED_INT_SAMPLER_CUBE |
+        gl::UNSIGNED_INT_SAMPLER_1D_ARRAY | gl::UNSIGNED_INT_SAMPLER_2D_ARRAY |
+        gl::UNSIGNED_INT_SAMPLER_BUFFER => true,
+        _ => false,
+    }
+}
+
+/// The GLSL name of a type, for log messages.
+fn type_name(gl_type: GLenum) -> &'static str {
     match gl_type {
-        gl::FLOAT             => 1 * mem::size_of::<GLfloat>(),
-        gl::FLOAT_VEC2        => 2 * mem::size_of::<GLfloat>(),
-        gl::FLOAT_VEC3        => 3 * mem::size_of::<GLfloat>(),
-        gl::FLOAT_VEC4        => 4 * mem::size_of::<GLfloat>(),
-        gl::INT               => 1 * mem::size_of::<GLint>(),
-        gl::INT_VEC2          => 2 * mem::size_of::<GLint>(),
-        gl::INT_VEC3          => 3 * mem::size_of::<GLint>(),
-        gl::INT_VEC4          => 4 * mem::size_of::<GLint>(),
-        gl::UNSIGNED_INT      => 1 * mem::size_of::<GLuint>(),
-        gl::UNSIGNED_INT_VEC2 => 2 * mem::size_of::<GLuint>(),
-        gl::UNSIGNED_INT_VEC3 => 3 * mem::size_of::<GLuint>(),
-        gl::UNSIGNED_INT_VEC4 => 4 * mem::size_of::<GLuint>(),
-        gl::BOOL              => 1 * mem::size_of::<GLboolean>(),
-        gl::BOOL_VEC2         => 2 * mem::size_of::<GLboolean>(),
-        gl::BOOL_VEC3         => 3 * mem::size_of::<GLboolean>(),
-        gl::BOOL_VEC4         => 4 * mem::size_of::<GLboolean>(),
-        gl::FLOAT_MAT2        => 4 * mem::size_of::<GLfloat>(),
-        gl::FLOAT_MAT2x3      => 6 * mem::size_of::<GLfloat>(),
-        gl::FLOAT_MAT2x4      => 8 * mem::size_of::<GLfloat>(),
-        gl::FLOAT_MAT3        => 9 * mem::size_of::<GLfloat>(),
-        gl::FLOAT_MAT3x2      => 6 * mem::size_of::<GLfloat>(),
-        gl::FLOAT_MAT3x4      => 12 * mem::size_of::<GLfloat>(),
-        gl::FLOAT_MAT4        => 16 * mem::size_of::<GLfloat>(),
-        gl::FLOAT_MAT4x2      => 8 * mem::size_of::<GLfloat>(),
-        gl::FLOAT_MAT4x3      => 12 * mem::size_of::<GLfloat>(),
-        _ => panic!()
+        gl::FLOAT             => "float",
+        gl::FLOAT_VEC2        => "vec2",
+        gl::FLOAT_VEC3        => "vec3",
+        gl::FLOAT_VEC4        => "vec4",
+        gl::INT               => "int",
+        gl::INT_VEC2          => "ivec2",
+        gl::INT_VEC3          => "ivec3",
+        gl::INT_VEC4          => "ivec4",
+        gl::UNSIGNED_INT      => "uint",
+        gl::UNSIGNED_INT_VEC2 => "uvec2",
+        gl::UNSIGNED_INT_VEC3 => "uvec3",
+        gl::UNSIGNED_INT_VEC4 => "uvec4",
+        gl::BOOL              => "bool",
+        gl::BOOL_VEC2         => "bvec2",
+        gl::BOOL_VEC3         => "bvec3",
+        gl::BOOL_VEC4         => "bvec4",
+        gl::FLOAT_MAT2        => "mat2",
+        gl::FLOAT_MAT2x3      => "mat2x3",
+        gl::FLOAT_MAT2x4      => "mat2x4",
+        gl::FLOAT_MAT3        => "mat3",
+        gl::FLOAT_MAT3x2      => "mat3x2",
+        gl::FLOAT_MAT3x4      => "mat3x4",
+        gl::FLOAT_MAT4        => "mat4",
+        gl::FLOAT_MAT4x2      => "mat4x2",
+        gl::FLOAT_MAT4x3      => "mat4x3",
+        gl::SAMPLER_2D        => "sampler2D",
+        gl::SAMPLER_3D        => "sampler3D",
+        gl::SAMPLER_CUBE      => "samplerCube",
+        gl::SAMPLER_2D_SHADOW => "sampler2DShadow",
+        gl::SAMPLER_2D_ARRAY  => "sampler2DArray",
+        _ if is_sampler_type(gl_type) => "sampler",
+        _ => "unknown type",
     }
 }
 
@@ -193,9 +268,35 @@ pub struct OpenGLContext {
     pub running_time_seconds: f64,
     pub framerate_time_seconds: f64,
     pub frame_count: u32,
+    /// The major and minor version of the context, which can be newer than the
+    /// version requested from GLFW.
+    gl_version: (u32, u32),
+    /// Dropped along with the context, which tells the GPU resources created with
+    /// the context that their objects are already gone.
+    lifetime: Rc<()>,
 }
 
 impl OpenGLContext {
+    /// The major and minor version of the OpenGL context.
+    #[inline]
+    pub fn gl_version(&self) -> (u32, u32) {
+        self.gl_version
+    }
+
+    /// Whether the context supports at least the given OpenGL version.
+    #[inline]
+    pub fn supports_gl_version(&self, major: u32, minor: u32) -> bool {
+        self.gl_version >= (major, minor)
+    }
+
+    /// A handle for tying the lifetime of a GPU resource to this context.
+    #[inline]
+    pub fn lifetime(&self) -> ContextLifetime {
+        ContextLifetime {
+            context: Rc::downgrade(&self.lifetime),
+        }
+    }
+
     /// Updates the timers in a GL context. It returns the elapsed time since the last call to
     /// `update_timers`.
     #[inline]
@@ -232,6 +333,8 @@ fn __init_glfw() -> Glfw {
     // glfw cannot change the properties of a window after it has been created.
     glfw.window_hint(glfw::WindowHint::Resizable(true));
     glfw.window_hint(glfw::WindowHint::Samples(Some(4)));
+    // macOS creates its newest core profile, OpenGL 4.1, for any request of 3.2 or
+    // later, so tessellation is available even though the request is for 3.3.
     glfw.window_hint(glfw::WindowHint::ContextVersionMajor(3));
     glfw.window_hint(glfw::WindowHint::ContextVersionMinor(3));
     glfw.window_hint(glfw::WindowHint::OpenGlForwardCompat(true));
@@ -297,6 +400,8 @@ pub fn start_opengl(width: u32, height: u32) -> Result<OpenGLContext, String> {
     window.set_size_polling(true);
     window.set_refresh_polling(true);
     window.set_size_polling(true);
+    window.set_cursor_pos_polling(true);
+    window.set_mouse_button_polling(true);
     window.set_sticky_keys(true);
 
     // Load the OpenGl function pointers.
@@ -309,6 +414,7 @@ pub fn start_opengl(width: u32, height: u32) -> Result<OpenGLContext, String> {
     let version = glubyte_ptr_to_string(unsafe { gl::GetString(gl::VERSION) });
     info!("OpenGL version supported: {}", version);
     info!("{}", gl_params());
+    let gl_version = query_gl_version();
 
     Ok(OpenGLContext {
         glfw: glfw, 
@@ -320,9 +426,245 @@ pub fn start_opengl(width: u32, height: u32) -> Result<OpenGLContext, String> {
         running_time_seconds: 0.0,
         framerate_time_seconds: 0.0,
         frame_count: 0,
+        gl_version: gl_version,
+        lifetime: Rc::new(()),
     })
 }
 
+/// Query the major and minor version of the current OpenGL context. Contexts older
+/// than OpenGL 3.0 cannot answer, and report version 0.0.
+fn query_gl_version() -> (u32, u32) {
+    let mut major = 0;
+    let mut minor = 0;
+    unsafe {
+        gl::GetIntegerv(gl::MAJOR_VERSION, &mut major);
+        gl::GetIntegerv(gl::MINOR_VERSION, &mut minor);
+    }
+
+    (major.max(0) as u32, minor.max(0) as u32)
+}
+
+
+/// Tracks whether the OpenGL context that created a GPU resource is still alive.
+/// The objects of a context are destroyed along with it, so a resource must only
+/// delete its object while its context lives.
+#[derive(Clone, Debug)]
+pub struct ContextLifetime {
+    context: Weak<()>,
+}
+
+impl ContextLifetime {
+    #[inline]
+    pub fn is_alive(&self) -> bool {
+        self.context.strong_count() > 0
+    }
+}
+
+/// An owned OpenGL vertex array object, deleted when dropped.
+#[derive(Debug)]
+pub struct VertexArray {
+    id: GLuint,
+    lifetime: ContextLifetime,
+}
+
+impl VertexArray {
+    pub fn new(context: &OpenGLContext) -> VertexArray {
+        let mut id = 0;
+        unsafe {
+            gl::GenVertexArrays(1, &mut id);
+        }
+        debug_assert!(id > 0);
+
+        VertexArray {
+            id: id,
+            lifetime: context.lifetime(),
+        }
+    }
+
+    #[inline]
+    pub fn id(&self) -> GLuint {
+        self.id
+    }
+
+    #[inline]
+    pub fn bind(&self) {
+        unsafe {
+            gl::BindVertexArray(self.id);
+        }
+    }
+}
+
+impl Drop for VertexArray {
+    fn drop(&mut self) {
+        if self.lifetime.is_alive() {
+            unsafe {
+                gl::DeleteVertexArrays(1, &self.id);
+            }
+        }
+    }
+}
+
+/// An owned OpenGL buffer object bound to a fixed target, deleted when dropped.
+#[derive(Debug)]
+pub struct Buffer {
+    id: GLuint,
+    target: GLenum,
+    lifetime: ContextLifetime,
+}
+
+impl Buffer {
+    /// Create an empty buffer for a binding target such as `gl::ARRAY_BUFFER`.
+    pub fn new(context: &OpenGLContext, target: GLenum) -> Buffer {
+        let mut id = 0;
+        unsafe {
+            gl::GenBuffers(1, &mut id);
+        }
+        debug_assert!(id > 0);
+
+        Buffer {
+            id: id,
+            target: target,
+            lifetime: context.lifetime(),
+        }
+    }
+
+    #[inline]
+    pub fn id(&self) -> GLuint {
+        self.id
+    }
+
+    #[inline]
+    pub fn target(&self) -> GLenum {
+        self.target
+    }
+
+    #[inline]
+    pub fn bind(&self) {
+        unsafe {
+            gl::BindBuffer(self.target, self.id);
+        }
+    }
+
+    /// Bind the buffer and replace its contents with `data`.
+    pub fn upload(&self, data: &[u8], usage: GLenum) {
+        self.bind();
+        unsafe {
+            gl::BufferData(self.target, data.len() as GLsizeiptr, data.as_ptr() as *const GLvoid, usage);
+        }
+    }
+
+    /// Bind the buffer and overwrite part of its contents, starting `offset` bytes
+    /// into the buffer. The buffer must already be large enough.
+    pub fn update(&self, offset: usize, data: &[u8]) {
+        self.bind();
+        unsafe {
+            gl::BufferSubData(self.target, offset as GLintptr, data.len() as GLsizeiptr, data.as_ptr() as *const GLvoid);
+        }
+    }
+
+    /// Read back the first `length` bytes of the buffer.
+    pub fn download(&self, length: usize) -> Vec<u8> {
+        let mut data = vec![0; length];
+        self.bind();
+        unsafe {
+            gl::GetBufferSubData(self.target, 0, length as GLsizeiptr, data.as_mut_ptr() as *mut GLvoid);
+        }
+
+        data
+    }
+
+    /// Bind the buffer to an indexed binding point of its target. The target must
+    /// be an indexed target such as `gl::SHADER_STORAGE_BUFFER`.
+    pub fn bind_base(&self, binding: u32) {
+        unsafe {
+            gl::BindBufferBase(self.target, binding, self.id);
+        }
+    }
+}
+
+impl Drop for Buffer {
+    fn drop(&mut self) {
+        if self.lifetime.is_alive() {
+            unsafe {
+                gl::DeleteBuffers(1, &self.id);
+            }
+        }
+    }
+}
+
+/// An owned OpenGL two dimensional texture, deleted when dropped.
+#[derive(Debug)]
+pub struct Texture2D {
+    id: GLuint,
+    lifetime: ContextLifetime,
+}
+
+impl Texture2D {
+    #[inline]
+    pub fn id(&self) -> GLuint {
+        self.id
+    }
+
+    /// Bind the texture to a texture unit.
+    #[inline]
+    pub fn bind(&self, unit: u32) {
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE0 + unit);
+            gl::BindTexture(gl::TEXTURE_2D, self.id);
+        }
+    }
+}
+
+impl Drop for Texture2D {
+    fn drop(&mut self) {
+        if self.lifetime.is_alive() {
+            unsafe {
+                gl::DeleteTextures(1, &self.id);
+            }
+        }
+    }
+}
+
+/// An owned, linked OpenGL shader program. The program is used through the cheaply
+/// cloned `ShaderHandle`, and every handle shares ownership of the program, which
+/// is deleted once the `Program` and all of its handles are dropped.
+#[derive(Debug)]
+pub struct Program {
+    handle: ShaderHandle,
+}
+
+impl Program {
+    /// Take ownership of a linked program object.
+    pub fn from_id(context: &OpenGLContext, id: GLuint) -> Program {
+        Program {
+            handle: ShaderHandle::new(context, id),
+        }
+    }
+
+    #[inline]
+    pub fn handle(&self) -> &ShaderHandle {
+        &self.handle
+    }
+}
+
+/// The OpenGL program object shared by a `Program` and its handles, deleted when
+/// the last of them is dropped.
+#[derive(Debug)]
+struct ProgramObject {
+    id: GLuint,
+    reflection: ProgramReflection,
+    lifetime: ContextLifetime,
+}
+
+impl Drop for ProgramObject {
+    fn drop(&mut self) {
+        if self.lifetime.is_alive() {
+            unsafe {
+                gl::DeleteProgram(self.id);
+            }
+        }
+    }
+}
 
 /// Validate that the shader program `shader` can execute with the current OpenGL program state.
 /// Use this for information purposes in application development. Return `true` if the program and
@@ -423,18 +765,74 @@ pub fn program_info_log(shader: GLuint) -> ProgramLog {
     ProgramLog { index: shader, log: log }
 }
 
+/// A programmable stage of the OpenGL pipeline.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ShaderStage {
+    Vertex,
+    TessControl,
+    TessEvaluation,
+    Geometry,
+    Fragment,
+    Compute,
+}
+
+impl ShaderStage {
+    /// The oldest OpenGL version with the stage. Tessellation arrived in OpenGL 4.0
+    /// and compute shaders in OpenGL 4.3.
+    #[inline]
+    pub fn min_gl_version(self) -> (u32, u32) {
+        match self {
+            ShaderStage::Vertex | ShaderStage::Geometry | ShaderStage::Fragment => (3, 3),
+            ShaderStage::TessControl | ShaderStage::TessEvaluation => (4, 0),
+            ShaderStage::Compute => (4, 3),
+        }
+    }
+
+    /// The OpenGL shader type of the stage.
+    #[inline]
+    pub fn gl_type(self) -> GLenum {
+        match self {
+            ShaderStage::Vertex => gl::VERTEX_SHADER,
+            ShaderStage::TessControl => gl::TESS_CONTROL_SHADER,
+            ShaderStage::TessEvaluation => gl::TESS_EVALUATION_SHADER,
+            ShaderStage::Geometry => gl::GEOMETRY_SHADER,
+            ShaderStage::Fragment => gl::FRAGMENT_SHADER,
+            ShaderStage::Compute => gl::COMPUTE_SHADER,
+        }
+    }
+}
+
+impl fmt::Display for ShaderStage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            ShaderStage::Vertex => "vertex",
+            ShaderStage::TessControl => "tessellation control",
+            ShaderStage::TessEvaluation => "tessellation evaluation",
+            ShaderStage::Geometry => "geometry",
+            ShaderStage::Fragment => "fragment",
+            ShaderStage::Compute => "compute",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum ErrorKind {
     ShaderNotFound,
     CouldNotParseShader,
+    CouldNotPreprocessShader,
     CouldNotCompileShader,
     CouldNotLinkShader,
     ShaderValidationFailed,
+    UnsupportedShaderStage,
 }
 
 #[derive(Clone, Debug)]
 pub struct ShaderCompilationError {
     kind: ErrorKind,
+    /// The stage that failed, or `None` for errors of the whole program.
+    stage: Option<ShaderStage>,
     shader: Option<u32>,
     shader_name: String,
     log: ProgramLog,
@@ -445,11 +843,57 @@ impl ShaderCompilationError {
     fn new(kind: ErrorKind, shader: Option<u32>, shader_name: String, log: ProgramLog) -> Self {
         Self {
             kind: kind,
+            stage: None,
             shader: shader,
             shader_name: shader_name,
             log: log,
         }
     }
+
+    /// An error for a shader stage that the preprocessor could not expand. The
+    /// preprocessor message takes the place of the OpenGL log.
+    pub(crate) fn from_preprocessor(stage: ShaderStage, shader_name: String, message: String) -> Self {
+        let kind = ErrorKind::CouldNotPreprocessShader;
+        let log = ProgramLog::new(0, message);
+
+        ShaderCompilationError::new(kind, None, shader_name, log).with_stage(stage)
+    }
+
+    #[inline]
+    fn with_stage(mut self, stage: ShaderStage) -> Self {
+        self.stage = Some(stage);
+        self
+    }
+
+    /// An error for a shader stage that the OpenGL context is too old to run.
+    fn unsupported_stage(stage: ShaderStage, shader_name: String, gl_version: (u32, u32)) -> Self {
+        let kind = ErrorKind::UnsupportedShaderStage;
+        let (major, minor) = stage.min_gl_version();
+        let message = format!(
+            "{} shaders need OpenGL {}.{}, but the context is OpenGL {}.{}.",
+            stage, major, minor, gl_version.0, gl_version.1
+        );
+        let log = ProgramLog::new(0, message);
+
+        ShaderCompilationError::new(kind, None, shader_name, log).with_stage(stage)
+    }
+
+    /// Rewrite the OpenGL log of the error.
+    pub(crate) fn map_log<F: FnOnce(&str) -> String>(mut self, f: F) -> Self {
+        self.log.log = f(&self.log.log);
+        self
+    }
+
+    #[inline]
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    /// The shader stage that failed to load or compile.
+    #[inline]
+    pub fn stage(&self) -> Option<ShaderStage> {
+        self.stage
+    }
 }
 
 impl fmt::Display for ShaderCompilationError {
@@ -467,12 +911,24 @@ impl fmt::Display for ShaderCompilationError {
                     self.shader_name
                 )
             }
-            ErrorKind::CouldNotCompileShader => {
-                write!(f, 
-                    "The shader program `{}` could not be compiled.\nLOG GENERATED BY OPENGL:\n{}\n", 
-                    self.shader_name, self.log
+            ErrorKind::CouldNotPreprocessShader => {
+                write!(f,
+                    "The shader `{}` could not be preprocessed.\n{}\n",
+                    self.shader_name, self.log.log
                 )
             }
+            ErrorKind::CouldNotCompileShader => {
+                match self.stage {
+                    Some(stage) => write!(f,
+                        "The {} shader `{}` could not be compiled.\nLOG GENERATED BY OPENGL:\n{}\n",
+                        stage, self.shader_name, self.log
+                    ),
+                    None => write!(f, 
+                        "The shader program `{}` could not be compiled.\nLOG GENERATED BY OPENGL:\n{}\n", 
+                        self.shader_name, self.log
+                    ),
+                }
+            }
             ErrorKind::CouldNotLinkShader => {
                 write!(f, 
                     "The shader program `{}` could not be linked.\nLOG GENERATED BY OPENGL:\n{}\n",
@@ -485,6 +941,12 @@ impl fmt::Display for ShaderCompilationError {
                     self.shader_name, self.log
                 )
             }
+            ErrorKind::UnsupportedShaderStage => {
+                write!(f,
+                    "The shader `{}` cannot run on this OpenGL context.\n{}\n",
+                    self.shader_name, self.log.log
+                )
+            }
         }
     }
 }
@@ -494,54 +956,43 @@ impl error::Error for ShaderCompilationError {}
 
 /// Load the shader source file(s).
 pub fn parse_shader<P: AsRef<Path>, R: Read>(
-    reader: &mut R, file_name: P, shader_str: &mut [u8]) -> Result<usize, ShaderCompilationError> {
+    reader: &mut R, file_name: P) -> Result<String, ShaderCompilationError> {
 
-    shader_str[0] = 0;
-    let bytes_read = match reader.read(shader_str) {
-        Ok(val) => val,
-        Err(_) => {
-            let kind = ErrorKind::CouldNotParseShader;
-            let shader_name = file_name.as_ref().display().to_string();
-            let log = ProgramLog::new(0, String::from(""));
-            return Err(ShaderCompilationError::new(kind, None, shader_name, log));
-        }
-    };
-
-    // Append \0 character to end of the shader string to mark the end of a C string.
-    shader_str[bytes_read] = 0;
+    let mut shader_string = String::new();
+    if reader.read_to_string(&mut shader_string).is_err() {
+        let kind = ErrorKind::CouldNotParseShader;
+        let shader_name = file_name.as_ref().display().to_string();
+        let log = ProgramLog::new(0, String::from(""));
+        return Err(ShaderCompilationError::new(kind, None, shader_name, log));
+    }
 
-    Ok(bytes_read)
+    Ok(shader_string)
 }
 
-/// Create a shader from source files.
+/// Create a shader for one pipeline stage from source files.
 pub fn compile_shader<P: AsRef<Path>, R: Read>(
-    reader: &mut R, file_name: P, kind: GLenum) -> Result<GLuint, ShaderCompilationError> {
+    reader: &mut R, file_name: P, stage: ShaderStage) -> Result<GLuint, ShaderCompilationError> {
 
     let disp = file_name.as_ref().display();
-    info!("Creating shader from {}.\n", disp);
+    info!("Creating {} shader from {}.\n", stage, disp);
 
-    let mut shader_string = vec![0; MAX_SHADER_LENGTH];
-    let bytes_read = match parse_shader(reader, &file_name, &mut shader_string) {
+    let shader_string = match parse_shader(reader, &file_name) {
         Ok(val) => val,
         Err(e) => {
+            let e = e.with_stage(stage);
             error!("{}", e);
             return Err(e);
         }
     };
 
-    if bytes_read >= (MAX_SHADER_LENGTH - 1) {
-        info!(
-            "WARNING: The shader was truncated because the shader code 
-            was longer than MAX_SHADER_LENGTH {} bytes.", MAX_SHADER_LENGTH
-        );
-    }
-
     let shader = unsafe { 
-        gl::CreateShader(kind)
+        gl::CreateShader(stage.gl_type())
     };
+    // Pass the length explicitly, so the source needs no terminating \0 character.
     let pointer = shader_string.as_ptr() as *const GLchar;
+    let length = shader_string.len() as GLint;
     unsafe {
-        gl::ShaderSource(shader, 1, &pointer, ptr::null());
+        gl::ShaderSource(shader, 1, &pointer, &length);
         gl::CompileShader(shader);
     }
 
@@ -557,29 +1008,35 @@ pub fn compile_shader<P: AsRef<Path>, R: Read>(
         let shader_name = file_name.as_ref().display().to_string();
         let shader_log = shader_info_log(shader);
         let log = ProgramLog::new(shader_log.index, shader_log.log);
-        error!("ERROR: GL shader index {} did not compile\n{}", shader, log);
-        
-        return Err(ShaderCompilationError::new(error_kind, shader_index, shader_name, log));
+        error!("ERROR: GL {} shader index {} did not compile\n{}", stage, shader, log);
+        unsafe {
+            gl::DeleteShader(shader);
+        }
+
+        return Err(ShaderCompilationError::new(error_kind, shader_index, shader_name, log).with_stage(stage));
     }
     info!("Shader compiled with index {}.\n", shader);
     
     Ok(shader)
 }
 
-/// Compile and link a shader program.
-pub fn link_shader(
-    vertex_shader: GLuint, fragment_shader: GLuint) -> Result<GLuint, ShaderCompilationError> {
-
+/// Link compiled shaders into a shader program. The shaders are deleted afterwards,
+/// whether or not linking succeeds.
+pub fn link_shader(shaders: &[GLuint]) -> Result<GLuint, ShaderCompilationError> {
     let program = unsafe { gl::CreateProgram() };
-    info!("Created program {}. Attaching shaders {} and {}.\n",
-        program, vertex_shader, fragment_shader
-    );
+    info!("Created program {}. Attaching shaders {:?}.\n", program, shaders);
 
     unsafe {
-        gl::AttachShader(program, vertex_shader);
-        gl::AttachShader(program, fragment_shader);
+        for &shader in shaders.iter() {
+            gl::AttachShader(program, shader);
+        }
         // Link the shader program. If binding input attributes, do that before linking.
         gl::LinkProgram(program);
+        // The linked program no longer needs the shader objects, so free them.
+        for &shader in shaders.iter() {
+            gl::DetachShader(program, shader);
+            gl::DeleteShader(shader);
+        }
     }
 
     let mut params = -1;
@@ -593,23 +1050,21 @@ pub fn link_shader(
         let log = program_info_log(program);
         error!("ERROR: could not link shader program GL index {}\n", program);
         error!("{}", log);
+        unsafe {
+            gl::DeleteProgram(program);
+        }
         return Err(ShaderCompilationError::new(kind, shader_index, shader_name, log));
     }
 
-    unsafe {
-        // Delete shaders here to free memory.
-        gl::DeleteShader(vertex_shader);
-        gl::DeleteShader(fragment_shader);
-    }
-
     Ok(program)
 }
 
-/// Compile and link a shader program directly from the files.
+/// Compile and link a shader program directly from the files. The program is
+/// deleted when the returned `Program` is dropped.
 pub fn compile_from_files<P: AsRef<Path>, Q: AsRef<Path>>(
     context: &OpenGLContext,
     vert_file_name: P, 
-    frag_file_name: Q) -> Result<GLuint, ShaderCompilationError> {
+    frag_file_name: Q) -> Result<Program, ShaderCompilationError> {
 
     let mut vert_reader = BufReader::new(match File::open(&vert_file_name) {
         Ok(val) => val,
@@ -618,7 +1073,8 @@ pub fn compile_from_files<P: AsRef<Path>, Q: AsRef<Path>>(
             let shader = None;
             let shader_name = vert_file_name.as_ref().display().to_string();
             let log = ProgramLog::new(0, String::from(""));
-            return Err(ShaderCompilationError::new(kind, shader, shader_name, log));
+            let error = ShaderCompilationError::new(kind, shader, shader_name, log);
+            return Err(error.with_stage(ShaderStage::Vertex));
         }
     });
     let mut frag_reader = BufReader::new(match File::open(&frag_file_name) {
@@ -628,54 +1084,171 @@ pub fn compile_from_files<P: AsRef<Path>, Q: AsRef<Path>>(
             let shader = None;
             let shader_name = frag_file_name.as_ref().display().to_string();
             let log = ProgramLog::new(0, String::from(""));
-            return Err(ShaderCompilationError::new(kind, shader, shader_name, log));
+            let error = ShaderCompilationError::new(kind, shader, shader_name, log);
+            return Err(error.with_stage(ShaderStage::Fragment));
         }
     });
 
-    let vertex_shader = compile_shader(
-        &mut vert_reader, vert_file_name, gl::VERTEX_SHADER
-    )?;
-    let fragment_shader = compile_shader(
-        &mut frag_reader, frag_file_name, gl::FRAGMENT_SHADER
-    )?;
-    let program = link_shader(vertex_shader, fragment_shader)?;
-
-    Ok(program)
+    compile_from_reader(context, &mut vert_reader, vert_file_name, &mut frag_reader, frag_file_name)
 }
 
-/// Compile and link a shader program directly from any readable sources.
+/// Compile and link a shader program directly from any readable sources. The
+/// program is deleted when the returned `Program` is dropped.
 pub fn compile_from_reader<R1: Read, P1: AsRef<Path>, R2: Read, P2: AsRef<Path>>(
+    context: &OpenGLContext,
     vert_reader: &mut R1, vert_file_name: P1,
-    frag_reader: &mut R2, frag_file_name: P2) -> Result<GLuint, ShaderCompilationError> {
+    frag_reader: &mut R2, frag_file_name: P2) -> Result<Program, ShaderCompilationError> {
 
-    let vertex_shader = compile_shader(
-        vert_reader, vert_file_name, gl::VERTEX_SHADER
-    )?;
-    let fragment_shader = compile_shader(
-        frag_reader, frag_file_name, gl::FRAGMENT_SHADER
-    )?;
+    let shader = compile_and_link(vec![
+        (ShaderStage::Vertex, vert_reader, vert_file_name.as_ref()),
+        (ShaderStage::Fragment, frag_reader, frag_file_name.as_ref()),
+    ])?;
+    debug_assert!(shader > 0);
 
-    let program = link_shader(vertex_shader, fragment_shader)?;
+    Ok(Program::from_id(context, shader))
+}
 
-    Ok(program)
+/// Compile every stage of a shader program and link them. The stages compiled so
+/// far are deleted when a later stage fails to compile.
+pub(crate) fn compile_and_link(stages: Vec<(ShaderStage, &mut dyn Read, &Path)>) -> Result<GLuint, ShaderCompilationError> {
+    let mut shaders = Vec::with_capacity(stages.len());
+    for (stage, mut reader, file_name) in stages.into_iter() {
+        match compile_shader(&mut reader, file_name, stage) {
+            Ok(shader) => shaders.push(shader),
+            Err(e) => {
+                for &shader in shaders.iter() {
+                    unsafe {
+                        gl::DeleteShader(shader);
+                    }
+                }
+                return Err(e);
+            }
+        }
+    }
+
+    link_shader(&shaders)
+}
+
+/// Check that the OpenGL context supports every stage of a shader program, before
+/// compiling any of them.
+pub(crate) fn check_stages_supported<'a, I>(context: &OpenGLContext, stages: I) -> Result<(), ShaderCompilationError>
+where
+    I: IntoIterator<Item = (ShaderStage, &'a str)>
+{
+    for (stage, name) in stages {
+        let (major, minor) = stage.min_gl_version();
+        if !context.supports_gl_version(major, minor) {
+            let e = ShaderCompilationError::unsupported_stage(stage, String::from(name), context.gl_version());
+            error!("{}", e);
+            return Err(e);
+        }
+    }
+
+    Ok(())
 }
 
-pub fn compile(shader_source: &ShaderSource) -> Result<ShaderHandle, ShaderCompilationError> {
+/// Compile and link a shader program from its sources, including the optional
+/// tessellation and geometry stages when the program has them. Programs with
+/// tessellation stages need an OpenGL 4.0 context.
+pub fn compile(context: &OpenGLContext, shader_source: &ShaderSource) -> Result<Program, ShaderCompilationError> {
     let mut vert_reader = Cursor::new(shader_source.vertex_source);
+    let mut tesc_reader = Cursor::new(shader_source.tess_control_source.unwrap_or(""));
+    let mut tese_reader = Cursor::new(shader_source.tess_evaluation_source.unwrap_or(""));
+    let mut geom_reader = Cursor::new(shader_source.geometry_source.unwrap_or(""));
     let mut frag_reader = Cursor::new(shader_source.fragment_source);
-    let result = compile_from_reader(
-        &mut vert_reader, shader_source.vertex_name,
-        &mut frag_reader, shader_source.fragment_name
-    );
-    let shader = match result {
-        Ok(value) => value,
-        Err(e) => {
-            panic!("Could not compile shaders. Got error: {}", e);
-        }
-    };
+    let mut stages: Vec<(ShaderStage, &mut dyn Read, &Path)> = vec![
+        (ShaderStage::Vertex, &mut vert_reader, Path::new(shader_source.vertex_name)),
+    ];
+    if let Some(tess_control_name) = shader_source.tess_control_name {
+        stages.push((ShaderStage::TessControl, &mut tesc_reader, Path::new(tess_control_name)));
+    }
+    if let Some(tess_evaluation_name) = shader_source.tess_evaluation_name {
+        stages.push((ShaderStage::TessEvaluation, &mut tese_reader, Path::new(tess_evaluation_name)));
+    }
+    if let Some(geometry_name) = shader_source.geometry_name {
+        stages.push((ShaderStage::Geometry, &mut geom_reader, Path::new(geometry_name)));
+    }
+    stages.push((ShaderStage::Fragment, &mut frag_reader, Path::new(shader_source.fragment_name)));
+
+    check_stages_supported(context, stages.iter().map(|&(stage, _, name)| (stage, name.to_str().unwrap_or(""))))?;
+    let shader = compile_and_link(stages)?;
+    debug_assert!(shader > 0);
+
+    Ok(Program::from_id(context, shader))
+}
+
+/// Compile and link a standalone compute shader program. Compute shaders require
+/// an OpenGL 4.3 context, and compiling one on an older context fails with
+/// `ErrorKind::UnsupportedShaderStage`.
+pub fn compile_compute(
+    context: &OpenGLContext,
+    shader_source: &ComputeShaderSource) -> Result<Program, ShaderCompilationError>
+{
+    check_stages_supported(context, vec![(ShaderStage::Compute, shader_source.name)])?;
+    let mut reader = Cursor::new(shader_source.source);
+    let shader = compile_and_link(vec![
+        (ShaderStage::Compute, &mut reader, Path::new(shader_source.name)),
+    ])?;
     debug_assert!(shader > 0);
 
-    Ok(ShaderHandle::new(shader))
+    Ok(Program::from_id(context, shader))
+}
+
+/// Run a compute shader program over a grid of work groups. Follow the dispatch
+/// with a `memory_barrier` before reading what the program wrote. The program
+/// comes from `compile_compute`, which only succeeds on an OpenGL 4.3 context.
+pub fn dispatch_compute(shader: &ShaderHandle, groups_x: u32, groups_y: u32, groups_z: u32) {
+    shader.use_program();
+    unsafe {
+        gl::DispatchCompute(groups_x, groups_y, groups_z);
+    }
+}
+
+/// The kinds of memory accesses a memory barrier orders after earlier shader
+/// writes. Combine them with `|`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct MemoryBarriers(GLbitfield);
+
+impl MemoryBarriers {
+    pub const VERTEX_ATTRIB_ARRAY: MemoryBarriers = MemoryBarriers(gl::VERTEX_ATTRIB_ARRAY_BARRIER_BIT);
+    pub const ELEMENT_ARRAY: MemoryBarriers = MemoryBarriers(gl::ELEMENT_ARRAY_BARRIER_BIT);
+    pub const UNIFORM: MemoryBarriers = MemoryBarriers(gl::UNIFORM_BARRIER_BIT);
+    pub const TEXTURE_FETCH: MemoryBarriers = MemoryBarriers(gl::TEXTURE_FETCH_BARRIER_BIT);
+    pub const BUFFER_UPDATE: MemoryBarriers = MemoryBarriers(gl::BUFFER_UPDATE_BARRIER_BIT);
+    pub const SHADER_STORAGE: MemoryBarriers = MemoryBarriers(gl::SHADER_STORAGE_BARRIER_BIT);
+    pub const ALL: MemoryBarriers = MemoryBarriers(gl::ALL_BARRIER_BITS);
+}
+
+impl ops::BitOr for MemoryBarriers {
+    type Output = MemoryBarriers;
+
+    #[inline]
+    fn bitor(self, other: MemoryBarriers) -> MemoryBarriers {
+        MemoryBarriers(self.0 | other.0)
+    }
+}
+
+/// Make the writes of earlier shader invocations visible to the given kinds of
+/// later memory accesses. Memory barriers need an OpenGL 4.2 context, which every
+/// context that runs compute shaders has.
+pub fn memory_barrier(barriers: MemoryBarriers) {
+    unsafe {
+        gl::MemoryBarrier(barriers.0);
+    }
+}
+
+/// Set the number of vertices in each patch drawn by programs with tessellation stages.
+/// Patches need an OpenGL 4.0 context.
+pub fn set_patch_vertices(context: &OpenGLContext, count: u32) -> Result<(), String> {
+    if !context.supports_gl_version(4, 0) {
+        let (major, minor) = context.gl_version();
+        return Err(format!("Patches need OpenGL 4.0, but the context is OpenGL {}.{}.", major, minor));
+    }
+    unsafe {
+        gl::PatchParameteri(gl::PATCH_VERTICES, count as GLint);
+    }
+
+    Ok(())
 }
 
 use cglinalg::{
@@ -695,6 +1268,10 @@ pub struct ShaderSource<'a, 'b, 'c> {
     fragment_source: &'b str,
     geometry_name: Option<&'c str>,
     geometry_source: Option< &'c str>,
+    tess_control_name: Option<&'c str>,
+    tess_control_source: Option<&'c str>,
+    tess_evaluation_name: Option<&'c str>,
+    tess_evaluation_source: Option<&'c str>,
 }
 
 pub struct ShaderSourceBuilder<'a, 'b, 'c> {
@@ -704,6 +1281,10 @@ pub struct ShaderSourceBuilder<'a, 'b, 'c> {
     fragment_source: &'b str,
     geometry_name: Option<&'c str>,
     geometry_source: Option< &'c str>,
+    tess_control_name: Option<&'c str>,
+    tess_control_source: Option<&'c str>,
+    tess_evaluation_name: Option<&'c str>,
+    tess_evaluation_source: Option<&'c str>,
 }
 
 impl<'a, 'b, 'c> ShaderSourceBuilder<'a, 'b, 'c> {
@@ -720,12 +1301,34 @@ impl<'a, 'b, 'c> ShaderSourceBuilder<'a, 'b, 'c> {
             fragment_source: fragment_source,
             geometry_name: None,
             geometry_source: None,
+            tess_control_name: None,
+            tess_control_source: None,
+            tess_evaluation_name: None,
+            tess_evaluation_source: None,
         }
     }
 
-    pub fn with_geometry_shader(&mut self, geometry_name: &'c str, geometry_source: &'c str) {
+    pub fn with_geometry_shader(mut self, geometry_name: &'c str, geometry_source: &'c str) -> Self {
         self.geometry_name = Some(geometry_name);
         self.geometry_source = Some(geometry_source);
+        self
+    }
+
+    /// Add the tessellation control and evaluation stages. Programs with these
+    /// stages draw `gl::PATCHES`, see `set_patch_vertices`, and `compile` rejects
+    /// them on contexts older than OpenGL 4.0.
+    pub fn with_tessellation_shaders(
+        mut self,
+        control_name: &'c str,
+        control_source: &'c str,
+        evaluation_name: &'c str,
+        evaluation_source: &'c str) -> Self
+    {
+        self.tess_control_name = Some(control_name);
+        self.tess_control_source = Some(control_source);
+        self.tess_evaluation_name = Some(evaluation_name);
+        self.tess_evaluation_source = Some(evaluation_source);
+        self
     }
 
     pub fn build(self) -> ShaderSource<'a, 'b, 'c> {
@@ -736,20 +1339,375 @@ impl<'a, 'b, 'c> ShaderSourceBuilder<'a, 'b, 'c> {
             fragment_source: self.fragment_source,
             geometry_name: self.geometry_name,
             geometry_source: self.geometry_source,
+            tess_control_name: self.tess_control_name,
+            tess_control_source: self.tess_control_source,
+            tess_evaluation_name: self.tess_evaluation_name,
+            tess_evaluation_source: self.tess_evaluation_source,
         }
     }
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+/// The source of a standalone compute shader program.
+#[derive(Copy, Clone, Debug)]
+pub struct ComputeShaderSource<'a> {
+    name: &'a str,
+    source: &'a str,
+}
+
+impl<'a> ComputeShaderSource<'a> {
+    pub fn new(name: &'a str, source: &'a str) -> Self {
+        Self {
+            name: name,
+            source: source,
+        }
+    }
+}
+
+/// An active attribute or uniform of a linked shader program.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ActiveVariable {
+    /// The name of the variable. Arrays are named without the `[0]` suffix OpenGL
+    /// reports for them.
+    pub name: String,
+    pub gl_type: GLenum,
+    /// The number of elements, which is 1 for variables that are not arrays.
+    pub array_size: usize,
+    /// The location of the variable, or -1 for uniforms in a uniform block and
+    /// built-in attributes.
+    pub location: i32,
+    /// The size of all the elements in bytes, or `None` when the size of the type
+    /// is unknown.
+    pub size: Option<usize>,
+}
+
+/// An active uniform block of a linked shader program.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ActiveUniformBlock {
+    pub name: String,
+    pub index: u32,
+    pub binding: u32,
+    /// The size of the buffer storage the block needs in bytes.
+    pub size: usize,
+    pub active_uniforms: usize,
+}
+
+/// The active attributes, uniforms and uniform blocks of a linked shader program,
+/// as OpenGL reports them.
+#[derive(Debug, Default)]
+pub struct ProgramReflection {
+    attributes: Vec<ActiveVariable>,
+    uniforms: Vec<ActiveVariable>,
+    uniform_blocks: Vec<ActiveUniformBlock>,
+    /// The location of every uniform outside a uniform block, and of every element
+    /// of the uniform arrays, found at link time. Other names are added as they are
+    /// looked up, with -1 for names that are not active uniforms.
+    uniform_locations: RefCell<HashMap<String, GLint>>,
+    /// The uniforms already warned about, so each problem is logged once and not
+    /// every frame.
+    warned_uniforms: RefCell<HashSet<String>>,
+}
+
+impl ProgramReflection {
+    /// Query the active variables of a linked program.
+    pub fn new(program: GLuint) -> ProgramReflection {
+        let uniforms = active_variables(program, true);
+        let uniform_locations = uniform_locations(program, &uniforms);
+
+        ProgramReflection {
+            attributes: active_variables(program, false),
+            uniforms: uniforms,
+            uniform_blocks: active_uniform_blocks(program),
+            uniform_locations: RefCell::new(uniform_locations),
+            warned_uniforms: RefCell::new(HashSet::new()),
+        }
+    }
+
+    #[inline]
+    pub fn attributes(&self) -> &[ActiveVariable] {
+        &self.attributes
+    }
+
+    #[inline]
+    pub fn uniforms(&self) -> &[ActiveVariable] {
+        &self.uniforms
+    }
+
+    #[inline]
+    pub fn uniform_blocks(&self) -> &[ActiveUniformBlock] {
+        &self.uniform_blocks
+    }
+
+    /// The uniforms of sampler types.
+    pub fn samplers(&self) -> impl Iterator<Item = &ActiveVariable> {
+        self.uniforms.iter().filter(|uniform| is_sampler_type(uniform.gl_type))
+    }
+
+    pub fn find_attribute(&self, name: &str) -> Option<&ActiveVariable> {
+        find_variable(&self.attributes, name)
+    }
+
+    /// Find a uniform by name. An array element such as `weights[2]` finds the
+    /// array it belongs to.
+    pub fn find_uniform(&self, name: &str) -> Option<&ActiveVariable> {
+        find_variable(&self.uniforms, name)
+    }
+
+    pub fn find_uniform_block(&self, name: &str) -> Option<&ActiveUniformBlock> {
+        self.uniform_blocks.iter().find(|block| block.name == name)
+    }
+
+    /// The location of a uniform or of an element of a uniform array in `program`,
+    /// or -1 if the program has no such active uniform. Names missing from the
+    /// locations found at link time, such as `weights[0]` for a one element array,
+    /// are asked of OpenGL the first time and cached, misses included.
+    pub fn uniform_location(&self, program: GLuint, name: &str) -> GLint {
+        if let Some(&location) = self.uniform_locations.borrow().get(name) {
+            return location;
+        }
+        let location = unsafe {
+            gl::GetUniformLocation(program, gl_str(name).as_ptr())
+        };
+        self.uniform_locations.borrow_mut().insert(String::from(name), location);
+
+        location
+    }
+
+    /// Warn once about a uniform that a `set_*` call could not set as the type it
+    /// writes.
+    fn validate_uniform(&self, program: GLuint, name: &str, gl_type: GLenum) {
+        let problem = match self.find_uniform(name) {
+            Some(uniform) if accepts_uniform_type(gl_type, uniform.gl_type) => return,
+            Some(uniform) => format!(
+                "Setting the uniform `{}` of shader program {} as a {}, but it is a {}.",
+                name, program, type_name(gl_type), type_name(uniform.gl_type)
+            ),
+            None => format!("Shader program {} has no active uniform `{}`.", program, name),
+        };
+        if self.warned_uniforms.borrow_mut().insert(String::from(name)) {
+            warn!("{}", problem);
+        }
+    }
+}
+
+/// The size of an active variable for the log, which may be unknown.
+fn size_description(size: Option<usize>) -> String {
+    match size {
+        Some(size) => format!("{} bytes", size),
+        None => String::from("unknown size"),
+    }
+}
+
+impl fmt::Display for ProgramReflection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Active attributes:")?;
+        for attribute in self.attributes.iter() {
+            writeln!(f,
+                "    {} {}[{}] at location {} ({})",
+                type_name(attribute.gl_type), attribute.name, attribute.array_size, attribute.location,
+                size_description(attribute.size)
+            )?;
+        }
+        writeln!(f, "Active uniforms:")?;
+        for uniform in self.uniforms.iter() {
+            writeln!(f,
+                "    {} {}[{}] at location {} ({})",
+                type_name(uniform.gl_type), uniform.name, uniform.array_size, uniform.location,
+                size_description(uniform.size)
+            )?;
+        }
+        writeln!(f, "Active uniform blocks:")?;
+        for block in self.uniform_blocks.iter() {
+            writeln!(f,
+                "    {} at index {} and binding {} ({} uniforms, {} bytes)",
+                block.name, block.index, block.binding, block.active_uniforms, block.size
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Whether a `set_*` call writing a value of type `set_type` can set a uniform of
+/// type `uniform_type`. Booleans and samplers are set as integers.
+fn accepts_uniform_type(set_type: GLenum, uniform_type: GLenum) -> bool {
+    set_type == uniform_type || match set_type {
+        gl::INT => uniform_type == gl::BOOL || is_sampler_type(uniform_type),
+        gl::BOOL => uniform_type == gl::INT,
+        _ => false,
+    }
+}
+
+fn find_variable<'a>(variables: &'a [ActiveVariable], name: &str) -> Option<&'a ActiveVariable> {
+    if let Some(variable) = variables.iter().find(|variable| variable.name == name) {
+        return Some(variable);
+    }
+
+    // Look up the array of an element such as `weights[2]`.
+    if !name.ends_with(']') {
+        return None;
+    }
+    let open = name.rfind('[')?;
+    let index: usize = name[(open + 1)..(name.len() - 1)].parse().ok()?;
+    variables.iter()
+        .find(|variable| variable.name == name[..open] && index < variable.array_size)
+}
+
+/// Query the active uniforms, or the active attributes, of a linked program.
+fn active_variables(program: GLuint, uniforms: bool) -> Vec<ActiveVariable> {
+    let (count_param, max_length_param) = if uniforms {
+        (gl::ACTIVE_UNIFORMS, gl::ACTIVE_UNIFORM_MAX_LENGTH)
+    } else {
+        (gl::ACTIVE_ATTRIBUTES, gl::ACTIVE_ATTRIBUTE_MAX_LENGTH)
+    };
+    let mut count = 0;
+    let mut max_length = 0;
+    unsafe {
+        gl::GetProgramiv(program, count_param, &mut count);
+        gl::GetProgramiv(program, max_length_param, &mut max_length);
+    }
+
+    let mut name_buffer = vec![0_u8; usize::max(max_length as usize, 1)];
+    let mut variables = Vec::with_capacity(count as usize);
+    for index in 0..(count as GLuint) {
+        let mut length = 0;
+        let mut array_size = 0;
+        let mut gl_type = 0;
+        let name_ptr = name_buffer.as_mut_ptr() as *mut GLchar;
+        unsafe {
+            if uniforms {
+                gl::GetActiveUniform(
+                    program, index, name_buffer.len() as GLint, &mut length, &mut array_size, &mut gl_type, name_ptr
+                );
+            } else {
+                gl::GetActiveAttrib(
+                    program, index, name_buffer.len() as GLint, &mut length, &mut array_size, &mut gl_type, name_ptr
+                );
+            }
+        }
+        let mut name = String::from_utf8_lossy(&name_buffer[..(length as usize)]).into_owned();
+        let location = unsafe {
+            if uniforms {
+                gl::GetUniformLocation(program, gl_str(&name).as_ptr())
+            } else {
+                gl::GetAttribLocation(program, gl_str(&name).as_ptr())
+            }
+        };
+        if name.ends_with("[0]") {
+            name.truncate(name.len() - 3);
+        }
+        let array_size = array_size as usize;
+        let size = type_size(gl_type).map(|size| array_size * size);
+        if size.is_none() {
+            warn!(
+                "Shader program {} has the variable `{}` of type {:#x}, whose size is unknown.",
+                program, name, gl_type
+            );
+        }
+
+        variables.push(ActiveVariable {
+            name: name,
+            gl_type: gl_type,
+            array_size: array_size,
+            location: location,
+            size: size,
+        });
+    }
+
+    variables
+}
+
+/// Look up the locations of the uniforms of a linked program once, including the
+/// location of every array element.
+fn uniform_locations(program: GLuint, uniforms: &[ActiveVariable]) -> HashMap<String, GLint> {
+    let mut locations = HashMap::new();
+    for uniform in uniforms.iter().filter(|uniform| uniform.location > -1) {
+        locations.insert(uniform.name.clone(), uniform.location);
+        if uniform.array_size > 1 {
+            for index in 0..uniform.array_size {
+                let element_name = format!("{}[{}]", uniform.name, index);
+                let location = unsafe {
+                    gl::GetUniformLocation(program, gl_str(&element_name).as_ptr())
+                };
+                locations.insert(element_name, location);
+            }
+        }
+    }
+
+    locations
+}
+
+/// Query the active uniform blocks of a linked program.
+fn active_uniform_blocks(program: GLuint) -> Vec<ActiveUniformBlock> {
+    let mut count = 0;
+    let mut max_length = 0;
+    unsafe {
+        gl::GetProgramiv(program, gl::ACTIVE_UNIFORM_BLOCKS, &mut count);
+        gl::GetProgramiv(program, gl::ACTIVE_UNIFORM_BLOCK_MAX_NAME_LENGTH, &mut max_length);
+    }
+
+    let mut name_buffer = vec![0_u8; usize::max(max_length as usize, 1)];
+    let mut blocks = Vec::with_capacity(count as usize);
+    for index in 0..(count as GLuint) {
+        let mut length = 0;
+        let mut binding = 0;
+        let mut size = 0;
+        let mut active_uniforms = 0;
+        unsafe {
+            gl::GetActiveUniformBlockName(
+                program, index, name_buffer.len() as GLint, &mut length, name_buffer.as_mut_ptr() as *mut GLchar
+            );
+            gl::GetActiveUniformBlockiv(program, index, gl::UNIFORM_BLOCK_BINDING, &mut binding);
+            gl::GetActiveUniformBlockiv(program, index, gl::UNIFORM_BLOCK_DATA_SIZE, &mut size);
+            gl::GetActiveUniformBlockiv(program, index, gl::UNIFORM_BLOCK_ACTIVE_UNIFORMS, &mut active_uniforms);
+        }
+
+        blocks.push(ActiveUniformBlock {
+            name: String::from_utf8_lossy(&name_buffer[..(length as usize)]).into_owned(),
+            index: index,
+            binding: binding as u32,
+            size: size as usize,
+            active_uniforms: active_uniforms as usize,
+        });
+    }
+
+    blocks
+}
+
+/// A handle to a linked shader program along with its reflection. Clones share
+/// ownership of the program, so the program lives as long as any handle to it.
+#[derive(Clone, Debug)]
 pub struct ShaderHandle {
     pub id: u32,
+    program: Rc<ProgramObject>,
 }
 
 impl ShaderHandle {
     #[inline]
-    const fn new(id: u32) -> ShaderHandle {
+    fn new(context: &OpenGLContext, id: u32) -> ShaderHandle {
+        let reflection = ProgramReflection::new(id);
+        info!("Reflection of shader program {}:\n{}", id, reflection);
+
         ShaderHandle {
             id: id,
+            program: Rc::new(ProgramObject {
+                id: id,
+                reflection: reflection,
+                lifetime: context.lifetime(),
+            }),
+        }
+    }
+
+    #[inline]
+    pub fn reflection(&self) -> &ProgramReflection {
+        &self.program.reflection
+    }
+
+    /// Check in debug builds that the program has an active uniform of a type
+    /// that a `set_*` call can write.
+    #[inline]
+    fn validate_uniform(&self, name: &str, gl_type: GLenum) {
+        if cfg!(debug_assertions) {
+            self.program.reflection.validate_uniform(self.id, name, gl_type);
         }
     }
 
@@ -770,89 +1728,482 @@ impl ShaderHandle {
         location as u32
     }
 
+    /// Look up the location of a vertex attribute, returning `None` if the program
+    /// has no active attribute with that name.
     #[inline]
-    pub fn get_uniform_location(&self, name: &str) -> i32 {
+    pub fn find_attrib_location(&self, name: &str) -> Option<u32> {
         let location = unsafe {
-            gl::GetUniformLocation(self.id, gl_str(name).as_ptr())
+            gl::GetAttribLocation(self.id, gl_str(name).as_ptr())
         };
-        debug_assert!(location > -1);
 
-        location
+        if location > -1 {
+            Some(location as u32)
+        } else {
+            None
+        }
     }
 
+    /// Look up the location of a uniform, returning `None` if the program has no
+    /// active uniform with that name.
     #[inline]
-    pub fn set_bool(&self, name: &str, value: bool) {
+    pub fn find_uniform_location(&self, name: &str) -> Option<i32> {
         let location = self.get_uniform_location(name);
+
+        if location > -1 {
+            Some(location)
+        } else {
+            None
+        }
+    }
+
+    /// Assign a shader storage block of the program to a binding point, to read and
+    /// write the buffer bound there with `Buffer::bind_base`. Returns `false` if the
+    /// program has no shader storage block with that name, or the context is older
+    /// than OpenGL 4.3 and has no shader storage blocks at all.
+    pub fn bind_storage_block(&self, name: &str, binding: u32) -> bool {
+        if !gl::GetProgramResourceIndex::is_loaded() {
+            return false;
+        }
+        let index = unsafe {
+            gl::GetProgramResourceIndex(self.id, gl::SHADER_STORAGE_BLOCK, gl_str(name).as_ptr())
+        };
+        if index == gl::INVALID_INDEX {
+            return false;
+        }
+        unsafe {
+            gl::ShaderStorageBlockBinding(self.id, index, binding);
+        }
+
+        true
+    }
+
+    /// Assign a uniform block of the program to a binding point, to read the buffer
+    /// bound there with `Buffer::bind_base`. Returns `false` if the program has no
+    /// active uniform block with that name.
+    pub fn bind_uniform_block(&self, name: &str, binding: u32) -> bool {
+        let index = match self.program.reflection.find_uniform_block(name) {
+            Some(block) => block.index,
+            None => return false,
+        };
         unsafe {
-            gl::Uniform1i(location, value as i32);
+            gl::UniformBlockBinding(self.id, index, binding);
         }
+
+        true
+    }
+
+    /// Look up the location of a uniform in the locations cached by the reflection
+    /// of the program, or -1 if the program has no such active uniform.
+    #[inline]
+    pub fn get_uniform_location(&self, name: &str) -> i32 {
+        self.program.reflection.uniform_location(self.id, name)
+    }
+
+    /// Locate a uniform once, to set it in the hot path without looking up its name.
+    /// This is the API for uniforms set every frame. The `set_*` methods below hash
+    /// the name and check its type on every call, which suits one-off settings.
+    #[inline]
+    pub fn uniform<T: UniformValue>(&self, name: &str) -> Uniform<T> {
+        self.validate_uniform(name, T::GL_TYPE);
+
+        Uniform::new(self.get_uniform_location(name))
+    }
+
+    #[inline]
+    pub fn set_bool(&self, name: &str, value: bool) {
+        self.uniform::<bool>(name).set(&value);
     }
 
     #[inline]
     pub fn set_int(&self, name: &str, value: i32) {
-        let location = self.get_uniform_location(name);
+        self.uniform::<i32>(name).set(&value);
+    }
+
+    #[inline]
+    pub fn set_float(&self, name: &str, value: f32) {
+        self.uniform::<f32>(name).set(&value);
+    }
+
+    #[inline]
+    pub fn set_vec2(&self, name: &str, value: &Vector2<f32>) {
+        self.uniform::<Vector2<f32>>(name).set(value);
+    }
+
+    #[inline]
+    pub fn set_vec3(&self, name: &str, value: &Vector3<f32>) {
+        self.uniform::<Vector3<f32>>(name).set(value);
+    }
+
+    #[inline]
+    pub fn set_vec4(&self, name: &str, value: &Vector4<f32>) {
+        self.uniform::<Vector4<f32>>(name).set(value);
+    }
+
+    #[inline]
+    pub fn set_mat2(&self, name: &str, value: &Matrix2<f32>) {
+        self.uniform::<Matrix2<f32>>(name).set(value);
+    }
+
+    #[inline]
+    pub fn set_mat3(&self, name: &str, value: &Matrix3<f32>) {
+        self.uniform::<Matrix3<f32>>(name).set(value);
+    }
+
+    #[inline]
+    pub fn set_mat4(&self, name: &str, value: &Matrix4<f32>) {
+        self.uniform::<Matrix4<f32>>(name).set(value);
+    }
+}
+
+/// A type that can be written to a uniform of a shader program.
+pub trait UniformValue {
+    /// The GLSL type of the uniforms the value is written to.
+    const GL_TYPE: GLenum;
+
+    /// Write the value to a uniform of the program in use.
+    fn set_uniform(&self, location: GLint);
+}
+
+impl UniformValue for bool {
+    const GL_TYPE: GLenum = gl::BOOL;
+
+    #[inline]
+    fn set_uniform(&self, location: GLint) {
         unsafe {
-            gl::Uniform1i(location, value);
+            gl::Uniform1i(location, *self as i32);
         }
     }
+}
+
+impl UniformValue for i32 {
+    const GL_TYPE: GLenum = gl::INT;
 
     #[inline]
-    pub fn set_float(&self, name: &str, value: f32) {
-        let location = self.get_uniform_location(name);
+    fn set_uniform(&self, location: GLint) {
         unsafe {
-            gl::Uniform1f(location, value);
+            gl::Uniform1i(location, *self);
         }
     }
+}
+
+impl UniformValue for f32 {
+    const GL_TYPE: GLenum = gl::FLOAT;
 
     #[inline]
-    pub fn set_vec2(&self, name: &str, value: &Vector2<f32>) {
-        let location = self.get_uniform_location(name);
+    fn set_uniform(&self, location: GLint) {
         unsafe {
-            gl::Uniform2fv(location, 1, value.as_ptr());
+            gl::Uniform1f(location, *self);
         }
     }
+}
+
+impl UniformValue for Vector2<f32> {
+    const GL_TYPE: GLenum = gl::FLOAT_VEC2;
 
     #[inline]
-    pub fn set_vec3(&self, name: &str, value: &Vector3<f32>) {
-        let location = self.get_uniform_location(name);
+    fn set_uniform(&self, location: GLint) {
         unsafe {
-            gl::Uniform3fv(location, 1, value.as_ptr());
+            gl::Uniform2fv(location, 1, self.as_ptr());
         }
     }
+}
+
+impl UniformValue for Vector3<f32> {
+    const GL_TYPE: GLenum = gl::FLOAT_VEC3;
 
     #[inline]
-    pub fn set_vec4(&self, name: &str, value: &Vector4<f32>) {
-        let location = self.get_uniform_location(name);
+    fn set_uniform(&self, location: GLint) {
         unsafe {
-            gl::Uniform4fv(location, 1, value.as_ptr());
+            gl::Uniform3fv(location, 1, self.as_ptr());
         }
     }
+}
+
+impl UniformValue for Vector4<f32> {
+    const GL_TYPE: GLenum = gl::FLOAT_VEC4;
 
     #[inline]
-    pub fn set_mat2(&self, name: &str, value: &Matrix2<f32>) {
-        let location = self.get_uniform_location(name);
+    fn set_uniform(&self, location: GLint) {
+        unsafe {
+            gl::Uniform4fv(location, 1, self.as_ptr());
+        }
+    }
+}
+
+impl UniformValue for Matrix2<f32> {
+    const GL_TYPE: GLenum = gl::FLOAT_MAT2;
+
+    #[inline]
+    fn set_uniform(&self, location: GLint) {
         unsafe {
-            gl::UniformMatrix2fv(location, 1, gl::FALSE, value.as_ptr());
+            gl::UniformMatrix2fv(location, 1, gl::FALSE, self.as_ptr());
         }
     }
+}
+
+impl UniformValue for Matrix3<f32> {
+    const GL_TYPE: GLenum = gl::FLOAT_MAT3;
 
     #[inline]
-    pub fn set_mat3(&self, name: &str, value: &Matrix3<f32>) {
-        let location = self.get_uniform_location(name);
+    fn set_uniform(&self, location: GLint) {
         unsafe {
-            gl::UniformMatrix3fv(location, 1, gl::FALSE, value.as_ptr());
+            gl::UniformMatrix3fv(location, 1, gl::FALSE, self.as_ptr());
         }
     }
+}
+
+impl UniformValue for Matrix4<f32> {
+    const GL_TYPE: GLenum = gl::FLOAT_MAT4;
 
     #[inline]
-    pub fn set_mat4(&self, name: &str, value: &Matrix4<f32>) {
-        let location = self.get_uniform_location(name);
+    fn set_uniform(&self, location: GLint) {
+        unsafe {
+            gl::UniformMatrix4fv(location, 1, gl::FALSE, self.as_ptr());
+        }
+    }
+}
+
+/// A located uniform of a shader program, typed by the values written to it. The
+/// location belongs to the program it came from, which must be in use when the
+/// uniform is set.
+#[derive(Debug)]
+pub struct Uniform<T> {
+    location: GLint,
+    _marker: PhantomData<T>,
+}
+
+impl<T> Clone for Uniform<T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Uniform<T> {}
+
+impl<T: UniformValue> Uniform<T> {
+    #[inline]
+    const fn new(location: GLint) -> Uniform<T> {
+        Uniform {
+            location: location,
+            _marker: PhantomData,
+        }
+    }
+
+    /// The location of the uniform, which is -1 for uniforms the program does not
+    /// have. Setting those does nothing.
+    #[inline]
+    pub fn location(&self) -> GLint {
+        self.location
+    }
+
+    #[inline]
+    pub fn set(&self, value: &T) {
+        value.set_uniform(self.location);
+    }
+}
+
+/// The component type of a vertex attribute as it is stored in a vertex buffer.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum AttributeType {
+    Float,
+    HalfFloat,
+    Byte,
+    UnsignedByte,
+    Short,
+    UnsignedShort,
+    Int,
+    UnsignedInt,
+}
+
+impl AttributeType {
+    #[inline]
+    pub fn gl_type(self) -> GLenum {
+        match self {
+            AttributeType::Float => gl::FLOAT,
+            AttributeType::HalfFloat => gl::HALF_FLOAT,
+            AttributeType::Byte => gl::BYTE,
+            AttributeType::UnsignedByte => gl::UNSIGNED_BYTE,
+            AttributeType::Short => gl::SHORT,
+            AttributeType::UnsignedShort => gl::UNSIGNED_SHORT,
+            AttributeType::Int => gl::INT,
+            AttributeType::UnsignedInt => gl::UNSIGNED_INT,
+        }
+    }
+
+    /// The size of one component in bytes.
+    #[inline]
+    pub fn size(self) -> usize {
+        match self {
+            AttributeType::Float | AttributeType::Int | AttributeType::UnsignedInt => 4,
+            AttributeType::HalfFloat | AttributeType::Short | AttributeType::UnsignedShort => 2,
+            AttributeType::Byte | AttributeType::UnsignedByte => 1,
+        }
+    }
+}
+
+/// A description of one attribute of an interleaved vertex. The attribute is
+/// matched to a shader input by `name`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct VertexAttribute {
+    pub name: &'static str,
+    pub components: usize,
+    pub attribute_type: AttributeType,
+    pub normalized: bool,
+    pub offset: usize,
+}
+
+impl VertexAttribute {
+    #[inline]
+    pub const fn new(
+        name: &'static str,
+        components: usize,
+        attribute_type: AttributeType,
+        normalized: bool,
+        offset: usize) -> VertexAttribute
+    {
+        VertexAttribute {
+            name: name,
+            components: components,
+            attribute_type: attribute_type,
+            normalized: normalized,
+            offset: offset,
+        }
+    }
+}
+
+/// A vertex type that can describe its own memory layout, so that vertex array
+/// objects can be set up for it without hand written attribute pointers.
+pub trait VertexLayout {
+    /// The distance in bytes between consecutive vertices.
+    fn stride() -> usize;
+
+    /// The attributes making up one vertex.
+    fn attributes() -> Vec<VertexAttribute>;
+}
+
+/// Point each vertex attribute at the currently bound array buffer. Attributes are
+/// matched to the inputs of `shader` by name, and attributes the shader does not
+/// use are skipped.
+pub fn setup_vertex_attributes(shader: &ShaderHandle, stride: usize, attributes: &[VertexAttribute]) {
+    for attribute in attributes.iter() {
+        let location = match shader.find_attrib_location(attribute.name) {
+            Some(location) => location,
+            None => {
+                info!("Shader program {} has no active vertex attribute `{}`.", shader.id, attribute.name);
+                continue;
+            }
+        };
+        let normalized = if attribute.normalized { gl::TRUE } else { gl::FALSE };
         unsafe {
-            gl::UniformMatrix4fv(location, 1, gl::FALSE, value.as_ptr());
+            gl::VertexAttribPointer(
+                location,
+                attribute.components as GLint,
+                attribute.attribute_type.gl_type(),
+                normalized,
+                stride as GLint,
+                attribute.offset as *const GLvoid
+            );
+            gl::EnableVertexAttribArray(location);
+        }
+    }
+}
+
+/// A vertex array object along with the vertex buffer and element buffer it draws from.
+#[derive(Debug)]
+pub struct VertexBuffers {
+    pub vertex_array: VertexArray,
+    pub vertex_buffer: Buffer,
+    pub index_buffer: Buffer,
+    pub index_count: usize,
+    /// The constant values of shader inputs the vertex buffer has no attribute for,
+    /// by attribute location.
+    pub constant_attributes: Vec<(GLuint, [f32; 4])>,
+}
+
+impl VertexBuffers {
+    /// Give the input `name` of `shader` a constant value for every vertex, in place
+    /// of an attribute missing from the vertex buffer. Inputs the shader does not
+    /// use are skipped.
+    pub fn set_constant_attribute(&mut self, shader: &ShaderHandle, name: &str, value: [f32; 4]) {
+        if let Some(location) = shader.find_attrib_location(name) {
+            self.constant_attributes.retain(|&(other, _)| other != location);
+            self.constant_attributes.push((location, value));
+        }
+    }
+
+    /// Bind the vertex array for drawing. Constant attribute values are not part of
+    /// the vertex array state, so they are set again on every bind.
+    pub fn bind(&self) {
+        self.vertex_array.bind();
+        for &(location, value) in self.constant_attributes.iter() {
+            unsafe {
+                gl::VertexAttrib4f(location, value[0], value[1], value[2], value[3]);
+            }
         }
     }
 }
 
+/// Create a vertex array object with a vertex buffer and an element buffer holding
+/// the given data, with its attributes set up for `shader`.
+pub fn send_to_gpu_vertex_data(
+    context: &OpenGLContext,
+    shader: &ShaderHandle,
+    vertex_data: &[u8],
+    stride: usize,
+    attributes: &[VertexAttribute],
+    vertex_indices: &[u32]) -> VertexBuffers
+{
+    let vertex_array = VertexArray::new(context);
+    let vertex_buffer = Buffer::new(context, gl::ARRAY_BUFFER);
+    let index_buffer = Buffer::new(context, gl::ELEMENT_ARRAY_BUFFER);
+
+    // SAFETY: The indices are plain integers, and the slice covers exactly the
+    // memory of `vertex_indices`.
+    let index_data = unsafe {
+        std::slice::from_raw_parts(vertex_indices.as_ptr() as *const u8, mem::size_of_val(vertex_indices))
+    };
+    vertex_array.bind();
+    vertex_buffer.upload(vertex_data, gl::STATIC_DRAW);
+    index_buffer.upload(index_data, gl::STATIC_DRAW);
+    setup_vertex_attributes(shader, stride, attributes);
+
+    VertexBuffers {
+        vertex_array: vertex_array,
+        vertex_buffer: vertex_buffer,
+        index_buffer: index_buffer,
+        index_count: vertex_indices.len(),
+        constant_attributes: vec![],
+    }
+}
+
+/// Create a vertex array object for a slice of vertices of a type with a known layout.
+pub fn send_to_gpu_vertices<V: VertexLayout>(
+    context: &OpenGLContext,
+    shader: &ShaderHandle,
+    vertices: &[V],
+    vertex_indices: &[u32]) -> VertexBuffers
+{
+    // SAFETY: Any initialized value can be viewed as bytes, and the slice covers
+    // exactly the memory of `vertices`.
+    let vertex_data = unsafe {
+        std::slice::from_raw_parts(vertices.as_ptr() as *const u8, mem::size_of_val(vertices))
+    };
+
+    send_to_gpu_vertex_data(context, shader, vertex_data, V::stride(), &V::attributes(), vertex_indices)
+}
+
+/// The range of line widths the OpenGL implementation can rasterize. Core profile
+/// contexts are only required to support a width of one.
+pub fn supported_line_width_range() -> (f32, f32) {
+    let mut range: [GLfloat; 2] = [1.0, 1.0];
+    unsafe {
+        gl::GetFloatv(gl::ALIASED_LINE_WIDTH_RANGE, range.as_mut_ptr());
+    }
+
+    (range[0], range[1])
+}
+
 pub fn from_png_buffer(buffer: &[u8]) -> TextureImage2D {
     let cursor = io::Cursor::new(buffer);
     let image_decoder = PngDecoder::new(cursor).unwrap();
@@ -912,12 +2263,20 @@ pub fn from_jpeg_reader<R: io::Read>(reader: &mut R) -> TextureImage2D {
 }
 
 /// Load texture image into the GPU.
-pub fn send_to_gpu_texture(texture_image: &TextureImage2D, wrapping_mode: GLuint) -> Result<GLuint, String> {
+pub fn send_to_gpu_texture(
+    context: &OpenGLContext,
+    texture_image: &TextureImage2D,
+    wrapping_mode: GLuint) -> Result<Texture2D, String>
+{
     let mut texture_id = 0;
     unsafe {
         gl::GenTextures(1, &mut texture_id);
     }
     debug_assert!(texture_id > 0);
+    let texture = Texture2D {
+        id: texture_id,
+        lifetime: context.lifetime(),
+    };
 
     let format = match texture_image.color_type {
         ColorType::Rgb8 => gl::RGB,
@@ -952,7 +2311,7 @@ pub fn send_to_gpu_texture(texture_image: &TextureImage2D, wrapping_mode: GLuint
         gl::TexParameterf(gl::TEXTURE_2D, GL_TEXTURE_MAX_ANISOTROPY_EXT, max_aniso);
     }
 
-    Ok(texture_id)
+    Ok(texture)
 }
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
@@ -0,0 +1,394 @@
+use cglinalg::{
+    Magnitude,
+    Matrix4,
+    Vector3,
+    Vector4,
+};
+
+
+/// An axis-aligned bounding box. An empty box has `min` greater than `max`
+/// on every axis so that extending it by any point yields that point.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: Vector3<f32>,
+    pub max: Vector3<f32>,
+}
+
+impl Aabb {
+    #[inline]
+    pub fn new(min: Vector3<f32>, max: Vector3<f32>) -> Aabb {
+        Aabb {
+            min: min,
+            max: max,
+        }
+    }
+
+    /// Construct a bounding box that contains no points.
+    #[inline]
+    pub fn empty() -> Aabb {
+        Aabb {
+            min: Vector3::new(f32::INFINITY, f32::INFINITY, f32::INFINITY),
+            max: Vector3::new(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY),
+        }
+    }
+
+    /// Construct the smallest bounding box containing every point in `points`.
+    pub fn from_points<'a, I>(points: I) -> Aabb
+        where I: IntoIterator<Item = &'a Vector3<f32>>
+    {
+        let mut aabb = Aabb::empty();
+        for point in points.into_iter() {
+            aabb.extend_point(point);
+        }
+
+        aabb
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+
+    /// Grow the bounding box so that it contains `point`.
+    #[inline]
+    pub fn extend_point(&mut self, point: &Vector3<f32>) {
+        self.min.x = f32::min(self.min.x, point.x);
+        self.min.y = f32::min(self.min.y, point.y);
+        self.min.z = f32::min(self.min.z, point.z);
+        self.max.x = f32::max(self.max.x, point.x);
+        self.max.y = f32::max(self.max.y, point.y);
+        self.max.z = f32::max(self.max.z, point.z);
+    }
+
+    /// Compute the smallest bounding box containing both `self` and `other`.
+    #[inline]
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        let mut aabb = *self;
+        if !other.is_empty() {
+            aabb.extend_point(&other.min);
+            aabb.extend_point(&other.max);
+        }
+
+        aabb
+    }
+
+    #[inline]
+    pub fn center(&self) -> Vector3<f32> {
+        (self.min + self.max) * 0.5
+    }
+
+    /// The length of the bounding box along each axis.
+    #[inline]
+    pub fn extents(&self) -> Vector3<f32> {
+        if self.is_empty() {
+            Vector3::zero()
+        } else {
+            self.max - self.min
+        }
+    }
+
+    /// The total area of the faces of the bounding box.
+    #[inline]
+    pub fn surface_area(&self) -> f32 {
+        let extents = self.extents();
+
+        2.0 * (extents.x * extents.y + extents.y * extents.z + extents.z * extents.x)
+    }
+
+    #[inline]
+    pub fn contains_point(&self, point: &Vector3<f32>) -> bool {
+        point.x >= self.min.x && point.x <= self.max.x &&
+        point.y >= self.min.y && point.y <= self.max.y &&
+        point.z >= self.min.z && point.z <= self.max.z
+    }
+
+    /// The eight corners of the bounding box.
+    pub fn corners(&self) -> [Vector3<f32>; 8] {
+        [
+            Vector3::new(self.min.x, self.min.y, self.min.z),
+            Vector3::new(self.max.x, self.min.y, self.min.z),
+            Vector3::new(self.min.x, self.max.y, self.min.z),
+            Vector3::new(self.max.x, self.max.y, self.min.z),
+            Vector3::new(self.min.x, self.min.y, self.max.z),
+            Vector3::new(self.max.x, self.min.y, self.max.z),
+            Vector3::new(self.min.x, self.max.y, self.max.z),
+            Vector3::new(self.max.x, self.max.y, self.max.z),
+        ]
+    }
+
+    /// Compute the bounding box of this box after an affine transformation. The
+    /// result is conservative: it bounds the transformed box, not the transformed
+    /// geometry inside it.
+    pub fn transform(&self, matrix: &Matrix4<f32>) -> Aabb {
+        if self.is_empty() {
+            return *self;
+        }
+
+        let mut aabb = Aabb::empty();
+        for corner in self.corners().iter() {
+            let transformed = matrix * Vector4::new(corner.x, corner.y, corner.z, 1.0);
+            aabb.extend_point(&Vector3::new(transformed.x, transformed.y, transformed.z));
+        }
+
+        aabb
+    }
+}
+
+/// A bounding sphere. An empty sphere has a negative radius.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BoundingSphere {
+    pub center: Vector3<f32>,
+    pub radius: f32,
+}
+
+impl BoundingSphere {
+    #[inline]
+    pub fn new(center: Vector3<f32>, radius: f32) -> BoundingSphere {
+        BoundingSphere {
+            center: center,
+            radius: radius,
+        }
+    }
+
+    /// Construct a bounding sphere that contains no points.
+    #[inline]
+    pub fn empty() -> BoundingSphere {
+        BoundingSphere {
+            center: Vector3::zero(),
+            radius: -1.0,
+        }
+    }
+
+    /// Construct a bounding sphere for a point set. The sphere is centered on
+    /// the bounding box of the points, which is cheap to compute and stable
+    /// under small changes to the input.
+    pub fn from_points<'a, I>(points: I) -> BoundingSphere
+        where I: IntoIterator<Item = &'a Vector3<f32>> + Clone
+    {
+        let aabb = Aabb::from_points(points.clone());
+        if aabb.is_empty() {
+            return BoundingSphere::empty();
+        }
+
+        let center = aabb.center();
+        let mut radius_squared = 0.0;
+        for point in points.into_iter() {
+            radius_squared = f32::max(radius_squared, (point - center).magnitude_squared());
+        }
+
+        BoundingSphere::new(center, f32::sqrt(radius_squared))
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.radius < 0.0
+    }
+
+    /// Compute a bounding sphere containing both `self` and `other`.
+    pub fn union(&self, other: &BoundingSphere) -> BoundingSphere {
+        if other.is_empty() {
+            return *self;
+        }
+        if self.is_empty() {
+            return *other;
+        }
+
+        let offset = other.center - self.center;
+        let distance = offset.magnitude();
+        if distance + other.radius <= self.radius {
+            return *self;
+        }
+        if distance + self.radius <= other.radius {
+            return *other;
+        }
+
+        let radius = (distance + self.radius + other.radius) * 0.5;
+        let center = self.center + offset * ((radius - self.radius) / distance);
+
+        BoundingSphere::new(center, radius)
+    }
+
+    #[inline]
+    pub fn contains_point(&self, point: &Vector3<f32>) -> bool {
+        (point - self.center).magnitude_squared() <= self.radius * self.radius
+    }
+
+    /// Transform a bounding sphere by an affine matrix. The radius is scaled by the
+    /// largest scale factor of the matrix, so the result bounds the transformed sphere.
+    pub fn transform(&self, matrix: &Matrix4<f32>) -> BoundingSphere {
+        if self.is_empty() {
+            return *self;
+        }
+
+        let center = matrix * Vector4::new(self.center.x, self.center.y, self.center.z, 1.0);
+        let scale = (0..3)
+            .map(|i| Vector3::new(matrix[i][0], matrix[i][1], matrix[i][2]).magnitude())
+            .fold(0.0, f32::max);
+
+        BoundingSphere::new(Vector3::new(center.x, center.y, center.z), self.radius * scale)
+    }
+
+    /// Estimate the fraction of the viewport height the sphere covers when seen from
+    /// `eye` through a perspective projection with vertical field of view `fovy_radians`.
+    pub fn screen_size(&self, eye: &Vector3<f32>, fovy_radians: f32) -> f32 {
+        if self.is_empty() {
+            return 0.0;
+        }
+
+        let distance = (self.center - eye).magnitude();
+        if distance <= self.radius {
+            return 1.0;
+        }
+
+        self.radius / (distance * f32::tan(0.5 * fovy_radians))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+
+    fn assert_vector_near(result: &Vector3<f32>, expected: &Vector3<f32>) {
+        assert!((result - expected).magnitude() < 1e-5, "{:?} != {:?}", result, expected);
+    }
+
+    fn points() -> Vec<Vector3<f32>> {
+        vec![
+            Vector3::new(1.0, 2.0, 3.0),
+            Vector3::new(-1.0, 0.0, 5.0),
+            Vector3::new(0.0, -4.0, 1.0),
+        ]
+    }
+
+    /// Scale by (2, 3, 4), then rotate a quarter turn about the z axis, then
+    /// translate by (1, 2, 3).
+    fn rotate_scale_translate() -> Matrix4<f32> {
+        let mut matrix = Matrix4::zero();
+        matrix[0][1] = 2.0;
+        matrix[1][0] = -3.0;
+        matrix[2][2] = 4.0;
+        matrix[3][0] = 1.0;
+        matrix[3][1] = 2.0;
+        matrix[3][2] = 3.0;
+        matrix[3][3] = 1.0;
+
+        matrix
+    }
+
+    /// Rotate an eighth of a turn about the z axis.
+    fn rotate_eighth_turn() -> Matrix4<f32> {
+        let c = f32::cos(std::f32::consts::FRAC_PI_4);
+        let s = f32::sin(std::f32::consts::FRAC_PI_4);
+        let mut matrix = Matrix4::identity();
+        matrix[0][0] = c;
+        matrix[0][1] = s;
+        matrix[1][0] = -s;
+        matrix[1][1] = c;
+
+        matrix
+    }
+
+    #[test]
+    fn test_aabb_from_points() {
+        let aabb = Aabb::from_points(points().iter());
+
+        assert_eq!(aabb, Aabb::new(Vector3::new(-1.0, -4.0, 1.0), Vector3::new(1.0, 2.0, 5.0)));
+        assert_eq!(aabb.center(), Vector3::new(0.0, -1.0, 3.0));
+        assert_eq!(aabb.extents(), Vector3::new(2.0, 6.0, 4.0));
+        assert_eq!(aabb.surface_area(), 2.0 * (12.0 + 24.0 + 8.0));
+        assert!(points().iter().all(|point| aabb.contains_point(point)));
+    }
+
+    #[test]
+    fn test_aabb_from_no_points_is_empty() {
+        let aabb = Aabb::from_points(vec![].iter());
+
+        assert!(aabb.is_empty());
+        assert_eq!(aabb.extents(), Vector3::zero());
+        assert!(!aabb.contains_point(&Vector3::zero()));
+    }
+
+    #[test]
+    fn test_aabb_union() {
+        let a = Aabb::new(Vector3::new(0.0, 0.0, 0.0), Vector3::new(1.0, 1.0, 1.0));
+        let b = Aabb::new(Vector3::new(-1.0, 0.5, 2.0), Vector3::new(0.5, 3.0, 4.0));
+        let expected = Aabb::new(Vector3::new(-1.0, 0.0, 0.0), Vector3::new(1.0, 3.0, 4.0));
+
+        assert_eq!(a.union(&b), expected);
+        assert_eq!(b.union(&a), expected);
+        assert_eq!(a.union(&Aabb::empty()), a);
+        assert_eq!(Aabb::empty().union(&a), a);
+        assert!(Aabb::empty().union(&Aabb::empty()).is_empty());
+    }
+
+    #[test]
+    fn test_aabb_transform_rotation_and_non_uniform_scale() {
+        let aabb = Aabb::new(Vector3::new(0.0, 0.0, 0.0), Vector3::new(1.0, 1.0, 1.0));
+        let result = aabb.transform(&rotate_scale_translate());
+
+        assert_vector_near(&result.min, &Vector3::new(-2.0, 2.0, 3.0));
+        assert_vector_near(&result.max, &Vector3::new(1.0, 4.0, 7.0));
+    }
+
+    #[test]
+    fn test_aabb_transform_bounds_rotated_corners() {
+        let aabb = Aabb::new(Vector3::new(-1.0, -1.0, -1.0), Vector3::new(1.0, 1.0, 1.0));
+        let result = aabb.transform(&rotate_eighth_turn());
+        let half_diagonal = f32::sqrt(2.0);
+
+        assert_vector_near(&result.min, &Vector3::new(-half_diagonal, -half_diagonal, -1.0));
+        assert_vector_near(&result.max, &Vector3::new(half_diagonal, half_diagonal, 1.0));
+        assert!(Aabb::empty().transform(&rotate_eighth_turn()).is_empty());
+    }
+
+    #[test]
+    fn test_sphere_from_points() {
+        let sphere = BoundingSphere::from_points(points().iter());
+
+        assert_eq!(sphere.center, Vector3::new(0.0, -1.0, 3.0));
+        assert!((sphere.radius - f32::sqrt(13.0)).abs() < 1e-6);
+        assert!(points().iter().all(|point| sphere.contains_point(point)));
+        assert!(BoundingSphere::from_points(vec![].iter()).is_empty());
+    }
+
+    #[test]
+    fn test_sphere_union() {
+        let a = BoundingSphere::new(Vector3::new(0.0, 0.0, 0.0), 1.0);
+        let b = BoundingSphere::new(Vector3::new(4.0, 0.0, 0.0), 1.0);
+        let inner = BoundingSphere::new(Vector3::new(0.5, 0.0, 0.0), 0.25);
+        let result = a.union(&b);
+
+        assert_vector_near(&result.center, &Vector3::new(2.0, 0.0, 0.0));
+        assert!((result.radius - 3.0).abs() < 1e-6);
+        assert_eq!(result, b.union(&a));
+        assert_eq!(a.union(&inner), a);
+        assert_eq!(inner.union(&a), a);
+        assert_eq!(a.union(&BoundingSphere::empty()), a);
+        assert_eq!(BoundingSphere::empty().union(&a), a);
+    }
+
+    #[test]
+    fn test_sphere_transform_rotation_and_non_uniform_scale() {
+        let sphere = BoundingSphere::new(Vector3::new(1.0, 0.0, 0.0), 1.0);
+        let result = sphere.transform(&rotate_scale_translate());
+
+        assert_vector_near(&result.center, &Vector3::new(1.0, 4.0, 3.0));
+        assert!((result.radius - 4.0).abs() < 1e-6);
+        assert!(BoundingSphere::empty().transform(&rotate_scale_translate()).is_empty());
+    }
+
+    #[test]
+    fn test_sphere_screen_size() {
+        let sphere = BoundingSphere::new(Vector3::zero(), 1.0);
+        let fovy = std::f32::consts::FRAC_PI_2;
+        let near = sphere.screen_size(&Vector3::new(0.0, 0.0, 10.0), fovy);
+        let far = sphere.screen_size(&Vector3::new(0.0, 0.0, 20.0), fovy);
+
+        assert!((near - 0.1).abs() < 1e-6);
+        assert!((far - 0.05).abs() < 1e-6);
+        assert_eq!(sphere.screen_size(&Vector3::new(0.0, 0.5, 0.0), fovy), 1.0);
+        assert_eq!(BoundingSphere::empty().screen_size(&Vector3::zero(), fovy), 0.0);
+    }
+}
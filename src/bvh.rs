@@ -0,0 +1,643 @@
+use crate::bounds::{
+    Aabb,
+};
+use crate::model::{
+    Mesh,
+    Model,
+};
+use cglinalg::{
+    Vector3,
+};
+
+
+/// The number of bins candidate splits are evaluated at along each axis.
+const BIN_COUNT: usize = 16;
+/// Nodes with at most this many triangles become leaves when splitting them does not pay off.
+const MAX_LEAF_SIZE: usize = 4;
+/// The cost of visiting a node relative to the cost of intersecting a triangle.
+const TRAVERSAL_COST: f32 = 1.0;
+/// Triangles this close to parallel to a ray are treated as missing it.
+const PARALLEL_EPSILON: f32 = 1e-8;
+
+
+/// A half line starting at `origin`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Ray {
+    pub origin: Vector3<f32>,
+    pub direction: Vector3<f32>,
+}
+
+impl Ray {
+    #[inline]
+    pub fn new(origin: Vector3<f32>, direction: Vector3<f32>) -> Ray {
+        Ray {
+            origin: origin,
+            direction: direction,
+        }
+    }
+
+    /// The point at parameter `t` along the ray. Distances along the ray are measured
+    /// in multiples of the length of `direction`.
+    #[inline]
+    pub fn at(&self, t: f32) -> Vector3<f32> {
+        self.origin + self.direction * t
+    }
+}
+
+/// An intersection between a ray and a triangle of a model.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RayHit {
+    /// The ray parameter of the intersection point.
+    pub distance: f32,
+    /// The weights of the three corners of the triangle at the intersection point.
+    pub barycentrics: Vector3<f32>,
+    /// The index of the mesh in `Model::meshes`.
+    pub mesh_index: usize,
+    /// The index of the triangle in `Mesh::vertex_indices` divided by three.
+    pub triangle_index: usize,
+}
+
+#[derive(Copy, Clone, Debug)]
+struct BvhTriangle {
+    mesh_index: u32,
+    triangle_index: u32,
+    positions: [Vector3<f32>; 3],
+}
+
+impl BvhTriangle {
+    #[inline]
+    fn aabb(&self) -> Aabb {
+        Aabb::from_points(self.positions.iter())
+    }
+
+    #[inline]
+    fn centroid(&self) -> Vector3<f32> {
+        (self.positions[0] + self.positions[1] + self.positions[2]) * (1.0 / 3.0)
+    }
+
+    /// Intersect a ray with the triangle using the Moller-Trumbore algorithm. Both
+    /// sides of the triangle are hit. Returns the ray parameter and the barycentric
+    /// coordinates of the second and third corners.
+    fn intersect(&self, ray: &Ray) -> Option<(f32, f32, f32)> {
+        let edge1 = self.positions[1] - self.positions[0];
+        let edge2 = self.positions[2] - self.positions[0];
+        let p = ray.direction.cross(&edge2);
+        let determinant = edge1.dot(&p);
+        if determinant.abs() < PARALLEL_EPSILON {
+            return None;
+        }
+
+        let inverse_determinant = 1.0 / determinant;
+        let s = ray.origin - self.positions[0];
+        let u = s.dot(&p) * inverse_determinant;
+        if u < 0.0 || u > 1.0 {
+            return None;
+        }
+        let q = s.cross(&edge1);
+        let v = ray.direction.dot(&q) * inverse_determinant;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+
+        Some((edge2.dot(&q) * inverse_determinant, u, v))
+    }
+}
+
+/// A node of the hierarchy. A leaf holds `count` triangles starting at `first`, and
+/// an interior node has a count of zero and its two children at `first` and `first + 1`.
+#[derive(Copy, Clone, Debug)]
+struct BvhNode {
+    aabb: Aabb,
+    first: u32,
+    count: u32,
+}
+
+impl BvhNode {
+    #[inline]
+    fn is_leaf(&self) -> bool {
+        self.count > 0
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+struct Bin {
+    aabb: Aabb,
+    count: usize,
+}
+
+/// Intersect a ray with a bounding box, returning the ray parameter the ray enters it at.
+#[inline]
+fn intersect_aabb(aabb: &Aabb, ray: &Ray, inverse_direction: &Vector3<f32>, max_distance: f32) -> Option<f32> {
+    let tx1 = (aabb.min.x - ray.origin.x) * inverse_direction.x;
+    let tx2 = (aabb.max.x - ray.origin.x) * inverse_direction.x;
+    let ty1 = (aabb.min.y - ray.origin.y) * inverse_direction.y;
+    let ty2 = (aabb.max.y - ray.origin.y) * inverse_direction.y;
+    let tz1 = (aabb.min.z - ray.origin.z) * inverse_direction.z;
+    let tz2 = (aabb.max.z - ray.origin.z) * inverse_direction.z;
+    let t_enter = f32::max(f32::max(f32::min(tx1, tx2), f32::min(ty1, ty2)), f32::min(tz1, tz2));
+    let t_exit = f32::min(f32::min(f32::max(tx1, tx2), f32::max(ty1, ty2)), f32::max(tz1, tz2));
+
+    if t_exit >= f32::max(t_enter, 0.0) && t_enter <= max_distance {
+        Some(t_enter)
+    } else {
+        None
+    }
+}
+
+#[inline]
+fn component(vector: &Vector3<f32>, axis: usize) -> f32 {
+    match axis {
+        0 => vector.x,
+        1 => vector.y,
+        _ => vector.z,
+    }
+}
+
+/// A bounding volume hierarchy over the triangles of a set of meshes, built with
+/// the surface area heuristic evaluated at a fixed number of bins per axis.
+#[derive(Clone, Debug)]
+pub struct Bvh {
+    nodes: Vec<BvhNode>,
+    triangles: Vec<BvhTriangle>,
+}
+
+impl Bvh {
+    /// Build a hierarchy over every triangle of a model at its full level of detail.
+    pub fn new(model: &Model) -> Bvh {
+        Bvh::from_meshes(&model.meshes)
+    }
+
+    /// Build a hierarchy over every triangle of a list of meshes.
+    pub fn from_meshes(meshes: &[Mesh]) -> Bvh {
+        let mut triangles = vec![];
+        for (mesh_index, mesh) in meshes.iter().enumerate() {
+            for (triangle_index, indices) in mesh.vertex_indices.chunks_exact(3).enumerate() {
+                if indices.iter().any(|&index| index as usize >= mesh.vertices.len()) {
+                    continue;
+                }
+                triangles.push(BvhTriangle {
+                    mesh_index: mesh_index as u32,
+                    triangle_index: triangle_index as u32,
+                    positions: [
+                        mesh.vertices[indices[0] as usize].position,
+                        mesh.vertices[indices[1] as usize].position,
+                        mesh.vertices[indices[2] as usize].position,
+                    ],
+                });
+            }
+        }
+
+        let mut bvh = Bvh {
+            nodes: vec![],
+            triangles: triangles,
+        };
+        if !bvh.triangles.is_empty() {
+            let mut centroids: Vec<Vector3<f32>> = bvh.triangles.iter()
+                .map(|triangle| triangle.centroid())
+                .collect();
+            bvh.nodes.push(BvhNode {
+                aabb: Aabb::empty(),
+                first: 0,
+                count: 0,
+            });
+            let triangle_count = bvh.triangles.len();
+            bvh.build_node(0, 0, triangle_count, &mut centroids);
+        }
+
+        bvh
+    }
+
+    /// Split the triangles in `start..end` below the node at `node_index`.
+    fn build_node(&mut self, node_index: usize, start: usize, end: usize, centroids: &mut [Vector3<f32>]) {
+        let mut aabb = Aabb::empty();
+        let mut centroid_aabb = Aabb::empty();
+        for i in start..end {
+            aabb = aabb.union(&self.triangles[i].aabb());
+            centroid_aabb.extend_point(&centroids[i]);
+        }
+        let count = end - start;
+        self.nodes[node_index].aabb = aabb;
+
+        let split = self.find_split(start, end, &centroid_aabb, &aabb, centroids);
+        let middle = match split {
+            Some((axis, bin)) => {
+                let min = component(&centroid_aabb.min, axis);
+                let extent = component(&centroid_aabb.extents(), axis);
+                let mut middle = start;
+                for i in start..end {
+                    let offset = (component(&centroids[i], axis) - min) / extent;
+                    let index = usize::min((offset * BIN_COUNT as f32) as usize, BIN_COUNT - 1);
+                    if index < bin {
+                        self.triangles.swap(i, middle);
+                        centroids.swap(i, middle);
+                        middle += 1;
+                    }
+                }
+
+                middle
+            }
+            None if count <= MAX_LEAF_SIZE => start,
+            // No split separates the centroids, but the node is too large for a
+            // leaf, so divide it in two arbitrarily.
+            None => start + count / 2,
+        };
+
+        if middle == start || middle == end {
+            self.nodes[node_index].first = start as u32;
+            self.nodes[node_index].count = count as u32;
+            return;
+        }
+
+        let left = self.nodes.len();
+        for _ in 0..2 {
+            self.nodes.push(BvhNode {
+                aabb: Aabb::empty(),
+                first: 0,
+                count: 0,
+            });
+        }
+        self.nodes[node_index].first = left as u32;
+        self.nodes[node_index].count = 0;
+        self.build_node(left, start, middle, centroids);
+        self.build_node(left + 1, middle, end, centroids);
+    }
+
+    /// Find the axis and bin boundary with the lowest surface area heuristic cost,
+    /// or `None` when keeping the triangles in a leaf is cheaper.
+    fn find_split(
+        &self,
+        start: usize,
+        end: usize,
+        centroid_aabb: &Aabb,
+        aabb: &Aabb,
+        centroids: &[Vector3<f32>]) -> Option<(usize, usize)>
+    {
+        let count = end - start;
+        if count <= 1 {
+            return None;
+        }
+
+        let mut best_cost = f32::INFINITY;
+        let mut best_split = None;
+        for axis in 0..3 {
+            let min = component(&centroid_aabb.min, axis);
+            let extent = component(&centroid_aabb.extents(), axis);
+            if extent <= 0.0 {
+                continue;
+            }
+
+            let mut bins = [Bin { aabb: Aabb::empty(), count: 0 }; BIN_COUNT];
+            for i in start..end {
+                let offset = (component(&centroids[i], axis) - min) / extent;
+                let index = usize::min((offset * BIN_COUNT as f32) as usize, BIN_COUNT - 1);
+                bins[index].aabb = bins[index].aabb.union(&self.triangles[i].aabb());
+                bins[index].count += 1;
+            }
+
+            // Sweep from the right to find the cost of everything right of each boundary.
+            let mut right_costs = [0.0; BIN_COUNT];
+            let mut right_aabb = Aabb::empty();
+            let mut right_count = 0;
+            for bin in (1..BIN_COUNT).rev() {
+                right_aabb = right_aabb.union(&bins[bin].aabb);
+                right_count += bins[bin].count;
+                right_costs[bin] = right_aabb.surface_area() * right_count as f32;
+            }
+
+            let mut left_aabb = Aabb::empty();
+            let mut left_count = 0;
+            for bin in 1..BIN_COUNT {
+                left_aabb = left_aabb.union(&bins[bin - 1].aabb);
+                left_count += bins[bin - 1].count;
+                if left_count == 0 || left_count == count {
+                    continue;
+                }
+                let cost = left_aabb.surface_area() * left_count as f32 + right_costs[bin];
+                if cost < best_cost {
+                    best_cost = cost;
+                    best_split = Some((axis, bin));
+                }
+            }
+        }
+
+        let area = aabb.surface_area();
+        let split_cost = TRAVERSAL_COST * area + best_cost;
+        let leaf_cost = area * count as f32;
+        if count <= MAX_LEAF_SIZE && leaf_cost <= split_cost {
+            None
+        } else {
+            best_split
+        }
+    }
+
+    /// Update the bounding boxes of the hierarchy after the vertex positions of the
+    /// meshes it was built from have moved, keeping the tree structure. The meshes
+    /// must have the same triangles as when the hierarchy was built. Large motions
+    /// degrade the quality of the tree, in which case rebuilding it is faster.
+    pub fn refit(&mut self, model: &Model) {
+        self.refit_meshes(&model.meshes);
+    }
+
+    /// Update the bounding boxes of the hierarchy from a list of meshes.
+    pub fn refit_meshes(&mut self, meshes: &[Mesh]) {
+        for triangle in self.triangles.iter_mut() {
+            let mesh = &meshes[triangle.mesh_index as usize];
+            let first = 3 * triangle.triangle_index as usize;
+            for k in 0..3 {
+                let index = mesh.vertex_indices[first + k] as usize;
+                triangle.positions[k] = mesh.vertices[index].position;
+            }
+        }
+
+        // Children are always stored after their parents.
+        for node_index in (0..self.nodes.len()).rev() {
+            let node = self.nodes[node_index];
+            let first = node.first as usize;
+            let aabb = if node.is_leaf() {
+                let mut aabb = Aabb::empty();
+                for triangle in self.triangles[first..(first + node.count as usize)].iter() {
+                    aabb = aabb.union(&triangle.aabb());
+                }
+
+                aabb
+            } else {
+                self.nodes[first].aabb.union(&self.nodes[first + 1].aabb)
+            };
+            self.nodes[node_index].aabb = aabb;
+        }
+    }
+
+    /// The bounding box of every triangle in the hierarchy.
+    pub fn bounds(&self) -> Aabb {
+        self.nodes.first().map_or(Aabb::empty(), |node| node.aabb)
+    }
+
+    #[inline]
+    pub fn triangle_count(&self) -> usize {
+        self.triangles.len()
+    }
+
+    #[inline]
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Find the intersection of a ray with the triangles nearest to the ray origin,
+    /// within `max_distance` along the ray.
+    pub fn closest_hit(&self, ray: &Ray, max_distance: f32) -> Option<RayHit> {
+        self.traverse(ray, max_distance, false)
+    }
+
+    /// Find any intersection of a ray with the triangles within `max_distance` along
+    /// the ray. This is faster than `closest_hit` for visibility and occlusion tests.
+    pub fn any_hit(&self, ray: &Ray, max_distance: f32) -> Option<RayHit> {
+        self.traverse(ray, max_distance, true)
+    }
+
+    fn traverse(&self, ray: &Ray, max_distance: f32, stop_at_first_hit: bool) -> Option<RayHit> {
+        if self.nodes.is_empty() {
+            return None;
+        }
+
+        let inverse_direction = Vector3::new(
+            1.0 / ray.direction.x, 1.0 / ray.direction.y, 1.0 / ray.direction.z
+        );
+        let mut closest_distance = max_distance;
+        let mut closest_hit = None;
+        // Each node on the stack is paired with the ray parameter the ray enters it at.
+        let mut stack = Vec::with_capacity(64);
+        if let Some(distance) = intersect_aabb(&self.nodes[0].aabb, ray, &inverse_direction, closest_distance) {
+            stack.push((0, distance));
+        }
+        while let Some((node_index, entry_distance)) = stack.pop() {
+            // A hit found since the node was pushed may have moved closer than the node.
+            if entry_distance > closest_distance {
+                continue;
+            }
+            let node = &self.nodes[node_index];
+            let first = node.first as usize;
+            if node.is_leaf() {
+                for triangle in self.triangles[first..(first + node.count as usize)].iter() {
+                    if let Some((distance, u, v)) = triangle.intersect(ray) {
+                        if distance >= 0.0 && distance <= closest_distance {
+                            closest_distance = distance;
+                            closest_hit = Some(RayHit {
+                                distance: distance,
+                                barycentrics: Vector3::new(1.0 - u - v, u, v),
+                                mesh_index: triangle.mesh_index as usize,
+                                triangle_index: triangle.triangle_index as usize,
+                            });
+                            if stop_at_first_hit {
+                                return closest_hit;
+                            }
+                        }
+                    }
+                }
+                continue;
+            }
+
+            // Visit the nearer child first so that hits in it can cull the farther one.
+            let left = intersect_aabb(&self.nodes[first].aabb, ray, &inverse_direction, closest_distance);
+            let right = intersect_aabb(&self.nodes[first + 1].aabb, ray, &inverse_direction, closest_distance);
+            match (left, right) {
+                (Some(left_distance), Some(right_distance)) => {
+                    if left_distance <= right_distance {
+                        stack.push((first + 1, right_distance));
+                        stack.push((first, left_distance));
+                    } else {
+                        stack.push((first, left_distance));
+                        stack.push((first + 1, right_distance));
+                    }
+                }
+                (Some(left_distance), None) => stack.push((first, left_distance)),
+                (None, Some(right_distance)) => stack.push((first + 1, right_distance)),
+                (None, None) => {}
+            }
+        }
+
+        closest_hit
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives;
+
+
+    /// A xorshift generator with a fixed seed, so the random rays are the same on
+    /// every run.
+    struct Random {
+        state: u32,
+    }
+
+    impl Random {
+        fn new() -> Random {
+            Random {
+                state: 0x2545F491,
+            }
+        }
+
+        /// A float in the range `[min, max)`.
+        fn next(&mut self, min: f32, max: f32) -> f32 {
+            self.state ^= self.state << 13;
+            self.state ^= self.state >> 17;
+            self.state ^= self.state << 5;
+
+            min + (max - min) * (self.state >> 8) as f32 / (1 << 24) as f32
+        }
+
+        fn next_vector(&mut self, min: f32, max: f32) -> Vector3<f32> {
+            Vector3::new(self.next(min, max), self.next(min, max), self.next(min, max))
+        }
+    }
+
+    /// A few overlapping meshes, so rays cross several of them.
+    fn scene() -> Vec<Mesh> {
+        let mut meshes = vec![
+            primitives::icosphere(1.0, 2),
+            primitives::cube(1.5),
+            primitives::torus(1.5, 0.4, 24, 12),
+        ];
+        for vertex in meshes[1].vertices.iter_mut() {
+            vertex.position += Vector3::new(0.8, 0.3, -0.5);
+        }
+
+        meshes
+    }
+
+    /// Rays from random points around the scene towards random points inside it.
+    fn random_rays(count: usize) -> Vec<Ray> {
+        let mut random = Random::new();
+        (0..count)
+            .map(|_| {
+                let origin = random.next_vector(-4.0, 4.0);
+                let target = random.next_vector(-1.5, 1.5);
+
+                Ray::new(origin, target - origin)
+            })
+            .collect()
+    }
+
+    /// Find the closest hit of a ray by intersecting it with every triangle.
+    fn brute_force_closest_hit(meshes: &[Mesh], ray: &Ray, max_distance: f32) -> Option<RayHit> {
+        let mut closest_hit: Option<RayHit> = None;
+        for (mesh_index, mesh) in meshes.iter().enumerate() {
+            for (triangle_index, indices) in mesh.vertex_indices.chunks_exact(3).enumerate() {
+                let triangle = BvhTriangle {
+                    mesh_index: mesh_index as u32,
+                    triangle_index: triangle_index as u32,
+                    positions: [
+                        mesh.vertices[indices[0] as usize].position,
+                        mesh.vertices[indices[1] as usize].position,
+                        mesh.vertices[indices[2] as usize].position,
+                    ],
+                };
+                if let Some((distance, u, v)) = triangle.intersect(ray) {
+                    let closer = closest_hit.map_or(true, |hit| distance < hit.distance);
+                    if distance >= 0.0 && distance <= max_distance && closer {
+                        closest_hit = Some(RayHit {
+                            distance: distance,
+                            barycentrics: Vector3::new(1.0 - u - v, u, v),
+                            mesh_index: mesh_index,
+                            triangle_index: triangle_index,
+                        });
+                    }
+                }
+            }
+        }
+
+        closest_hit
+    }
+
+    fn assert_same_hit(result: Option<RayHit>, expected: Option<RayHit>) {
+        match (result, expected) {
+            (Some(result), Some(expected)) => {
+                // Rays through a shared edge may hit either triangle at the same distance.
+                assert!(
+                    (result.distance - expected.distance).abs() <= 1e-5 * expected.distance.max(1.0),
+                    "expected a hit at {}, got {}", expected.distance, result.distance
+                );
+            }
+            (None, None) => {}
+            _ => panic!("expected {:?}, got {:?}", expected, result),
+        }
+    }
+
+    #[test]
+    fn test_closest_hit_matches_brute_force() {
+        let meshes = scene();
+        let bvh = Bvh::from_meshes(&meshes);
+        let mut hit_count = 0;
+        for ray in random_rays(500).iter() {
+            let expected = brute_force_closest_hit(&meshes, ray, f32::INFINITY);
+            hit_count += expected.is_some() as usize;
+
+            assert_same_hit(bvh.closest_hit(ray, f32::INFINITY), expected);
+        }
+
+        assert!(hit_count > 100, "only {} rays hit the scene", hit_count);
+    }
+
+    #[test]
+    fn test_closest_hit_respects_max_distance() {
+        let meshes = scene();
+        let bvh = Bvh::from_meshes(&meshes);
+        for ray in random_rays(200).iter() {
+            assert_same_hit(bvh.closest_hit(ray, 0.5), brute_force_closest_hit(&meshes, ray, 0.5));
+        }
+    }
+
+    #[test]
+    fn test_any_hit_agrees_with_closest_hit() {
+        let meshes = scene();
+        let bvh = Bvh::from_meshes(&meshes);
+        for ray in random_rays(500).iter() {
+            let closest_hit = bvh.closest_hit(ray, f32::INFINITY);
+            let any_hit = bvh.any_hit(ray, f32::INFINITY);
+
+            assert_eq!(any_hit.is_some(), closest_hit.is_some());
+            if let (Some(any_hit), Some(closest_hit)) = (any_hit, closest_hit) {
+                assert!(any_hit.distance >= closest_hit.distance);
+            }
+        }
+    }
+
+    #[test]
+    fn test_refit_matches_rebuild() {
+        let mut meshes = scene();
+        let mut bvh = Bvh::from_meshes(&meshes);
+        for mesh in meshes.iter_mut() {
+            for vertex in mesh.vertices.iter_mut() {
+                let position = vertex.position;
+                vertex.position = Vector3::new(
+                    2.0 * position.x + 0.3 * position.y + 1.0,
+                    0.5 * position.y - 2.0,
+                    position.z - 0.4 * position.x + 0.5,
+                );
+            }
+        }
+        bvh.refit_meshes(&meshes);
+        let rebuilt_bvh = Bvh::from_meshes(&meshes);
+
+        assert_eq!(bvh.bounds(), rebuilt_bvh.bounds());
+        assert_eq!(bvh.triangle_count(), rebuilt_bvh.triangle_count());
+        for ray in random_rays(500).iter() {
+            let expected = brute_force_closest_hit(&meshes, ray, f32::INFINITY);
+
+            assert_same_hit(bvh.closest_hit(ray, f32::INFINITY), expected);
+            assert_same_hit(rebuilt_bvh.closest_hit(ray, f32::INFINITY), expected);
+        }
+    }
+
+    #[test]
+    fn test_empty_hierarchy() {
+        let bvh = Bvh::from_meshes(&[]);
+        let ray = Ray::new(Vector3::zero(), Vector3::unit_z());
+
+        assert_eq!(bvh.node_count(), 0);
+        assert_eq!(bvh.closest_hit(&ray, f32::INFINITY), None);
+        assert_eq!(bvh.any_hit(&ray, f32::INFINITY), None);
+    }
+}
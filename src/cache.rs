@@ -0,0 +1,1084 @@
+use crate::backend::{
+    ColorType,
+    TextureImage2D,
+};
+use crate::model;
+use crate::model::{
+    Mesh,
+    Model,
+    ModelLod,
+    ModelLoadError,
+    ModelLoadOptions,
+    Texture,
+    TextureKind,
+    Vertex,
+};
+use crate::normals::{
+    NormalGeneration,
+};
+use crate::quantize::{
+    VertexFormat,
+};
+use crate::subdivide::{
+    SubdivisionScheme,
+};
+use crate::transform::{
+    Handedness,
+    UpAxis,
+};
+use cglinalg::{
+    Vector2,
+    Vector3,
+    Vector4,
+};
+use log::{
+    info,
+    error,
+};
+use memmap::{
+    Mmap,
+};
+use std::collections::{
+    HashMap,
+};
+use std::error;
+use std::fmt;
+use std::fs::{
+    File,
+};
+use std::io;
+use std::io::{
+    Write,
+};
+use std::path::{
+    Path,
+    PathBuf,
+};
+use std::time::{
+    Instant,
+};
+
+
+/// The magic number at the start of every model cache file.
+const CACHE_MAGIC: [u8; 8] = *b"MLDCACHE";
+
+/// The version of the binary model cache format. Bump this whenever the layout of
+/// the payload changes, or the loader builds different models from the same source,
+/// so that stale cache files get rebuilt instead of misread.
+pub const CACHE_FORMAT_VERSION: u32 = 6;
+
+/// The size of the cache file header in bytes.
+const CACHE_HEADER_SIZE: usize = 8 + 4 + 4 + 8 + 8 + 8;
+
+/// Texture data is stored as decoded pixels, ready to send to the GPU.
+const TEXTURE_ENCODING_RAW: u8 = 0;
+
+/// The encoded size of a vertex in bytes.
+const VERTEX_SIZE: usize = 20 * 4;
+
+/// The smallest encoded size of a texture in bytes, with an empty name and no pixels.
+const MIN_TEXTURE_SIZE: usize = 4 + 1 + 4 + 4 + 1 + 4 + 1 + 8;
+
+/// The smallest encoded size of a mesh in bytes, with an empty name and no elements.
+const MIN_MESH_SIZE: usize = 4 + 4 + 4 + 4 + 4 + 4;
+
+/// The smallest encoded size of a level of detail in bytes, with no meshes.
+const MIN_LOD_SIZE: usize = 4 + 4;
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+
+/// Compute the 64-bit FNV-1a hash of a byte buffer. This is used both for keying a
+/// cache file on the contents of its source archive and for checksumming the payload.
+pub fn fnv1a_64(bytes: &[u8]) -> u64 {
+    let mut hash = FNV_OFFSET_BASIS;
+    for byte in bytes.iter() {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+
+    hash
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CacheErrorKind {
+    Io,
+    BadMagic,
+    UnsupportedVersion,
+    StaleSource,
+    ChecksumMismatch,
+    Truncated,
+    InvalidData,
+}
+
+#[derive(Debug)]
+pub struct CacheError {
+    kind: CacheErrorKind,
+    error: Option<Box<dyn error::Error>>,
+}
+
+impl CacheError {
+    #[inline]
+    fn new(kind: CacheErrorKind) -> Self {
+        Self {
+            kind: kind,
+            error: None,
+        }
+    }
+
+    #[inline]
+    fn from_io(error: io::Error) -> Self {
+        Self {
+            kind: CacheErrorKind::Io,
+            error: Some(Box::new(error)),
+        }
+    }
+
+    #[inline]
+    pub fn kind(&self) -> CacheErrorKind {
+        self.kind
+    }
+}
+
+impl fmt::Display for CacheError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            CacheErrorKind::Io => {
+                match self.error {
+                    Some(ref e) => write!(f, "Could not access the model cache file: {}", e),
+                    None => write!(f, "Could not access the model cache file."),
+                }
+            }
+            CacheErrorKind::BadMagic => {
+                write!(f, "The file is not a model cache file.")
+            }
+            CacheErrorKind::UnsupportedVersion => {
+                write!(f,
+                    "The model cache file was written with an unsupported format version. Expected version {}.",
+                    CACHE_FORMAT_VERSION
+                )
+            }
+            CacheErrorKind::StaleSource => {
+                write!(f, "The model cache file was built from a different source archive.")
+            }
+            CacheErrorKind::ChecksumMismatch => {
+                write!(f, "The model cache file payload does not match its checksum.")
+            }
+            CacheErrorKind::Truncated => {
+                write!(f, "The model cache file ended unexpectedly.")
+            }
+            CacheErrorKind::InvalidData => {
+                write!(f, "The model cache file contains invalid data.")
+            }
+        }
+    }
+}
+
+impl error::Error for CacheError {}
+
+
+/// The fixed size header at the start of every cache file.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct CacheHeader {
+    pub version: u32,
+    pub flags: u32,
+    pub source_hash: u64,
+    pub payload_length: u64,
+    pub payload_checksum: u64,
+}
+
+impl CacheHeader {
+    fn write(&self, buffer: &mut Vec<u8>) {
+        buffer.extend_from_slice(&CACHE_MAGIC);
+        write_u32(buffer, self.version);
+        write_u32(buffer, self.flags);
+        write_u64(buffer, self.source_hash);
+        write_u64(buffer, self.payload_length);
+        write_u64(buffer, self.payload_checksum);
+    }
+
+    fn read(bytes: &[u8]) -> Result<CacheHeader, CacheError> {
+        if bytes.len() < CACHE_HEADER_SIZE {
+            return Err(CacheError::new(CacheErrorKind::Truncated));
+        }
+        if bytes[0..8] != CACHE_MAGIC {
+            return Err(CacheError::new(CacheErrorKind::BadMagic));
+        }
+
+        let mut reader = ByteReader::new(&bytes[8..CACHE_HEADER_SIZE]);
+        let version = reader.read_u32()?;
+        let flags = reader.read_u32()?;
+        let source_hash = reader.read_u64()?;
+        let payload_length = reader.read_u64()?;
+        let payload_checksum = reader.read_u64()?;
+
+        Ok(CacheHeader {
+            version: version,
+            flags: flags,
+            source_hash: source_hash,
+            payload_length: payload_length,
+            payload_checksum: payload_checksum,
+        })
+    }
+}
+
+
+#[inline]
+fn write_u8(buffer: &mut Vec<u8>, value: u8) {
+    buffer.push(value);
+}
+
+#[inline]
+fn write_u32(buffer: &mut Vec<u8>, value: u32) {
+    buffer.extend_from_slice(&value.to_le_bytes());
+}
+
+#[inline]
+fn write_u64(buffer: &mut Vec<u8>, value: u64) {
+    buffer.extend_from_slice(&value.to_le_bytes());
+}
+
+#[inline]
+fn write_f32(buffer: &mut Vec<u8>, value: f32) {
+    buffer.extend_from_slice(&value.to_le_bytes());
+}
+
+#[inline]
+fn write_string(buffer: &mut Vec<u8>, value: &str) {
+    write_u32(buffer, value.len() as u32);
+    buffer.extend_from_slice(value.as_bytes());
+}
+
+#[inline]
+fn write_vector2(buffer: &mut Vec<u8>, value: &Vector2<f32>) {
+    write_f32(buffer, value.x);
+    write_f32(buffer, value.y);
+}
+
+#[inline]
+fn write_vector3(buffer: &mut Vec<u8>, value: &Vector3<f32>) {
+    write_f32(buffer, value.x);
+    write_f32(buffer, value.y);
+    write_f32(buffer, value.z);
+}
+
+#[inline]
+fn write_vector4(buffer: &mut Vec<u8>, value: &Vector4<f32>) {
+    write_f32(buffer, value.x);
+    write_f32(buffer, value.y);
+    write_f32(buffer, value.z);
+    write_f32(buffer, value.w);
+}
+
+/// A cursor over a borrowed byte buffer. Reading past the end of the
+/// buffer is reported as a truncated cache file instead of panicking.
+struct ByteReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    fn new(bytes: &'a [u8]) -> ByteReader<'a> {
+        ByteReader {
+            bytes: bytes,
+            position: 0,
+        }
+    }
+
+    fn read_bytes(&mut self, length: usize) -> Result<&'a [u8], CacheError> {
+        let end = self.position.checked_add(length)
+            .ok_or(CacheError::new(CacheErrorKind::Truncated))?;
+        if end > self.bytes.len() {
+            return Err(CacheError::new(CacheErrorKind::Truncated));
+        }
+        let slice = &self.bytes[self.position..end];
+        self.position = end;
+
+        Ok(slice)
+    }
+
+    fn read_u8(&mut self) -> Result<u8, CacheError> {
+        let bytes = self.read_bytes(1)?;
+
+        Ok(bytes[0])
+    }
+
+    fn read_u32(&mut self) -> Result<u32, CacheError> {
+        let bytes = self.read_bytes(4)?;
+
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn read_u64(&mut self) -> Result<u64, CacheError> {
+        let bytes = self.read_bytes(8)?;
+        let mut array = [0; 8];
+        array.copy_from_slice(bytes);
+
+        Ok(u64::from_le_bytes(array))
+    }
+
+    /// Read an element count, rejecting counts of elements of at least
+    /// `element_size` bytes that cannot fit in the rest of the buffer, so a
+    /// corrupt count never drives a huge allocation.
+    fn read_count(&mut self, element_size: usize) -> Result<usize, CacheError> {
+        let count = self.read_u32()? as usize;
+        let remaining = self.bytes.len() - self.position;
+        if count > remaining / element_size {
+            return Err(CacheError::new(CacheErrorKind::Truncated));
+        }
+
+        Ok(count)
+    }
+
+    fn read_f32(&mut self) -> Result<f32, CacheError> {
+        let bytes = self.read_bytes(4)?;
+
+        Ok(f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn read_string(&mut self) -> Result<String, CacheError> {
+        let length = self.read_u32()? as usize;
+        let bytes = self.read_bytes(length)?;
+
+        String::from_utf8(bytes.to_vec()).map_err(|e| {
+            CacheError {
+                kind: CacheErrorKind::InvalidData,
+                error: Some(Box::new(e)),
+            }
+        })
+    }
+
+    fn read_vector2(&mut self) -> Result<Vector2<f32>, CacheError> {
+        let x = self.read_f32()?;
+        let y = self.read_f32()?;
+
+        Ok(Vector2::new(x, y))
+    }
+
+    fn read_vector3(&mut self) -> Result<Vector3<f32>, CacheError> {
+        let x = self.read_f32()?;
+        let y = self.read_f32()?;
+        let z = self.read_f32()?;
+
+        Ok(Vector3::new(x, y, z))
+    }
+
+    fn read_vector4(&mut self) -> Result<Vector4<f32>, CacheError> {
+        let x = self.read_f32()?;
+        let y = self.read_f32()?;
+        let z = self.read_f32()?;
+        let w = self.read_f32()?;
+
+        Ok(Vector4::new(x, y, z, w))
+    }
+}
+
+fn texture_kind_to_u8(kind: TextureKind) -> u8 {
+    match kind {
+        TextureKind::Ambient => 0,
+        TextureKind::Diffuse => 1,
+        TextureKind::Specular => 2,
+        TextureKind::Bump => 3,
+        TextureKind::Emission => 4,
+    }
+}
+
+fn texture_kind_from_u8(value: u8) -> Result<TextureKind, CacheError> {
+    match value {
+        0 => Ok(TextureKind::Ambient),
+        1 => Ok(TextureKind::Diffuse),
+        2 => Ok(TextureKind::Specular),
+        3 => Ok(TextureKind::Bump),
+        4 => Ok(TextureKind::Emission),
+        _ => Err(CacheError::new(CacheErrorKind::InvalidData)),
+    }
+}
+
+fn color_type_to_u8(color_type: ColorType) -> u8 {
+    match color_type {
+        ColorType::L8 => 0,
+        ColorType::Rgb8 => 1,
+        ColorType::Rgba8 => 2,
+    }
+}
+
+fn color_type_from_u8(value: u8) -> Result<ColorType, CacheError> {
+    match value {
+        0 => Ok(ColorType::L8),
+        1 => Ok(ColorType::Rgb8),
+        2 => Ok(ColorType::Rgba8),
+        _ => Err(CacheError::new(CacheErrorKind::InvalidData)),
+    }
+}
+
+fn vertex_format_to_u8(format: VertexFormat) -> u8 {
+    match format {
+        VertexFormat::Float32 => 0,
+        VertexFormat::PackedHalf => 1,
+        VertexFormat::PackedSnorm16 => 2,
+    }
+}
+
+fn vertex_format_from_u8(value: u8) -> Result<VertexFormat, CacheError> {
+    match value {
+        0 => Ok(VertexFormat::Float32),
+        1 => Ok(VertexFormat::PackedHalf),
+        2 => Ok(VertexFormat::PackedSnorm16),
+        _ => Err(CacheError::new(CacheErrorKind::InvalidData)),
+    }
+}
+
+fn write_texture(buffer: &mut Vec<u8>, texture: &Texture) {
+    write_string(buffer, &texture.name);
+    write_u8(buffer, texture_kind_to_u8(texture.kind));
+    write_u32(buffer, texture.data.width);
+    write_u32(buffer, texture.data.height);
+    write_u8(buffer, color_type_to_u8(texture.data.color_type));
+    write_u32(buffer, texture.data.bytes_per_pixel);
+    write_u8(buffer, TEXTURE_ENCODING_RAW);
+    let data = texture.data.as_slice();
+    write_u64(buffer, data.len() as u64);
+    buffer.extend_from_slice(data);
+}
+
+fn read_texture(reader: &mut ByteReader) -> Result<Texture, CacheError> {
+    let name = reader.read_string()?;
+    let kind = texture_kind_from_u8(reader.read_u8()?)?;
+    let width = reader.read_u32()?;
+    let height = reader.read_u32()?;
+    let color_type = color_type_from_u8(reader.read_u8()?)?;
+    let bytes_per_pixel = reader.read_u32()?;
+    let encoding = reader.read_u8()?;
+    if encoding != TEXTURE_ENCODING_RAW {
+        return Err(CacheError::new(CacheErrorKind::InvalidData));
+    }
+    let length = reader.read_u64()? as usize;
+    if length != (width as usize) * (height as usize) * (bytes_per_pixel as usize) {
+        return Err(CacheError::new(CacheErrorKind::InvalidData));
+    }
+    let data = reader.read_bytes(length)?.to_vec();
+    let image = TextureImage2D::new(width, height, color_type, bytes_per_pixel, data);
+
+    Ok(Texture::new(name, kind, image))
+}
+
+fn write_vertex(buffer: &mut Vec<u8>, vertex: &Vertex) {
+    write_vector3(buffer, &vertex.position);
+    write_vector3(buffer, &vertex.normal);
+    write_vector2(buffer, &vertex.tex_coords);
+    write_vector3(buffer, &vertex.tangent);
+    write_vector3(buffer, &vertex.bitangent);
+    write_vector4(buffer, &vertex.color);
+    write_vector2(buffer, &vertex.tex_coords2);
+}
+
+fn read_vertex(reader: &mut ByteReader) -> Result<Vertex, CacheError> {
+    let position = reader.read_vector3()?;
+    let normal = reader.read_vector3()?;
+    let tex_coords = reader.read_vector2()?;
+    let tangent = reader.read_vector3()?;
+    let bitangent = reader.read_vector3()?;
+    let color = reader.read_vector4()?;
+    let tex_coords2 = reader.read_vector2()?;
+
+    Ok(Vertex {
+        position: position,
+        normal: normal,
+        tex_coords: tex_coords,
+        tangent: tangent,
+        bitangent: bitangent,
+        color: color,
+        tex_coords2: tex_coords2,
+    })
+}
+
+fn write_mesh(buffer: &mut Vec<u8>, mesh: &Mesh) {
+    write_string(buffer, &mesh.name);
+    write_u32(buffer, mesh.vertices.len() as u32);
+    for vertex in mesh.vertices.iter() {
+        write_vertex(buffer, vertex);
+    }
+    write_u32(buffer, mesh.vertex_indices.len() as u32);
+    for index in mesh.vertex_indices.iter() {
+        write_u32(buffer, *index);
+    }
+    write_u32(buffer, mesh.line_vertices.len() as u32);
+    for vertex in mesh.line_vertices.iter() {
+        write_vertex(buffer, vertex);
+    }
+    write_u32(buffer, mesh.point_vertices.len() as u32);
+    for vertex in mesh.point_vertices.iter() {
+        write_vertex(buffer, vertex);
+    }
+    // Sort the texture entries so identical models produce identical cache files.
+    let mut texture_indices: Vec<(&TextureKind, &u32)> = mesh.texture_indices.iter().collect();
+    texture_indices.sort_by_key(|(kind, _)| texture_kind_to_u8(**kind));
+    write_u32(buffer, texture_indices.len() as u32);
+    for (kind, index) in texture_indices.iter() {
+        write_u8(buffer, texture_kind_to_u8(**kind));
+        write_u32(buffer, **index);
+    }
+}
+
+fn read_mesh(reader: &mut ByteReader, texture_count: usize) -> Result<Mesh, CacheError> {
+    let name = reader.read_string()?;
+    let vertex_count = reader.read_count(VERTEX_SIZE)?;
+    let mut vertices = Vec::with_capacity(vertex_count);
+    for _ in 0..vertex_count {
+        vertices.push(read_vertex(reader)?);
+    }
+    let index_count = reader.read_count(4)?;
+    let mut vertex_indices = Vec::with_capacity(index_count);
+    for _ in 0..index_count {
+        let index = reader.read_u32()?;
+        if index as usize >= vertex_count {
+            return Err(CacheError::new(CacheErrorKind::InvalidData));
+        }
+        vertex_indices.push(index);
+    }
+    let line_vertex_count = reader.read_count(VERTEX_SIZE)?;
+    if line_vertex_count % 2 != 0 {
+        return Err(CacheError::new(CacheErrorKind::InvalidData));
+    }
+    let mut line_vertices = Vec::with_capacity(line_vertex_count);
+    for _ in 0..line_vertex_count {
+        line_vertices.push(read_vertex(reader)?);
+    }
+    let point_vertex_count = reader.read_count(VERTEX_SIZE)?;
+    let mut point_vertices = Vec::with_capacity(point_vertex_count);
+    for _ in 0..point_vertex_count {
+        point_vertices.push(read_vertex(reader)?);
+    }
+    let texture_index_count = reader.read_count(1 + 4)?;
+    let mut texture_indices = HashMap::new();
+    for _ in 0..texture_index_count {
+        let kind = texture_kind_from_u8(reader.read_u8()?)?;
+        let index = reader.read_u32()?;
+        if index as usize >= texture_count {
+            return Err(CacheError::new(CacheErrorKind::InvalidData));
+        }
+        texture_indices.insert(kind, index);
+    }
+
+    Ok(Mesh::with_primitives(name, vertices, vertex_indices, line_vertices, point_vertices, texture_indices))
+}
+
+/// Serialize a fully processed model into the payload section of a cache file.
+fn write_model_payload(model: &Model) -> Vec<u8> {
+    let mut buffer = vec![];
+    write_string(&mut buffer, &model.name);
+    write_u8(&mut buffer, model.gamma_correction as u8);
+    write_u8(&mut buffer, vertex_format_to_u8(model.vertex_format));
+    write_u32(&mut buffer, model.textures_loaded.len() as u32);
+    for texture in model.textures_loaded.iter() {
+        write_texture(&mut buffer, texture);
+    }
+    write_u32(&mut buffer, model.meshes.len() as u32);
+    for mesh in model.meshes.iter() {
+        write_mesh(&mut buffer, mesh);
+    }
+    write_u32(&mut buffer, model.lods.len() as u32);
+    for lod in model.lods.iter() {
+        write_f32(&mut buffer, lod.error);
+        write_u32(&mut buffer, lod.meshes.len() as u32);
+        for mesh in lod.meshes.iter() {
+            write_mesh(&mut buffer, mesh);
+        }
+    }
+
+    buffer
+}
+
+fn read_model_payload(payload: &[u8]) -> Result<Model, CacheError> {
+    let mut reader = ByteReader::new(payload);
+    let name = reader.read_string()?;
+    let gamma_correction = reader.read_u8()? != 0;
+    let vertex_format = vertex_format_from_u8(reader.read_u8()?)?;
+    let texture_count = reader.read_count(MIN_TEXTURE_SIZE)?;
+    let mut textures_loaded = Vec::with_capacity(texture_count);
+    for _ in 0..texture_count {
+        textures_loaded.push(read_texture(&mut reader)?);
+    }
+    let mesh_count = reader.read_count(MIN_MESH_SIZE)?;
+    let mut meshes = Vec::with_capacity(mesh_count);
+    for _ in 0..mesh_count {
+        meshes.push(read_mesh(&mut reader, texture_count)?);
+    }
+    let lod_count = reader.read_count(MIN_LOD_SIZE)?;
+    let mut lods = Vec::with_capacity(lod_count);
+    for _ in 0..lod_count {
+        let error = reader.read_f32()?;
+        let lod_mesh_count = reader.read_count(MIN_MESH_SIZE)?;
+        let mut lod_meshes = Vec::with_capacity(lod_mesh_count);
+        for _ in 0..lod_mesh_count {
+            lod_meshes.push(read_mesh(&mut reader, texture_count)?);
+        }
+        lods.push(ModelLod::new(lod_meshes, error));
+    }
+
+    let mut model = Model::new(name, meshes, textures_loaded, gamma_correction);
+    model.lods = lods;
+    model.vertex_format = vertex_format;
+
+    Ok(model)
+}
+
+/// Serialize a model into a complete cache file image, keyed on the hash of the
+/// source archive the model was loaded from.
+pub fn to_bytes(model: &Model, source_hash: u64) -> Vec<u8> {
+    let payload = write_model_payload(model);
+    let header = CacheHeader {
+        version: CACHE_FORMAT_VERSION,
+        flags: 0,
+        source_hash: source_hash,
+        payload_length: payload.len() as u64,
+        payload_checksum: fnv1a_64(&payload),
+    };
+    let mut buffer = Vec::with_capacity(CACHE_HEADER_SIZE + payload.len());
+    header.write(&mut buffer);
+    buffer.extend_from_slice(&payload);
+
+    buffer
+}
+
+/// Deserialize a model from a cache file image. If `source_hash` is provided, the cache
+/// is rejected as stale unless it was built from a source archive with the same hash.
+pub fn from_bytes(bytes: &[u8], source_hash: Option<u64>) -> Result<Model, CacheError> {
+    let header = CacheHeader::read(bytes)?;
+    if header.version != CACHE_FORMAT_VERSION {
+        return Err(CacheError::new(CacheErrorKind::UnsupportedVersion));
+    }
+    if let Some(expected_hash) = source_hash {
+        if header.source_hash != expected_hash {
+            return Err(CacheError::new(CacheErrorKind::StaleSource));
+        }
+    }
+    let payload_end = CACHE_HEADER_SIZE.checked_add(header.payload_length as usize)
+        .ok_or(CacheError::new(CacheErrorKind::Truncated))?;
+    if payload_end > bytes.len() {
+        return Err(CacheError::new(CacheErrorKind::Truncated));
+    }
+    let payload = &bytes[CACHE_HEADER_SIZE..payload_end];
+    if fnv1a_64(payload) != header.payload_checksum {
+        return Err(CacheError::new(CacheErrorKind::ChecksumMismatch));
+    }
+
+    read_model_payload(payload)
+}
+
+/// Write a model cache file to disk.
+pub fn save_to_file<P: AsRef<Path>>(path: P, model: &Model, source_hash: u64) -> Result<(), CacheError> {
+    let bytes = to_bytes(model, source_hash);
+    let mut file = File::create(path).map_err(CacheError::from_io)?;
+    file.write_all(&bytes).map_err(CacheError::from_io)?;
+
+    Ok(())
+}
+
+/// Load a model from a memory mapped cache file on disk.
+pub fn load_from_file<P: AsRef<Path>>(path: P, source_hash: Option<u64>) -> Result<Model, CacheError> {
+    let file = File::open(path).map_err(CacheError::from_io)?;
+    // SAFETY: The mapping is read only and only lives for the duration of this function.
+    // Every value read out of it is copied into the returned model.
+    let mapping = unsafe {
+        Mmap::map(&file).map_err(CacheError::from_io)?
+    };
+
+    from_bytes(&mapping, source_hash)
+}
+
+/// The cache file of a model asset, which sits next to the asset with the
+/// extension `.cache`.
+pub fn cache_path<P: AsRef<Path>>(asset_path: P) -> PathBuf {
+    asset_path.as_ref().with_extension("cache")
+}
+
+fn write_option_tag<T>(buffer: &mut Vec<u8>, value: &Option<T>) {
+    write_u8(buffer, if value.is_some() { 1 } else { 0 });
+}
+
+fn write_bool(buffer: &mut Vec<u8>, value: bool) {
+    write_u8(buffer, value as u8);
+}
+
+fn normal_generation_to_u8(normals: NormalGeneration) -> u8 {
+    match normals {
+        NormalGeneration::Never => 0,
+        NormalGeneration::Missing => 1,
+        NormalGeneration::Always => 2,
+    }
+}
+
+fn subdivision_scheme_to_u8(scheme: SubdivisionScheme) -> u8 {
+    match scheme {
+        SubdivisionScheme::Loop => 0,
+        SubdivisionScheme::CatmullClark => 1,
+    }
+}
+
+fn up_axis_to_u8(up_axis: UpAxis) -> u8 {
+    match up_axis {
+        UpAxis::Y => 0,
+        UpAxis::Z => 1,
+    }
+}
+
+fn handedness_to_u8(handedness: Handedness) -> u8 {
+    match handedness {
+        Handedness::Right => 0,
+        Handedness::Left => 1,
+    }
+}
+
+/// Encode every field of the load options in declaration order. Optional fields are
+/// a presence tag followed by their value when present, and floats are encoded by
+/// their bit patterns, so the encoding only changes when the options do.
+fn write_load_options(buffer: &mut Vec<u8>, options: &ModelLoadOptions) {
+    write_bool(buffer, options.gamma_correction);
+    write_option_tag(buffer, &options.weld_tolerance);
+    if let Some(weld_tolerance) = options.weld_tolerance {
+        write_f32(buffer, weld_tolerance);
+    }
+    write_u8(buffer, normal_generation_to_u8(options.normals));
+    write_bool(buffer, options.sanitize);
+    write_option_tag(buffer, &options.subdivision);
+    if let Some(subdivision) = options.subdivision {
+        write_u8(buffer, subdivision_scheme_to_u8(subdivision.scheme));
+        write_u64(buffer, subdivision.levels as u64);
+    }
+
+    let transform = &options.transform;
+    write_u8(buffer, up_axis_to_u8(transform.up_axis));
+    write_u8(buffer, handedness_to_u8(transform.handedness));
+    write_option_tag(buffer, &transform.matrix);
+    if let Some(matrix) = transform.matrix {
+        for column in 0..4 {
+            write_vector4(buffer, &matrix[column]);
+        }
+    }
+    write_bool(buffer, transform.recenter);
+    write_bool(buffer, transform.normalize_scale);
+    write_bool(buffer, transform.flip_winding);
+    write_bool(buffer, transform.flip_v);
+
+    write_option_tag(buffer, &options.lod_chain);
+    if let Some(lod_chain) = options.lod_chain {
+        write_u64(buffer, lod_chain.levels as u64);
+        write_f32(buffer, lod_chain.reduction);
+        write_f32(buffer, lod_chain.max_relative_error);
+    }
+    write_option_tag(buffer, &options.optimize);
+    if let Some(optimize) = options.optimize {
+        write_u64(buffer, optimize.cache_size as u64);
+        write_bool(buffer, optimize.optimize_overdraw);
+        write_bool(buffer, optimize.optimize_vertex_fetch);
+    }
+    write_u8(buffer, vertex_format_to_u8(options.vertex_format));
+}
+
+/// Compute the key a cache file is validated against. The cache stores a fully processed
+/// model, so the key covers both the source archive and the options used to process it.
+pub fn source_hash(buffer: &[u8], options: &ModelLoadOptions) -> u64 {
+    let mut options_bytes = vec![];
+    write_load_options(&mut options_bytes, options);
+    let options_hash = fnv1a_64(&options_bytes);
+
+    fnv1a_64(buffer) ^ options_hash.rotate_left(1)
+}
+
+/// Load a model from its cache file if the cache is valid for the source archive `buffer`.
+/// Otherwise load the model from the archive and rebuild the cache file. Timings for
+/// both paths are written to the log so the speedup can be measured.
+pub fn load_or_build<P: AsRef<Path>>(
+    cache_path: P,
+    buffer: &[u8],
+    model_name: &str,
+    options: &ModelLoadOptions) -> Result<Model, ModelLoadError>
+{
+    let source_hash = source_hash(buffer, options);
+    let cache_path = cache_path.as_ref();
+
+    let start = Instant::now();
+    match load_from_file(cache_path, Some(source_hash)) {
+        Ok(model) => {
+            info!(
+                "Loaded model \"{}\" from cache file {} in {:?}",
+                model_name, cache_path.display(), start.elapsed()
+            );
+            return Ok(model);
+        }
+        Err(e) => {
+            info!("Rebuilding model cache file {}: {}", cache_path.display(), e);
+        }
+    }
+
+    let start = Instant::now();
+    let model = model::load_from_memory_with_options(buffer, model_name, options)?;
+    info!("Loaded model \"{}\" from source archive in {:?}", model_name, start.elapsed());
+
+    if let Err(e) = save_to_file(cache_path, &model, source_hash) {
+        error!("Could not write model cache file {}: {}", cache_path.display(), e);
+    }
+
+    Ok(model)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optimize::{
+        OptimizeOptions,
+    };
+    use crate::primitives;
+    use crate::simplify::{
+        LodChainOptions,
+    };
+    use crate::subdivide::{
+        SubdivisionOptions,
+    };
+    use crate::transform::{
+        MeshTransformOptions,
+    };
+    use cglinalg::{
+        Matrix4,
+    };
+    use std::env;
+    use std::fs;
+    use std::time::{
+        Duration,
+    };
+
+
+    fn test_texture() -> Texture {
+        let data = vec![
+            255, 0, 0, 255,     0, 255, 0, 255,
+            0, 0, 255, 255,     255, 255, 255, 128,
+        ];
+        let image = TextureImage2D::new(2, 2, ColorType::Rgba8, 4, data);
+
+        Texture::new(String::from("diffuse.png"), TextureKind::Diffuse, image)
+    }
+
+    /// A small model with a texture, two meshes, and a level of detail.
+    fn test_model() -> Model {
+        let mut sphere = primitives::uv_sphere(1.0, 16, 8);
+        sphere.texture_indices.insert(TextureKind::Diffuse, 0);
+        let mut cube = primitives::cube(0.5);
+        cube.line_vertices = cube.vertices[0..2].to_vec();
+        cube.point_vertices = cube.vertices[2..3].to_vec();
+        let lod = ModelLod::new(vec![primitives::uv_sphere(1.0, 8, 4), cube.clone()], 0.25);
+        let mut model = Model::new(String::from("test_model"), vec![sphere, cube], vec![test_texture()], true);
+        model.lods = vec![lod];
+        model.vertex_format = VertexFormat::PackedSnorm16;
+
+        model
+    }
+
+    fn temp_cache_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("model_loading_demo_{}_{}.cache", name, std::process::id()))
+    }
+
+    fn assert_meshes_eq(result: &[Mesh], expected: &[Mesh]) {
+        assert_eq!(result.len(), expected.len());
+        for (result_mesh, expected_mesh) in result.iter().zip(expected.iter()) {
+            assert_eq!(result_mesh.name, expected_mesh.name);
+            assert_eq!(result_mesh.vertices, expected_mesh.vertices);
+            assert_eq!(result_mesh.vertex_indices, expected_mesh.vertex_indices);
+            assert_eq!(result_mesh.line_vertices, expected_mesh.line_vertices);
+            assert_eq!(result_mesh.point_vertices, expected_mesh.point_vertices);
+            assert_eq!(result_mesh.texture_indices, expected_mesh.texture_indices);
+        }
+    }
+
+    fn assert_models_eq(result: &Model, expected: &Model) {
+        assert_eq!(result.name, expected.name);
+        assert_eq!(result.gamma_correction, expected.gamma_correction);
+        assert_eq!(result.vertex_format, expected.vertex_format);
+        assert_eq!(result.textures_loaded.len(), expected.textures_loaded.len());
+        for (result_texture, expected_texture) in result.textures_loaded.iter().zip(expected.textures_loaded.iter()) {
+            assert_eq!(result_texture.name, expected_texture.name);
+            assert_eq!(result_texture.kind, expected_texture.kind);
+            assert_eq!(result_texture.data.width, expected_texture.data.width);
+            assert_eq!(result_texture.data.height, expected_texture.data.height);
+            assert_eq!(result_texture.data.color_type, expected_texture.data.color_type);
+            assert_eq!(result_texture.data.as_slice(), expected_texture.data.as_slice());
+        }
+        assert_meshes_eq(&result.meshes, &expected.meshes);
+        assert_eq!(result.lods.len(), expected.lods.len());
+        for (result_lod, expected_lod) in result.lods.iter().zip(expected.lods.iter()) {
+            assert_eq!(result_lod.error, expected_lod.error);
+            assert_meshes_eq(&result_lod.meshes, &expected_lod.meshes);
+        }
+    }
+
+    #[test]
+    fn test_round_trip_bytes() {
+        let model = test_model();
+        let bytes = to_bytes(&model, 42);
+        let result = from_bytes(&bytes, Some(42)).unwrap();
+
+        assert_models_eq(&result, &model);
+    }
+
+    #[test]
+    fn test_round_trip_file_and_load_time() {
+        let model = test_model();
+        let path = temp_cache_path("round_trip");
+        save_to_file(&path, &model, 42).unwrap();
+
+        let start = Instant::now();
+        let result = load_from_file(&path, Some(42));
+        let load_time = start.elapsed();
+        fs::remove_file(&path).unwrap();
+
+        assert_models_eq(&result.unwrap(), &model);
+        assert!(load_time < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_identical_models_produce_identical_bytes() {
+        assert_eq!(to_bytes(&test_model(), 42), to_bytes(&test_model(), 42));
+    }
+
+    #[test]
+    fn test_reject_bad_checksum() {
+        let mut bytes = to_bytes(&test_model(), 42);
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xFF;
+        let result = from_bytes(&bytes, Some(42));
+
+        assert_eq!(result.err().map(|e| e.kind()), Some(CacheErrorKind::ChecksumMismatch));
+    }
+
+    #[test]
+    fn test_reject_unsupported_version() {
+        let mut bytes = to_bytes(&test_model(), 42);
+        bytes[8..12].copy_from_slice(&(CACHE_FORMAT_VERSION + 1).to_le_bytes());
+        let result = from_bytes(&bytes, Some(42));
+
+        assert_eq!(result.err().map(|e| e.kind()), Some(CacheErrorKind::UnsupportedVersion));
+    }
+
+    #[test]
+    fn test_reject_bad_magic() {
+        let mut bytes = to_bytes(&test_model(), 42);
+        bytes[0] = b'X';
+        let result = from_bytes(&bytes, Some(42));
+
+        assert_eq!(result.err().map(|e| e.kind()), Some(CacheErrorKind::BadMagic));
+    }
+
+    #[test]
+    fn test_reject_truncated_file() {
+        let bytes = to_bytes(&test_model(), 42);
+        let result = from_bytes(&bytes[0..bytes.len() / 2], Some(42));
+
+        assert_eq!(result.err().map(|e| e.kind()), Some(CacheErrorKind::Truncated));
+    }
+
+    #[test]
+    fn test_reject_counts_larger_than_the_payload() {
+        let mut payload = vec![];
+        write_string(&mut payload, "test_model");
+        write_u8(&mut payload, 1);
+        write_u8(&mut payload, vertex_format_to_u8(VertexFormat::Float32));
+        write_u32(&mut payload, u32::MAX);
+        let result = read_model_payload(&payload);
+
+        assert_eq!(result.err().map(|e| e.kind()), Some(CacheErrorKind::Truncated));
+    }
+
+    #[test]
+    fn test_reject_vertex_count_larger_than_the_payload() {
+        let mut payload = vec![];
+        write_mesh(&mut payload, &primitives::cube(0.5));
+        let name_size = 4 + primitives::cube(0.5).name.len();
+        payload[name_size..(name_size + 4)].copy_from_slice(&u32::MAX.to_le_bytes());
+        let result = read_mesh(&mut ByteReader::new(&payload), 0);
+
+        assert_eq!(result.err().map(|e| e.kind()), Some(CacheErrorKind::Truncated));
+    }
+
+    #[test]
+    fn test_changed_source_hash_invalidates_cache() {
+        let model = test_model();
+        let path = temp_cache_path("stale_source");
+        save_to_file(&path, &model, 42).unwrap();
+        let stale = load_from_file(&path, Some(43));
+        let unchecked = load_from_file(&path, None);
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(stale.err().map(|e| e.kind()), Some(CacheErrorKind::StaleSource));
+        assert!(unchecked.is_ok());
+    }
+
+    #[test]
+    fn test_cache_path_is_next_to_asset() {
+        let path = cache_path(Path::new("assets/backpack.zip"));
+
+        assert_eq!(path, Path::new("assets/backpack.cache"));
+    }
+
+    #[test]
+    fn test_load_options_encoding() {
+        let mut bytes = vec![];
+        write_load_options(&mut bytes, &ModelLoadOptions::new(false));
+
+        assert_eq!(bytes, vec![0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_source_hash_covers_every_option() {
+        let source = b"source archive";
+        let base = ModelLoadOptions::new(false);
+        let mut variants = vec![];
+        variants.push(ModelLoadOptions { gamma_correction: true, ..base });
+        variants.push(ModelLoadOptions { weld_tolerance: Some(0.0), ..base });
+        variants.push(ModelLoadOptions { weld_tolerance: Some(1e-4), ..base });
+        variants.push(ModelLoadOptions { normals: NormalGeneration::Always, ..base });
+        variants.push(ModelLoadOptions { sanitize: true, ..base });
+        variants.push(ModelLoadOptions {
+            subdivision: Some(SubdivisionOptions::new(SubdivisionScheme::Loop, 1)),
+            ..base
+        });
+        variants.push(ModelLoadOptions {
+            subdivision: Some(SubdivisionOptions::new(SubdivisionScheme::CatmullClark, 1)),
+            ..base
+        });
+        let transforms = [
+            MeshTransformOptions { up_axis: UpAxis::Z, ..base.transform },
+            MeshTransformOptions { handedness: Handedness::Left, ..base.transform },
+            MeshTransformOptions { matrix: Some(Matrix4::identity()), ..base.transform },
+            MeshTransformOptions { recenter: true, ..base.transform },
+            MeshTransformOptions { normalize_scale: true, ..base.transform },
+            MeshTransformOptions { flip_winding: true, ..base.transform },
+            MeshTransformOptions { flip_v: true, ..base.transform },
+        ];
+        for &transform in transforms.iter() {
+            variants.push(ModelLoadOptions { transform: transform, ..base });
+        }
+        variants.push(ModelLoadOptions { lod_chain: Some(LodChainOptions::new()), ..base });
+        variants.push(ModelLoadOptions {
+            lod_chain: Some(LodChainOptions { reduction: 0.25, ..LodChainOptions::new() }),
+            ..base
+        });
+        variants.push(ModelLoadOptions { optimize: Some(OptimizeOptions::new()), ..base });
+        variants.push(ModelLoadOptions {
+            optimize: Some(OptimizeOptions { optimize_overdraw: false, ..OptimizeOptions::new() }),
+            ..base
+        });
+        variants.push(ModelLoadOptions { vertex_format: VertexFormat::PackedHalf, ..base });
+
+        let mut hashes = vec![source_hash(source, &base)];
+        for options in variants.iter() {
+            let hash = source_hash(source, options);
+            assert!(!hashes.contains(&hash), "{:?} hashes like another set of options", options);
+            hashes.push(hash);
+        }
+
+        assert_eq!(source_hash(source, &base), source_hash(source, &ModelLoadOptions::new(false)));
+        assert_ne!(source_hash(source, &base), source_hash(b"other archive", &base));
+    }
+}
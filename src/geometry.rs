@@ -0,0 +1,36 @@
+use cglinalg::{
+    Vector3,
+};
+
+
+/// A key identifying a vector exactly, for hashing positions and normals. Vectors
+/// only share a key when they are bitwise equal, so nearly coincident positions
+/// need welding before they share one.
+pub type VectorKey = [u32; 3];
+
+#[inline]
+pub fn vector_key(vector: &Vector3<f32>) -> VectorKey {
+    // Adding zero maps negative zero to positive zero so both hash the same.
+    [
+        (vector.x + 0.0).to_bits(),
+        (vector.y + 0.0).to_bits(),
+        (vector.z + 0.0).to_bits(),
+    ]
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+
+    #[test]
+    fn test_vector_key_negative_zero() {
+        assert_eq!(vector_key(&Vector3::new(-0.0, 0.0, -0.0)), vector_key(&Vector3::new(0.0, -0.0, 0.0)));
+    }
+
+    #[test]
+    fn test_vector_key_distinct_vectors() {
+        assert_ne!(vector_key(&Vector3::new(1.0, 2.0, 3.0)), vector_key(&Vector3::new(1.0, 2.0, 3.0001)));
+    }
+}
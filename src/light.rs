@@ -68,7 +68,10 @@ impl<S> DirLight<S> where S: ScalarFloat {
 pub struct SpotLight<S> {
     pub position: Vector3<S>,
     pub direction: Vector3<S>,
+    /// The cosine of the angle of the inner cone, inside which the light is at
+    /// full strength.
     pub cutoff: S,
+    /// The cosine of the angle of the outer cone, outside which there is no light.
     pub outer_cutoff: S,
 
     pub constant: S,
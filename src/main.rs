@@ -3,6 +3,9 @@ extern crate cglinalg;
 extern crate cgperspective;
 extern crate image;
 extern crate log;
+extern crate memmap;
+extern crate serde;
+extern crate serde_json;
 extern crate file_logger;
 extern crate wavefront_obj;
 
@@ -11,15 +14,34 @@ mod gl {
     include!(concat!(env!("OUT_DIR"), "/gl_bindings.rs"));
 }
 
+mod analysis;
 mod backend;
+mod bounds;
+mod bvh;
+mod cache;
 mod camera;
+mod geometry;
 mod light;
 mod model;
+mod normals;
+mod optimize;
+mod picking;
+mod preprocessor;
+mod primitives;
+mod quantize;
+mod renderer;
+mod simplify;
+mod subdivide;
+mod transform;
+mod uniform_blocks;
+mod validate;
+mod weld;
 
 
 use cglinalg::{
     Angle,
     Degrees,
+    Magnitude,
     Matrix4,
     Radians,
     Vector3,
@@ -39,28 +61,49 @@ use glfw::{
     Key
 };
 use gl::types::{
+    GLenum,
     GLfloat,
     GLint,
-    GLuint, 
-    GLvoid, 
-    GLsizeiptr,
 };
 use log::{
     info
 };
 use crate::backend::{
     OpenGLContext,
-    ShaderSourceBuilder,
-    ShaderSource,
     ShaderHandle,
+    ShaderStage,
+    VertexBuffers,
+};
+use crate::bvh::{
+    Bvh,
 };
 use crate::camera::{
     PerspectiveFovCamera,
 };
 use crate::light::*;
 use crate::model::*;
+use crate::optimize::{
+    OptimizeOptions,
+};
+use crate::preprocessor::{
+    ShaderDefines,
+    ShaderLibrary,
+    ShaderVariantCache,
+};
+use crate::quantize::{
+    VertexFormat,
+};
+use crate::renderer::{
+    ModelRenderer,
+};
+use crate::simplify::{
+    LodChainOptions,
+};
+use crate::uniform_blocks::{
+    LightsLayout,
+    SharedUniformBlocks,
+};
 
-use std::mem;
 use std::ptr;
 
 
@@ -72,72 +115,43 @@ const CLEAR_DEPTH: [f32; 4] = [1.0_f32, 1.0_f32, 1.0_f32, 1.0_f32];
 const SCREEN_WIDTH: u32 = 800;
 const SCREEN_HEIGHT: u32 = 600;
 
+// The width of line primitives and the diameter of point primitives in pixels. The
+// line width is clamped to the range the driver supports at startup.
+const PRIMITIVE_LINE_WIDTH: f32 = 2.0;
+const PRIMITIVE_POINT_SIZE: f32 = 6.0;
+
+// The model asset is embedded in the executable. Its cache file is written next to
+// the asset in the source tree.
+const BACKPACK_ASSET_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/assets/backpack.zip");
+
+// The vertical field of view of the camera in degrees.
+const CAMERA_FOVY_DEGREES: f32 = 72.0;
+
+// The largest simplification error of the level of detail the model is drawn with,
+// as a fraction of the viewport height.
+const MAX_SCREEN_ERROR: f32 = 1.0 / SCREEN_HEIGHT as f32;
+
+// The edge length of the cubes marking the positions of the point lights.
+const LIGHT_GIZMO_SIZE: f32 = 0.2;
 
-#[inline]
-fn offset_of<S, T>(ptr1: &S, ptr2: &T) -> usize {
-    unsafe {
-        ptr1 as *const S as usize - ptr2 as *const T as usize
-    }
-}
 
 fn create_backpack_model() -> Model {
     let buffer = include_bytes!("../assets/backpack.zip");
-    let asset = model::load_from_memory(buffer, "backpack.zip", false).unwrap();
+    let mut options = ModelLoadOptions::new(false);
+    options.sanitize = true;
+    options.lod_chain = Some(LodChainOptions::new());
+    options.optimize = Some(OptimizeOptions::new());
+    options.vertex_format = VertexFormat::PackedSnorm16;
+    let cache_path = cache::cache_path(BACKPACK_ASSET_PATH);
+    let asset = cache::load_or_build(&cache_path, buffer, "backpack.zip", &options).unwrap();
 
     asset
 }
-/*
-fn create_box_mesh() -> ObjMesh {
-    let points: Vec<[f32; 3]> = vec![
-        [-0.5, -0.5, -0.5], [ 0.5, -0.5, -0.5], [ 0.5,  0.5, -0.5],
-        [ 0.5,  0.5, -0.5], [-0.5,  0.5, -0.5], [-0.5, -0.5, -0.5],
-        [-0.5, -0.5,  0.5], [ 0.5, -0.5,  0.5], [ 0.5,  0.5,  0.5],  
-        [ 0.5,  0.5,  0.5], [-0.5,  0.5,  0.5], [-0.5, -0.5,  0.5],
-        [-0.5,  0.5,  0.5], [-0.5,  0.5, -0.5], [-0.5, -0.5, -0.5], 
-        [-0.5, -0.5, -0.5], [-0.5, -0.5,  0.5], [-0.5,  0.5,  0.5], 
-        [ 0.5,  0.5,  0.5], [ 0.5,  0.5, -0.5], [ 0.5, -0.5, -0.5], 
-        [ 0.5, -0.5, -0.5], [ 0.5, -0.5,  0.5], [ 0.5,  0.5,  0.5],
-        [-0.5, -0.5, -0.5], [ 0.5, -0.5, -0.5], [ 0.5, -0.5,  0.5],  
-        [ 0.5, -0.5,  0.5], [-0.5, -0.5,  0.5], [-0.5, -0.5, -0.5],
-        [-0.5,  0.5, -0.5], [ 0.5,  0.5, -0.5], [ 0.5,  0.5,  0.5], 
-        [ 0.5,  0.5,  0.5], [-0.5,  0.5,  0.5], [-0.5,  0.5, -0.5],  
-    ];
-    let tex_coords = vec![
-        [0.0, 0.0], [1.0, 0.0], [1.0, 1.0],
-        [1.0, 1.0], [0.0, 1.0], [0.0, 0.0],
-        [0.0, 0.0], [1.0, 0.0], [1.0, 1.0],
-        [1.0, 1.0], [0.0, 1.0], [0.0, 0.0],
-        [1.0, 0.0], [1.0, 1.0], [0.0, 1.0],
-        [0.0, 1.0], [0.0, 0.0], [1.0, 0.0],
-        [1.0, 0.0], [1.0, 1.0], [0.0, 1.0],
-        [0.0, 1.0], [0.0, 0.0], [1.0, 0.0],
-        [0.0, 1.0], [1.0, 1.0], [1.0, 0.0],
-        [1.0, 0.0], [0.0, 0.0], [0.0, 1.0],
-        [0.0, 1.0], [1.0, 1.0], [1.0, 0.0],
-        [1.0, 0.0], [0.0, 0.0], [0.0, 1.0]
-    ];
-    let normals = vec![
-        [ 0.0,  0.0, -1.0], [ 0.0,  0.0, -1.0], [ 0.0,  0.0, -1.0],
-        [ 0.0,  0.0, -1.0], [ 0.0,  0.0, -1.0], [ 0.0,  0.0, -1.0],
-        [ 0.0,  0.0,  1.0], [ 0.0,  0.0,  1.0], [ 0.0,  0.0,  1.0],
-        [ 0.0,  0.0,  1.0], [ 0.0,  0.0,  1.0], [ 0.0,  0.0,  1.0],
-        [-1.0,  0.0,  0.0], [-1.0,  0.0,  0.0], [-1.0,  0.0,  0.0],
-        [-1.0,  0.0,  0.0], [-1.0,  0.0,  0.0], [-1.0,  0.0,  0.0],
-        [ 1.0,  0.0,  0.0], [ 1.0,  0.0,  0.0], [ 1.0,  0.0,  0.0],
-        [ 1.0,  0.0,  0.0], [ 1.0,  0.0,  0.0], [ 1.0,  0.0,  0.0],
-        [ 0.0, -1.0,  0.0], [ 0.0, -1.0,  0.0], [ 0.0, -1.0,  0.0],
-        [ 0.0, -1.0,  0.0], [ 0.0, -1.0,  0.0], [ 0.0, -1.0,  0.0],
-        [ 0.0,  1.0,  0.0], [ 0.0,  1.0,  0.0], [ 0.0,  1.0,  0.0],
-        [ 0.0,  1.0,  0.0], [ 0.0,  1.0,  0.0], [ 0.0,  1.0,  0.0],
-    ];
-
-    ObjMesh::new(points, tex_coords, normals)
-}
-*/
+
 fn create_camera(width: u32, height: u32) -> PerspectiveFovCamera<f32> {
     let near = 0.1;
     let far = 100.0;
-    let fovy = Degrees(72.0);
+    let fovy = Degrees(CAMERA_FOVY_DEGREES);
     let aspect = width as f32 / height as f32;
     let model_spec = PerspectiveFovSpec::new(
         fovy, 
@@ -166,8 +180,10 @@ fn create_camera(width: u32, height: u32) -> PerspectiveFovCamera<f32> {
 
     Camera::new(&model_spec, &attitude_spec, &kinematics_spec)
 }
-/*
-fn create_cube_lights() -> [PointLight<f32>; 4] {
+
+/// The point lights of the scene. The light arrays of the shaders are sized to hold
+/// however many lights there are.
+fn create_cube_lights() -> Vec<PointLight<f32>> {
     let position_0 = Vector3::new(0.7, 0.2, 2.0);
     let ambient_0 = Vector3::new(0.2, 0.2, 0.2);
     let diffuse_0 = Vector3::new(0.5, 0.5, 0.5);
@@ -237,7 +253,7 @@ fn create_cube_lights() -> [PointLight<f32>; 4] {
         specular_3
     );
 
-    [light_0, light_1, light_2, light_3]
+    vec![light_0, light_1, light_2, light_3]
 }
 
 fn create_directional_light() -> DirLight<f32> {
@@ -249,6 +265,48 @@ fn create_directional_light() -> DirLight<f32> {
     DirLight::new(direction, ambient, diffuse, specular)
 }
 
+/// A flashlight that follows the camera. Its position and direction are updated
+/// every frame.
+fn create_flashlight() -> SpotLight<f32> {
+    let position = Vector3::zero();
+    let direction = Vector3::new(0.0, 0.0, -1.0);
+    let cutoff = f32::cos(12.5_f32.to_radians());
+    let outer_cutoff = f32::cos(17.5_f32.to_radians());
+    let ambient = Vector3::zero();
+    let diffuse = Vector3::new(1.0, 1.0, 1.0);
+    let specular = Vector3::new(1.0, 1.0, 1.0);
+    let constant = 1.0;
+    let linear = 0.09;
+    let quadratic = 0.032;
+
+    SpotLight::new(
+        position,
+        direction,
+        cutoff,
+        outer_cutoff,
+        constant,
+        linear,
+        quadratic,
+        ambient,
+        diffuse,
+        specular
+    )
+}
+
+/// The position and the forward direction of a camera in world space, which the
+/// inverse of its view matrix maps from the origin and the negative z axis.
+fn camera_position_and_forward(view_mat: &Matrix4<f32>) -> (Vector3<f32>, Vector3<f32>) {
+    match view_mat.inverse() {
+        Some(world) => {
+            let position = Vector3::new(world[3][0], world[3][1], world[3][2]);
+            let forward = Vector3::new(-world[2][0], -world[2][1], -world[2][2]);
+            (position, forward.normalize())
+        }
+        None => (Vector3::zero(), Vector3::new(0.0, 0.0, -1.0)),
+    }
+}
+
+/*
 fn create_mesh_shader_source() -> ShaderSource<'static, 'static, 'static> {
     let vertex_name = "multiple_lights.vert.glsl";
     let vertex_source = include_str!("../shaders/multiple_lights.vert.glsl");
@@ -278,119 +336,189 @@ fn create_cube_light_shader_source() -> ShaderSource<'static, 'static, 'static>
 }
 */
 
-fn create_model_shader_source() -> ShaderSource<'static, 'static, 'static> {
-    let vertex_name = "model.vert.glsl";
-    let vertex_source = include_str!("../shaders/model.vert.glsl");
-    let fragment_name = "model.frag.glsl";
-    let fragment_source = include_str!("../shaders/model.frag.glsl");
+/// The shaders of the demo, named by their paths relative to the shader directory.
+fn create_shader_library() -> ShaderLibrary {
+    let mut library = ShaderLibrary::new();
+    library.insert("model.vert.glsl", include_str!("../shaders/model.vert.glsl"));
+    library.insert("model.frag.glsl", include_str!("../shaders/model.frag.glsl"));
+    library.insert("outline.vert.glsl", include_str!("../shaders/outline.vert.glsl"));
+    library.insert("outline.frag.glsl", include_str!("../shaders/outline.frag.glsl"));
+    library.insert("primitive.vert.glsl", include_str!("../shaders/primitive.vert.glsl"));
+    library.insert("primitive.frag.glsl", include_str!("../shaders/primitive.frag.glsl"));
+    library.insert("normals.vert.glsl", include_str!("../shaders/normals.vert.glsl"));
+    library.insert("normals.geom.glsl", include_str!("../shaders/normals.geom.glsl"));
+    library.insert("normals.frag.glsl", include_str!("../shaders/normals.frag.glsl"));
+    library.insert("include/camera.glsl", include_str!("../shaders/include/camera.glsl"));
+    library.insert("include/lights.glsl", include_str!("../shaders/include/lights.glsl"));
+    library.insert("include/octahedral.glsl", include_str!("../shaders/include/octahedral.glsl"));
+
+    library
+}
 
-    ShaderSourceBuilder::new(
-        vertex_name,
-        vertex_source,
-        fragment_name,
-        fragment_source
-    )
-    .build()
+const MODEL_SHADER_STAGES: [(ShaderStage, &str); 2] = [
+    (ShaderStage::Vertex, "model.vert.glsl"),
+    (ShaderStage::Fragment, "model.frag.glsl"),
+];
+
+const OUTLINE_SHADER_STAGES: [(ShaderStage, &str); 2] = [
+    (ShaderStage::Vertex, "outline.vert.glsl"),
+    (ShaderStage::Fragment, "outline.frag.glsl"),
+];
+
+const PRIMITIVE_SHADER_STAGES: [(ShaderStage, &str); 2] = [
+    (ShaderStage::Vertex, "primitive.vert.glsl"),
+    (ShaderStage::Fragment, "primitive.frag.glsl"),
+];
+
+const NORMAL_SHADER_STAGES: [(ShaderStage, &str); 3] = [
+    (ShaderStage::Vertex, "normals.vert.glsl"),
+    (ShaderStage::Geometry, "normals.geom.glsl"),
+    (ShaderStage::Fragment, "normals.frag.glsl"),
+];
+
+/// The definitions of the model shader variant that decodes a vertex format and
+/// lights with the light arrays of a lights layout.
+fn model_shader_defines(vertex_format: VertexFormat, lights_layout: LightsLayout) -> ShaderDefines {
+    let defines = match vertex_format {
+        VertexFormat::Float32 => ShaderDefines::new(),
+        VertexFormat::PackedHalf | VertexFormat::PackedSnorm16 => ShaderDefines::new().with_flag("PACKED_VERTICES"),
+    };
+
+    defines.with_defines(&lights_layout.shader_defines())
 }
 
+/// Compile a shader program variant and attach its uniform blocks to the shared
+/// camera and lights buffers.
+fn send_to_gpu_shaders(
+    context: &OpenGLContext,
+    shader_variants: &mut ShaderVariantCache,
+    shared_blocks: &SharedUniformBlocks,
+    stages: &[(ShaderStage, &str)],
+    defines: &ShaderDefines) -> ShaderHandle
+{
+    let shader = shader_variants.get_or_compile(context, stages, defines).unwrap();
+    shared_blocks.bind_program(&shader);
+
+    shader
+}
 
-fn send_to_gpu_mesh(shader: ShaderHandle, mesh: &Mesh) -> (GLuint, GLuint, GLuint) {
-    let a_pos_loc = shader.get_attrib_location("aPos");
-    let a_normal_loc = shader.get_attrib_location("aNormal");
-    let a_tex_coords_loc = shader.get_attrib_location("aTexCoords");
+/// How to draw the line and point primitives of a model.
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct PrimitiveStyle {
+    /// The width of lines in pixels, within the range the driver supports.
+    line_width: f32,
+    /// The diameter of points in pixels.
+    point_size: f32,
+    line_color: Vector3<f32>,
+    point_color: Vector3<f32>,
+}
 
-    let mut vao = 0;
-    unsafe {
-        gl::GenVertexArrays(1, &mut vao);
-    };
-    debug_assert!(vao > 0);
-    
-    let mut vbo = 0; 
-    unsafe {
-        gl::GenBuffers(1, &mut vbo);
-    };
-    debug_assert!(vbo > 0);
-    
-    // Element Buffer Object.
-    let mut ebo = 0;
-    unsafe {
-        gl::GenBuffers(1, &mut ebo);
+impl PrimitiveStyle {
+    /// Construct a style with the line width clamped to `line_width_range`, as
+    /// returned by `backend::supported_line_width_range`.
+    fn new(line_width: f32, point_size: f32, line_width_range: (f32, f32)) -> PrimitiveStyle {
+        let (min_width, max_width) = line_width_range;
+
+        PrimitiveStyle {
+            line_width: f32::max(min_width, f32::min(max_width, line_width)),
+            point_size: point_size,
+            line_color: Vector3::new(0.9, 0.9, 0.9),
+            point_color: Vector3::new(1.0, 0.2, 0.2),
+        }
+    }
+}
+
+/// Send line or point vertices to the GPU. The vertices are drawn in order, so
+/// the index buffer is just the sequence of vertex indices.
+fn send_to_gpu_primitives(context: &OpenGLContext, shader: &ShaderHandle, vertices: &[Vertex]) -> VertexBuffers {
+    let vertex_indices: Vec<u32> = (0..vertices.len() as u32).collect();
+    let packed_vertices = quantize::pack_vertices("", vertices, &vertex_indices, VertexFormat::Float32);
+
+    renderer::send_to_gpu_packed_mesh(context, shader, &packed_vertices)
+}
+
+/// Draw the line or point primitives of a mesh, where `mode` is either
+/// `gl::LINES` or `gl::POINTS`.
+fn draw_primitives(
+    shader: &ShaderHandle,
+    model_mat: &Matrix4<f32>,
+    style: &PrimitiveStyle,
+    mode: GLenum,
+    buffers: &VertexBuffers)
+{
+    if buffers.index_count == 0 {
+        return;
     }
-    debug_assert!(ebo > 0);
 
+    let color = if mode == gl::POINTS { &style.point_color } else { &style.line_color };
+    shader.use_program();
+    shader.set_mat4("model", model_mat);
+    shader.set_float("point_size", style.point_size);
+    shader.set_vec3("primitive_color", color);
     unsafe {
-        let null_vertex = mem::zeroed::<Vertex>();
-        gl::BindVertexArray(vao);
-        gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
-        gl::BufferData(
-            gl::ARRAY_BUFFER, 
-            (mesh.vertices.len() * mem::size_of::<Vertex>()) as GLsizeiptr, 
-            mesh.vertices.as_ptr() as *const GLvoid, 
-            gl::STATIC_DRAW
-        );
-        gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, ebo);
-        gl::BufferData(
-            gl::ELEMENT_ARRAY_BUFFER,
-            (mesh.vertex_indices.len() * mem::size_of::<u32>()) as GLsizeiptr,
-            mesh.vertex_indices.as_ptr() as *const GLvoid,
-            gl::STATIC_DRAW
-        );
-        gl::VertexAttribPointer(
-            a_pos_loc, 
-            3, 
-            gl::FLOAT, 
-            gl::FALSE, 
-            mem::size_of::<Vertex>() as GLint, 
-            offset_of(&null_vertex, &null_vertex.position) as *const GLvoid
-        );
-        gl::VertexAttribPointer(
-            a_normal_loc, 
-            3, 
-            gl::FLOAT, 
-            gl::FALSE, 
-            mem::size_of::<Vertex>() as GLint,
-            offset_of(&null_vertex, &null_vertex.normal) as *const GLvoid
-        );
-        gl::VertexAttribPointer(
-            a_tex_coords_loc,
-            2,
-            gl::FLOAT,
-            gl::FALSE,
-            mem::size_of::<Vertex>() as GLint,
-            offset_of(&null_vertex, &null_vertex.tex_coords) as *const GLvoid
-        );
-        /*
-        gl::VertexAttribPointer(
-            3,
-            3,
-            gl::FLOAT,
-            gl::FALSE,
-            mem::size_of::<Vertex>() as GLint,
-            offset_of(&null_vertex, &null_vertex.tangent) as *const GLvoid
-        );
-        gl::VertexAttribPointer(
-            4,
-            3,
-            gl::FLOAT,
-            gl::FALSE,
-            mem::size_of::<Vertex>() as GLint,
-            offset_of(&null_vertex, &null_vertex.bitangent) as *const GLvoid
-        );
-        */
-
-        gl::EnableVertexAttribArray(0);
-        gl::EnableVertexAttribArray(1);
-        gl::EnableVertexAttribArray(2);
-        // Tangent and Bitangent are not in use.
-        // gl::EnableVertexAttribArray(3);
-        // gl::EnableVertexAttribArray(4);
+        gl::LineWidth(style.line_width);
+        buffers.bind();
+        gl::DrawElements(mode, buffers.index_count as GLint, gl::UNSIGNED_INT, ptr::null());
+    }
+}
+
+/// Draw a small cube in the color of each point light at the position of the light.
+fn draw_light_gizmos(
+    shader: &ShaderHandle,
+    point_lights: &[PointLight<f32>],
+    buffers: &VertexBuffers)
+{
+    shader.use_program();
+    shader.set_float("point_size", 1.0);
+    buffers.bind();
+    for light in point_lights.iter() {
+        shader.set_mat4("model", &light.model_matrix());
+        shader.set_vec3("primitive_color", &light.diffuse);
+        unsafe {
+            gl::DrawElements(gl::TRIANGLES, buffers.index_count as GLint, gl::UNSIGNED_INT, ptr::null());
+        }
     }
+}
 
-    (vao, vbo, ebo)
+/// Draw the selection outline of a mesh as an inverted hull: the mesh is inflated
+/// along its normals and only its back faces are drawn, so the outline shows
+/// around the silhouette of the mesh.
+fn draw_selection_outline(
+    shader: &ShaderHandle,
+    model_mat: &Matrix4<f32>,
+    outline_width: f32,
+    buffers: &VertexBuffers)
+{
+    shader.use_program();
+    shader.set_mat4("model", model_mat);
+    shader.set_float("outline_width", outline_width);
+    shader.set_vec3("outline_color", &Vector3::new(1.0, 0.6, 0.0));
+    unsafe {
+        gl::Enable(gl::CULL_FACE);
+        gl::CullFace(gl::FRONT);
+        buffers.bind();
+        gl::DrawElements(gl::TRIANGLES, buffers.index_count as GLint, gl::UNSIGNED_INT, ptr::null());
+        gl::CullFace(gl::BACK);
+        gl::Disable(gl::CULL_FACE);
+    }
 }
 
-fn send_to_gpu_shaders(_context: &mut OpenGLContext, source: &ShaderSource) -> ShaderHandle {
-    backend::compile(source).unwrap()
+/// Draw the vertex normals of a mesh as line segments of length `normal_length`,
+/// generated by a geometry shader.
+fn draw_normals(
+    shader: &ShaderHandle,
+    model_mat: &Matrix4<f32>,
+    normal_length: f32,
+    buffers: &VertexBuffers)
+{
+    shader.use_program();
+    shader.set_mat4("model", model_mat);
+    shader.set_float("normal_length", normal_length);
+    shader.set_vec3("normal_color", &Vector3::new(0.2, 0.4, 1.0));
+    unsafe {
+        buffers.bind();
+        gl::DrawElements(gl::TRIANGLES, buffers.index_count as GLint, gl::UNSIGNED_INT, ptr::null());
+    }
 }
 
 /// Initialize the logger.
@@ -507,27 +635,138 @@ fn process_input(context: &mut OpenGLContext) -> CameraMovement {
     movement
 }
 
+/// Pick the mesh under the cursor when the left mouse button is pressed, and
+/// update the selected mesh. The N key toggles the vertex normal visualization.
+fn process_events(
+    context: &OpenGLContext,
+    camera: &PerspectiveFovCamera<f32>,
+    model: &Model,
+    bvh: &Bvh,
+    model_mat: &Matrix4<f32>,
+    selected_mesh: &mut Option<usize>,
+    show_normals: &mut bool)
+{
+    for (_, event) in glfw::flush_messages(&context.events) {
+        match event {
+            glfw::WindowEvent::Key(Key::N, _, Action::Press, _) => {
+                *show_normals = !*show_normals;
+            }
+            glfw::WindowEvent::MouseButton(glfw::MouseButtonLeft, Action::Press, _) => {
+                let (cursor_x, cursor_y) = context.window.get_cursor_pos();
+                let (width, height) = context.window.get_size();
+                let ray = picking::cursor_ray(
+                    cursor_x,
+                    cursor_y,
+                    width as u32,
+                    height as u32,
+                    camera.projection(),
+                    camera.view_matrix()
+                );
+                match ray.and_then(|ray| picking::pick(model, bvh, model_mat, &ray)) {
+                    Some(hit) => {
+                        info!(
+                            "Picked mesh \"{}\" at position {:?} with normal {:?}",
+                            hit.mesh_name, hit.position, hit.normal
+                        );
+                        *selected_mesh = Some(hit.mesh_index);
+                    }
+                    None => {
+                        *selected_mesh = None;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
 fn main() {
-    let model = create_backpack_model();
     init_logger("opengl_demo.log");
     info!("BEGIN LOG");
+    let model = create_backpack_model();
     info!("Model name: \"{}\"", model.name);
     info!("Number of meshes loaded: {}", model.meshes.len());
     info!("Number of textures loaded: {}", model.textures_loaded.len());
+    info!("Model bounding box: {:?}", model.aabb);
+    info!("Model bounding sphere: {:?}", model.bounding_sphere);
+    for (i, lod) in model.lods.iter().enumerate() {
+        let triangle_count: usize = lod.meshes.iter().map(|mesh| mesh.vertex_indices.len() / 3).sum();
+        info!("Level of detail {}: {} triangles with error {}", i + 1, triangle_count, lod.error);
+    }
+    info!("Vertex memory: {}", quantize::vertex_memory_report(&model));
+    for mesh_analysis in analysis::analyze_model(&model).iter() {
+        info!("{}", serde_json::to_string(mesh_analysis).unwrap());
+    }
     let mut camera = create_camera(SCREEN_WIDTH, SCREEN_HEIGHT);
     let mut context = init_gl(SCREEN_WIDTH, SCREEN_HEIGHT);
-    let model_shader_source = create_model_shader_source();
-    let model_shader = send_to_gpu_shaders(&mut context, &model_shader_source);
-    let (
-        model_vao,
-        model_vbo,
-        model_ebo) = send_to_gpu_mesh(model_shader, &model.meshes[0]);
+    let mut shader_variants = ShaderVariantCache::new(create_shader_library());
+    let dir_light = create_directional_light();
+    let point_lights = create_cube_lights();
+    // The flashlight is the only spot light.
+    let mut spot_lights = vec![create_flashlight()];
+    let lights_layout = LightsLayout::new(point_lights.len(), spot_lights.len());
+    let shared_blocks = SharedUniformBlocks::new(&context, lights_layout);
+    let model_shader_defines = model_shader_defines(model.vertex_format, shared_blocks.lights_layout());
+    let model_shader = send_to_gpu_shaders(
+        &context, &mut shader_variants, &shared_blocks, &MODEL_SHADER_STAGES, &model_shader_defines
+    );
+    let model_renderer = ModelRenderer::new(&context, model_shader, &model).unwrap();
     let model_mat: Matrix4<f32> = Matrix4::identity();
+    let bvh = Bvh::new(&model);
+    let outline_shader = send_to_gpu_shaders(
+        &context, &mut shader_variants, &shared_blocks, &OUTLINE_SHADER_STAGES, &ShaderDefines::new()
+    );
+    // The outline and normal shaders share the position and normal attribute
+    // locations, so they draw from the same buffers. There is one set of buffers
+    // per level of detail, so the overlays match the level the model is drawn at.
+    // Every level keeps the meshes of the model in the same order, so a picked mesh
+    // index selects the same mesh in each of them.
+    let lod_count = model.lods.len() + 1;
+    let mesh_buffers: Vec<Vec<VertexBuffers>> = (0..lod_count)
+        .map(|level| {
+            model.lod_meshes(level).iter()
+                .map(|mesh| {
+                    let packed_mesh = quantize::pack_mesh(mesh, VertexFormat::Float32);
+                    renderer::send_to_gpu_packed_mesh(&context, &outline_shader, &packed_mesh)
+                })
+                .collect()
+        })
+        .collect();
+    let outline_width = 0.01 * f32::max(model.bounding_sphere.radius, 0.0);
+    let mut selected_mesh = None;
+    let normal_shader = send_to_gpu_shaders(
+        &context, &mut shader_variants, &shared_blocks, &NORMAL_SHADER_STAGES, &ShaderDefines::new()
+    );
+    let normal_length = 0.02 * f32::max(model.bounding_sphere.radius, 0.0);
+    let mut show_normals = false;
+    let primitive_shader = send_to_gpu_shaders(
+        &context, &mut shader_variants, &shared_blocks, &PRIMITIVE_SHADER_STAGES, &ShaderDefines::new()
+    );
+    let primitive_meshes: Vec<Vec<(VertexBuffers, VertexBuffers)>> = (0..lod_count)
+        .map(|level| {
+            model.lod_meshes(level).iter()
+                .map(|mesh| {
+                    (
+                        send_to_gpu_primitives(&context, &primitive_shader, &mesh.line_vertices),
+                        send_to_gpu_primitives(&context, &primitive_shader, &mesh.point_vertices),
+                    )
+                })
+                .collect()
+        })
+        .collect();
+    let primitive_style = PrimitiveStyle::new(
+        PRIMITIVE_LINE_WIDTH, PRIMITIVE_POINT_SIZE, backend::supported_line_width_range()
+    );
+    let light_gizmo = primitives::cube(LIGHT_GIZMO_SIZE);
+    let light_gizmo_buffers = renderer::send_to_gpu_packed_mesh(
+        &context, &primitive_shader, &quantize::pack_mesh(&light_gizmo, VertexFormat::Float32)
+    );
 
 
     unsafe {
         gl::Enable(gl::DEPTH_TEST);
         gl::DepthFunc(gl::LESS);
+        gl::Enable(gl::PROGRAM_POINT_SIZE);
         gl::ClearBufferfv(gl::COLOR, 0, &CLEAR_COLOR[0] as *const GLfloat);
         gl::ClearBufferfv(gl::DEPTH, 0, &CLEAR_DEPTH[0] as *const GLfloat);
         gl::Viewport(0, 0, context.width as GLint, context.height as GLint);
@@ -537,6 +776,7 @@ fn main() {
         let elapsed_seconds = context.update_timers();
         context.update_fps_counter();
         context.glfw.poll_events();
+        process_events(&context, &camera, &model, &bvh, &model_mat, &mut selected_mesh, &mut show_normals);
         let (width, height) = context.window.get_framebuffer_size();
         if (width != context.width as i32) && (height != context.height as i32) {
             camera.update_viewport(width as usize, height as usize);
@@ -546,6 +786,35 @@ fn main() {
         let delta_movement = process_input(&mut context);
         camera.update_movement(delta_movement, elapsed_seconds as f32);
 
+        renderer::clear_frame(&CLEAR_COLOR, &CLEAR_DEPTH);
+        let (camera_position, camera_forward) = camera_position_and_forward(camera.view_matrix());
+        spot_lights[0].update(&camera_position, &camera_forward);
+        shared_blocks.update_camera(camera.view_matrix(), camera.projection(), &camera_position);
+        shared_blocks.update_lights(Some(&dir_light), &point_lights, &spot_lights);
+        let bounding_sphere = model.bounding_sphere.transform(&model_mat);
+        let screen_size = bounding_sphere.screen_size(&camera_position, CAMERA_FOVY_DEGREES.to_radians());
+        let lod = model.select_lod(screen_size, MAX_SCREEN_ERROR);
+        model_renderer.draw(&model_mat, lod);
+        for (lines, points) in primitive_meshes[lod].iter() {
+            draw_primitives(
+                &primitive_shader, &model_mat, &primitive_style, gl::LINES, lines
+            );
+            draw_primitives(
+                &primitive_shader, &model_mat, &primitive_style, gl::POINTS, points
+            );
+        }
+        draw_light_gizmos(&primitive_shader, &point_lights, &light_gizmo_buffers);
+        if let Some(mesh_index) = selected_mesh {
+            draw_selection_outline(
+                &outline_shader, &model_mat, outline_width, &mesh_buffers[lod][mesh_index]
+            );
+        }
+        if show_normals {
+            for buffers in mesh_buffers[lod].iter() {
+                draw_normals(&normal_shader, &model_mat, normal_length, buffers);
+            }
+        }
+
         context.window.swap_buffers();
     }
 
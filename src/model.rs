@@ -1,13 +1,51 @@
 use crate::backend;
 use crate::backend::{
+    AttributeType,
     TextureImage2D,
+    VertexAttribute,
+    VertexLayout,
 };
+use crate::bounds::{
+    Aabb,
+    BoundingSphere,
+};
+use crate::normals;
+use crate::normals::{
+    NormalGeneration,
+};
+use crate::optimize;
+use crate::optimize::{
+    OptimizeOptions,
+};
+use crate::quantize::{
+    VertexFormat,
+};
+use crate::simplify;
+use crate::simplify::{
+    LodChainOptions,
+};
+use crate::subdivide;
+use crate::subdivide::{
+    SubdivisionOptions,
+};
+use crate::transform::{
+    MeshTransformOptions,
+};
+use crate::validate;
+use crate::weld;
 use cglinalg::{
     Vector2,
     Vector3,
+    Vector4,
+};
+use log::{
+    info,
 };
 use wavefront_obj::obj;
 use wavefront_obj::mtl;
+use std::borrow::{
+    Cow,
+};
 use std::collections::{
     HashMap,
 };
@@ -18,6 +56,8 @@ use std::io;
 use std::io::{
     Read,
 };
+use std::mem;
+use std::ptr;
 use zip::{
     ZipArchive,
 };
@@ -31,6 +71,77 @@ pub struct Vertex {
     pub tex_coords: Vector2<f32>,
     pub tangent: Vector3<f32>,
     pub bitangent: Vector3<f32>,
+    /// The linear RGBA color of the vertex, multiplied into the shaded color.
+    pub color: Vector4<f32>,
+    /// A second set of texture coordinates, for light maps and detail textures.
+    pub tex_coords2: Vector2<f32>,
+}
+
+impl Vertex {
+    /// The color of vertices without a color of their own. Opaque white leaves the
+    /// shading of the vertex unchanged.
+    #[inline]
+    pub fn default_color() -> Vector4<f32> {
+        Vector4::new(1.0, 1.0, 1.0, 1.0)
+    }
+}
+
+/// The optional vertex attributes a set of vertices actually uses. Vertex layouts
+/// for the GPU only include the attributes of the streams that are present.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct VertexStreams {
+    /// Some vertex has a color other than `Vertex::default_color`.
+    pub colors: bool,
+    /// Some vertex has second texture coordinates other than zero.
+    pub tex_coords2: bool,
+}
+
+impl VertexStreams {
+    /// Find the optional attributes used by any of the vertices.
+    pub fn of<'a, I>(vertices: I) -> VertexStreams
+    where
+        I: IntoIterator<Item = &'a Vertex>
+    {
+        let mut streams = VertexStreams::default();
+        for vertex in vertices {
+            streams.colors |= vertex.color != Vertex::default_color();
+            streams.tex_coords2 |= vertex.tex_coords2 != Vector2::zero();
+        }
+
+        streams
+    }
+}
+
+/// The offset in bytes of a field of a `#[repr(C)]` struct from the start of the struct.
+macro_rules! field_offset {
+    ($Struct:ty, $field:ident) => {{
+        let value = mem::MaybeUninit::<$Struct>::uninit();
+        let base = value.as_ptr();
+        // SAFETY: `addr_of!` computes the address of the field without reading the
+        // uninitialized memory or creating a reference to it.
+        let field = unsafe { ptr::addr_of!((*base).$field) };
+
+        (field as usize) - (base as usize)
+    }};
+}
+
+impl VertexLayout for Vertex {
+    #[inline]
+    fn stride() -> usize {
+        mem::size_of::<Vertex>()
+    }
+
+    fn attributes() -> Vec<VertexAttribute> {
+        vec![
+            VertexAttribute::new("aPos", 3, AttributeType::Float, false, field_offset!(Vertex, position)),
+            VertexAttribute::new("aNormal", 3, AttributeType::Float, false, field_offset!(Vertex, normal)),
+            VertexAttribute::new("aTexCoords", 2, AttributeType::Float, false, field_offset!(Vertex, tex_coords)),
+            VertexAttribute::new("aTangent", 3, AttributeType::Float, false, field_offset!(Vertex, tangent)),
+            VertexAttribute::new("aBitangent", 3, AttributeType::Float, false, field_offset!(Vertex, bitangent)),
+            VertexAttribute::new("aColor", 4, AttributeType::Float, false, field_offset!(Vertex, color)),
+            VertexAttribute::new("aTexCoords2", 2, AttributeType::Float, false, field_offset!(Vertex, tex_coords2)),
+        ]
+    }
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
@@ -51,7 +162,7 @@ pub struct Texture {
 }
 
 impl Texture {
-    fn new(name: String, kind: TextureKind, data: TextureImage2D) -> Texture {
+    pub fn new(name: String, kind: TextureKind, data: TextureImage2D) -> Texture {
         Texture {
             name: name,
             kind: kind,
@@ -66,21 +177,91 @@ pub struct Mesh {
     pub name: String,
     pub vertices: Vec<Vertex>,
     pub vertex_indices: Vec<u32>,
+    /// The line segments of the mesh, each one a consecutive pair of vertices.
+    pub line_vertices: Vec<Vertex>,
+    /// The points of the mesh.
+    pub point_vertices: Vec<Vertex>,
     pub texture_indices: HashMap<TextureKind, u32>,
+    pub aabb: Aabb,
+    pub bounding_sphere: BoundingSphere,
 }
 
 impl Mesh {
-    fn new(
+    pub fn new(
         name: String, 
         vertices: Vec<Vertex>, 
         vertex_indices: Vec<u32>, 
         texture_indices: HashMap<TextureKind, u32>) -> Mesh {
         
-        Mesh {
+        let mut mesh = Mesh {
             name: name,
             vertices: vertices,
             vertex_indices: vertex_indices,
+            line_vertices: vec![],
+            point_vertices: vec![],
             texture_indices: texture_indices,
+            aabb: Aabb::empty(),
+            bounding_sphere: BoundingSphere::empty(),
+        };
+        mesh.update_bounds();
+
+        mesh
+    }
+
+    /// Construct a mesh with line and point primitives in addition to its triangles.
+    pub fn with_primitives(
+        name: String,
+        vertices: Vec<Vertex>,
+        vertex_indices: Vec<u32>,
+        line_vertices: Vec<Vertex>,
+        point_vertices: Vec<Vertex>,
+        texture_indices: HashMap<TextureKind, u32>) -> Mesh
+    {
+        let mut mesh = Mesh::new(name, vertices, vertex_indices, texture_indices);
+        mesh.line_vertices = line_vertices;
+        mesh.point_vertices = point_vertices;
+        mesh.update_bounds();
+
+        mesh
+    }
+
+    /// Iterate over the vertices of every primitive of the mesh: triangles, lines,
+    /// and points.
+    pub fn all_vertices(&self) -> impl Iterator<Item = &Vertex> + Clone {
+        self.vertices.iter()
+            .chain(self.line_vertices.iter())
+            .chain(self.point_vertices.iter())
+    }
+
+    /// The optional vertex attributes used by the triangles of the mesh.
+    #[inline]
+    pub fn streams(&self) -> VertexStreams {
+        VertexStreams::of(self.vertices.iter())
+    }
+
+    /// Recompute the bounding volumes of the mesh from its vertex positions. Call
+    /// this after modifying the vertex positions of the mesh.
+    pub fn update_bounds(&mut self) {
+        self.aabb = Aabb::from_points(self.all_vertices().map(|vertex| &vertex.position));
+        self.bounding_sphere = BoundingSphere::from_points(
+            self.all_vertices().map(|vertex| &vertex.position)
+        );
+    }
+}
+
+/// A simplified version of every mesh of a model.
+#[derive(Clone, Debug)]
+pub struct ModelLod {
+    pub meshes: Vec<Mesh>,
+    /// The largest distance, in model units, between this level and the original model.
+    pub error: f32,
+}
+
+impl ModelLod {
+    pub fn new(meshes: Vec<Mesh>, error: f32) -> ModelLod {
+        ModelLod {
+            meshes: meshes,
+            error: error,
         }
     }
 }
@@ -90,24 +271,126 @@ pub struct Model {
     pub meshes: Vec<Mesh>,
     pub textures_loaded: Vec<Texture>,
     pub gamma_correction: bool,
+    pub aabb: Aabb,
+    pub bounding_sphere: BoundingSphere,
+    /// Progressively simplified versions of the model, from most to least detailed.
+    pub lods: Vec<ModelLod>,
+    /// The layout the vertices of the model are stored in on the GPU.
+    pub vertex_format: VertexFormat,
 }
 
 impl Model {
-    fn new(
+    pub fn new(
         name: String, 
         meshes: Vec<Mesh>, 
         textures_loaded: Vec<Texture>, 
         gamma_correction: bool) -> Model 
     {
-        Model {
+        let mut model = Model {
             name: name,
             meshes: meshes,
             textures_loaded: textures_loaded,
             gamma_correction: gamma_correction,
+            aabb: Aabb::empty(),
+            bounding_sphere: BoundingSphere::empty(),
+            lods: vec![],
+            vertex_format: VertexFormat::Float32,
+        };
+        model.update_bounds();
+
+        model
+    }
+
+    /// Recompute the bounding volumes of the model from the bounding volumes of its
+    /// meshes. Call this after modifying any of the meshes of the model.
+    pub fn update_bounds(&mut self) {
+        let mut aabb = Aabb::empty();
+        let mut bounding_sphere = BoundingSphere::empty();
+        for mesh in self.meshes.iter() {
+            aabb = aabb.union(&mesh.aabb);
+            bounding_sphere = bounding_sphere.union(&mesh.bounding_sphere);
+        }
+
+        self.aabb = aabb;
+        self.bounding_sphere = bounding_sphere;
+    }
+
+    /// Select the level of detail to draw the model with. Here `screen_size` is the
+    /// fraction of the viewport height covered by the model's bounding sphere, and
+    /// `max_screen_error` is the largest acceptable simplification error as a fraction
+    /// of the viewport height. Level zero is the original model.
+    pub fn select_lod(&self, screen_size: f32, max_screen_error: f32) -> usize {
+        let diameter = 2.0 * self.bounding_sphere.radius;
+        if diameter <= 0.0 {
+            return 0;
+        }
+
+        let mut level = 0;
+        for (i, lod) in self.lods.iter().enumerate() {
+            let screen_error = (lod.error / diameter) * screen_size;
+            if screen_error > max_screen_error {
+                break;
+            }
+            level = i + 1;
+        }
+
+        level
+    }
+
+    /// The meshes making up a level of detail of the model.
+    pub fn lod_meshes(&self, level: usize) -> &[Mesh] {
+        if level == 0 || self.lods.is_empty() {
+            &self.meshes
+        } else {
+            let index = usize::min(level, self.lods.len()) - 1;
+            &self.lods[index].meshes
+        }
+    }
+}
+
+/// The processing steps to apply to a model while loading it.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ModelLoadOptions {
+    pub gamma_correction: bool,
+    /// Snap vertex positions closer together than this distance onto each other.
+    pub weld_tolerance: Option<f32>,
+    /// When to compute vertex normals from the geometry and the OBJ smoothing groups.
+    pub normals: NormalGeneration,
+    /// Remove broken geometry from every mesh before any other processing step.
+    pub sanitize: bool,
+    /// Smooth every mesh with subdivision surfaces.
+    pub subdivision: Option<SubdivisionOptions>,
+    pub transform: MeshTransformOptions,
+    /// Generate a chain of simplified levels of detail for the model.
+    pub lod_chain: Option<LodChainOptions>,
+    /// Reorder the index and vertex buffers of every mesh for the GPU vertex cache.
+    pub optimize: Option<OptimizeOptions>,
+    /// The layout to store the vertices of the model in on the GPU.
+    pub vertex_format: VertexFormat,
+}
+
+impl ModelLoadOptions {
+    pub fn new(gamma_correction: bool) -> ModelLoadOptions {
+        ModelLoadOptions {
+            gamma_correction: gamma_correction,
+            weld_tolerance: None,
+            normals: NormalGeneration::Missing,
+            sanitize: false,
+            subdivision: None,
+            transform: MeshTransformOptions::new(),
+            lod_chain: None,
+            optimize: None,
+            vertex_format: VertexFormat::Float32,
         }
     }
 }
 
+impl Default for ModelLoadOptions {
+    fn default() -> ModelLoadOptions {
+        ModelLoadOptions::new(false)
+    }
+}
+
 #[derive(Debug)]
 pub struct ModelLoadError {
     error: Option<Box<dyn Error>>,
@@ -128,64 +411,197 @@ fn search_material_sets<'a>(
     None
 }
 
-// TODO: Calculate normals from vertex data in the case that they're missing?
-fn load_mesh_vertices(object: &obj::Object) -> Vec<Vertex> {
+/// The smoothing group of every element of an object.
+fn element_smoothing_groups(object: &obj::Object) -> Vec<u32> {
+    let mut smoothing_groups = vec![normals::SMOOTHING_GROUP_OFF; object.element_set.len()];
+    for shape in object.shape_set.iter() {
+        if let Some(smoothing_group) = object.smoothing_group_set.get(shape.smoothing_group) {
+            smoothing_groups[shape.element] = smoothing_group.as_usize() as u32;
+        }
+    }
+
+    smoothing_groups
+}
+
+/// The index of the position of an OBJ vertex.
+#[inline]
+fn vtn_vertex_index(vtn: obj::VTNIndex) -> usize {
+    match vtn {
+        obj::VTNIndex::V(v) => v,
+        obj::VTNIndex::VT(v, _) => v,
+        obj::VTNIndex::VN(v, _) => v,
+        obj::VTNIndex::VTN(v, _, _) => v,
+    }
+}
+
+/// Convert a vertex of an OBJ file into a vertex with zeroed out missing attributes.
+/// The vertex color comes from `colors`, indexed by the position index of the vertex.
+fn load_vertex(object: &obj::Object, colors: &[Vector4<f32>], vtn: obj::VTNIndex) -> Vertex {
+    let (position, normal, tex_coords) = match object.get_vtn_triple(vtn).unwrap() {
+        obj::VTNTriple::V(vp) => {
+            (vp, None, None)
+        }
+        obj::VTNTriple::VT(vp, vt) => {
+            (vp, None, Some(vt))
+        }
+        obj::VTNTriple::VN(vp, vn) => {
+            (vp, Some(vn), None)
+        }
+        obj::VTNTriple::VTN(vp, vt, vn) => {
+            (vp, Some(vn), Some(vt))
+        }
+    };
+
+    Vertex {
+        position: Vector3::new(position.x as f32, position.y as f32, position.z as f32),
+        normal: normal
+            .map(|vn| Vector3::new(vn.x as f32, vn.y as f32, vn.z as f32))
+            .unwrap_or(Vector3::zero()),
+        tex_coords: tex_coords
+            .map(|vt| Vector2::new(vt.u as f32, vt.v as f32))
+            .unwrap_or(Vector2::zero()),
+        tangent: Vector3::zero(),
+        bitangent: Vector3::zero(),
+        color: colors.get(vtn_vertex_index(vtn)).copied().unwrap_or(Vertex::default_color()),
+        tex_coords2: Vector2::zero(),
+    }
+}
+
+/// Separate the vertex colors from the positions of an OBJ file. Many tools write
+/// vertex colors as `v x y z r g b`, or `v x y z r g b a`, which the OBJ parser does
+/// not accept, so the color components are stripped before parsing. Returns the
+/// source without the vertex colors along with the color of every position in the
+/// file, in order, where positions without a color are opaque white. The colors are
+/// empty when the file has no vertex colors at all.
+fn split_vertex_colors(source: &str) -> (Cow<str>, Vec<Vector4<f32>>) {
+    let mut stripped = String::with_capacity(source.len());
+    let mut colors = vec![];
+    let mut has_colors = false;
+    for (i, line) in source.split('\n').enumerate() {
+        if i > 0 {
+            stripped.push('\n');
+        }
+        let mut tokens = line.split_whitespace();
+        if tokens.next() != Some("v") {
+            stripped.push_str(line);
+            continue;
+        }
+
+        let components: Vec<&str> = tokens.collect();
+        let color: Option<Vec<f32>> = if components.len() == 6 || components.len() == 7 {
+            components[3..].iter().map(|component| component.parse::<f32>().ok()).collect()
+        } else {
+            None
+        };
+        match color {
+            Some(color) => {
+                let alpha = if color.len() == 4 { color[3] } else { 1.0 };
+                colors.push(Vector4::new(color[0], color[1], color[2], alpha));
+                has_colors = true;
+                stripped.push_str("v ");
+                stripped.push_str(&components[0..3].join(" "));
+            }
+            None => {
+                colors.push(Vertex::default_color());
+                stripped.push_str(line);
+            }
+        }
+    }
+
+    if has_colors {
+        (Cow::Owned(stripped), colors)
+    } else {
+        (Cow::Borrowed(source), vec![])
+    }
+}
+
+/// The colors of the positions of one object, out of the colors of every position
+/// in the file. The vertex indices of an object are relative to the first position
+/// of the object, which is `vertex_offset` positions into the file.
+fn object_vertex_colors(
+    vertex_colors: &[Vector4<f32>],
+    vertex_offset: usize,
+    vertex_count: usize) -> &[Vector4<f32>]
+{
+    let colors_start = usize::min(vertex_offset, vertex_colors.len());
+    let colors_end = usize::min(vertex_offset + vertex_count, vertex_colors.len());
+
+    &vertex_colors[colors_start..colors_end]
+}
+
+/// Load the faces of an object as a triangle soup, along with the smoothing group
+/// of each triangle.
+fn load_mesh_vertices(object: &obj::Object, colors: &[Vector4<f32>]) -> (Vec<Vertex>, Vec<u32>) {
+    let element_groups = element_smoothing_groups(object);
     let mut vertices = vec![];
-    for element in object.element_set.iter() {
+    let mut smoothing_groups = vec![];
+    for (i, element) in object.element_set.iter().enumerate() {
         match element {
             obj::Element::Face(vtn1, vtn2, vtn3) => {
-                let triples = [
-                    object.get_vtn_triple(*vtn1).unwrap(),
-                    object.get_vtn_triple(*vtn2).unwrap(),
-                    object.get_vtn_triple(*vtn3).unwrap(),
-                ];
-             
-                for triple in triples.iter() {
-                    match triple {
-                        obj::VTNTriple::V(vp) => {
-                            vertices.push(Vertex {
-                                position: Vector3::new(vp.x as f32, vp.y as f32, vp.z as f32),
-                                normal: Vector3::zero(),
-                                tex_coords: Vector2::zero(),
-                                tangent: Vector3::zero(),
-                                bitangent: Vector3::zero(),
-                            });
-                        }
-                        obj::VTNTriple::VT(vp, vt) => {
-                            vertices.push(Vertex {
-                                position: Vector3::new(vp.x as f32, vp.y as f32, vp.z as f32),
-                                normal: Vector3::zero(),
-                                tex_coords: Vector2::new(vt.u as f32, vt.v as f32),
-                                tangent: Vector3::zero(),
-                                bitangent: Vector3::zero(),
-                            });
-                        }
-                        obj::VTNTriple::VN(vp, vn) => {
-                            vertices.push(Vertex {
-                                position: Vector3::new(vp.x as f32, vp.y as f32, vp.z as f32),
-                                normal: Vector3::new(vn.x as f32, vn.y as f32, vn.z as f32),
-                                tex_coords: Vector2::zero(),
-                                tangent: Vector3::zero(),
-                                bitangent: Vector3::zero(),
-                            });
-                        }
-                        obj::VTNTriple::VTN(vp, vt, vn) => {
-                            vertices.push(Vertex {
-                                position: Vector3::new(vp.x as f32, vp.y as f32, vp.z as f32),
-                                normal: Vector3::new(vn.x as f32, vn.y as f32, vn.z as f32),
-                                tex_coords: Vector2::new(vt.u as f32, vt.v as f32),
-                                tangent: Vector3::zero(),
-                                bitangent: Vector3::zero(),
-                            });
-                        }
-                    }
-                }
+                smoothing_groups.push(element_groups[i]);
+                vertices.push(load_vertex(object, colors, *vtn1));
+                vertices.push(load_vertex(object, colors, *vtn2));
+                vertices.push(load_vertex(object, colors, *vtn3));
             }
             _ => {}
         }
     }
 
-    vertices
+    (vertices, smoothing_groups)
+}
+
+/// Load the line and point elements of an object. The parser splits polylines into
+/// individual segments, so each consecutive pair of line vertices is one segment.
+fn load_mesh_lines_and_points(
+    object: &obj::Object,
+    colors: &[Vector4<f32>]) -> (Vec<Vertex>, Vec<Vertex>)
+{
+    let mut line_vertices = vec![];
+    let mut point_vertices = vec![];
+    for element in object.element_set.iter() {
+        match element {
+            obj::Element::Line(vtn1, vtn2) => {
+                line_vertices.push(load_vertex(object, colors, *vtn1));
+                line_vertices.push(load_vertex(object, colors, *vtn2));
+            }
+            obj::Element::Point(vtn) => {
+                point_vertices.push(load_vertex(object, colors, *vtn));
+            }
+            obj::Element::Face(_, _, _) => {}
+        }
+    }
+
+    (line_vertices, point_vertices)
+}
+
+/// A key identifying a vertex by the exact bit patterns of its attributes.
+fn vertex_key(vertex: &Vertex) -> [u32; 20] {
+    [
+        vertex.position.x.to_bits(), vertex.position.y.to_bits(), vertex.position.z.to_bits(),
+        vertex.normal.x.to_bits(), vertex.normal.y.to_bits(), vertex.normal.z.to_bits(),
+        vertex.tex_coords.x.to_bits(), vertex.tex_coords.y.to_bits(),
+        vertex.tangent.x.to_bits(), vertex.tangent.y.to_bits(), vertex.tangent.z.to_bits(),
+        vertex.bitangent.x.to_bits(), vertex.bitangent.y.to_bits(), vertex.bitangent.z.to_bits(),
+        vertex.color.x.to_bits(), vertex.color.y.to_bits(), vertex.color.z.to_bits(), vertex.color.w.to_bits(),
+        vertex.tex_coords2.x.to_bits(), vertex.tex_coords2.y.to_bits(),
+    ]
+}
+
+/// Merge vertices that are identical in every attribute, turning a triangle soup into
+/// an indexed vertex list.
+pub fn deduplicate_vertices(vertices: &[Vertex]) -> (Vec<Vertex>, Vec<u32>) {
+    let mut unique_vertices = vec![];
+    let mut vertex_indices = Vec::with_capacity(vertices.len());
+    let mut lookup = HashMap::new();
+    for vertex in vertices.iter() {
+        let index = *lookup.entry(vertex_key(vertex)).or_insert_with(|| {
+            unique_vertices.push(*vertex);
+            (unique_vertices.len() - 1) as u32
+        });
+        vertex_indices.push(index);
+    }
+
+    (unique_vertices, vertex_indices)
 }
 
 fn lookup_texture(textures_loaded: &[Texture], texture_name: &str) -> Option<u32> {
@@ -238,6 +654,14 @@ pub fn load_from_memory(
     buffer: &[u8], 
     model_name: &str, 
     gamma_correction: bool) -> Result<Model, ModelLoadError> 
+{
+    load_from_memory_with_options(buffer, model_name, &ModelLoadOptions::new(gamma_correction))
+}
+
+pub fn load_from_memory_with_options(
+    buffer: &[u8],
+    model_name: &str,
+    options: &ModelLoadOptions) -> Result<Model, ModelLoadError>
 {
     let reader = io::Cursor::new(buffer);
     let mut zip_archive = zip::ZipArchive::new(reader).map_err(|e| {
@@ -265,6 +689,7 @@ pub fn load_from_memory(
 
         buffer
     };
+    let (obj_file, vertex_colors) = split_vertex_colors(&obj_file);
     let obj_set = obj::parse(&obj_file).map_err(|e| {
         ModelLoadError {
             error: Some(Box::new(e)),
@@ -293,9 +718,13 @@ pub fn load_from_memory(
 
     let mut textures_loaded = vec![];
     let mut meshes = vec![];
+    let mut vertex_offset = 0;
     for object in obj_set.objects.iter() {
         assert!(object.geometry_set.len() == 1);
 
+        let colors = object_vertex_colors(&vertex_colors, vertex_offset, object.vertex_set.len());
+        vertex_offset += object.vertex_set.len();
+
         let material_name = &object.geometry_set[0].material_name
             .as_ref()
             .map(|s| s.as_str())
@@ -311,8 +740,21 @@ pub fn load_from_memory(
         )?;
 
         let mesh_name = object.name.clone();
-        let vertices = load_mesh_vertices(&object);
-        let vertex_indices: Vec<u32> = (0..vertices.len() as u32).collect();
+        let (mut vertices, mut smoothing_groups) = load_mesh_vertices(&object, colors);
+        if let Some(weld_tolerance) = options.weld_tolerance {
+            weld::weld_positions(&mut vertices, weld_tolerance);
+            weld::remove_collapsed_triangles(&mut vertices, &mut smoothing_groups);
+        }
+        let generate_normals = match options.normals {
+            NormalGeneration::Never => false,
+            NormalGeneration::Missing => normals::has_missing_normals(&vertices),
+            NormalGeneration::Always => true,
+        };
+        if generate_normals {
+            normals::generate_normals(&mut vertices, &smoothing_groups);
+        }
+        let (vertices, vertex_indices) = deduplicate_vertices(&vertices);
+        let (line_vertices, point_vertices) = load_mesh_lines_and_points(&object, colors);
         let mut texture_indices = HashMap::new();
         load_texture_map(
             &mut zip_archive, 
@@ -342,18 +784,141 @@ pub fn load_from_memory(
             TextureKind::Bump,
             material.map_bump.as_ref().map(|s| s.as_str()), 
         );
-        load_texture_map(
-            &mut zip_archive, 
-            &mut textures_loaded,
-            &mut texture_indices,
-            TextureKind::Emission,
-            material.map_ambient.as_ref().map(|s| s.as_str()), 
-        );
+        // The material library parser has no emissive map, so emission is left
+        // unset and the renderer binds its black fallback in its place.
 
-        let mesh = Mesh::new(mesh_name, vertices, vertex_indices, texture_indices);
+        let mesh = Mesh::with_primitives(
+            mesh_name, vertices, vertex_indices, line_vertices, point_vertices, texture_indices
+        );
         meshes.push(mesh);
     }
 
-    Ok(Model::new(model_name.to_owned(), meshes, textures_loaded, gamma_correction))
+    let mut model = Model::new(
+        model_name.to_owned(), meshes, textures_loaded, options.gamma_correction
+    );
+    model.vertex_format = options.vertex_format;
+    if options.sanitize {
+        for report in validate::sanitize_model(&mut model).iter() {
+            if !report.is_clean() {
+                info!(
+                    "Sanitized mesh \"{}\" with {} issues.\n{}",
+                    report.mesh_name, report.issue_count(), report
+                );
+            }
+        }
+    }
+    if let Some(subdivision) = options.subdivision {
+        subdivide::subdivide_model(&mut model, &subdivision);
+    }
+    options.transform.apply(&mut model);
+    if let Some(lod_chain) = options.lod_chain {
+        simplify::generate_lod_chain(&mut model, &lod_chain);
+    }
+    if let Some(optimize_options) = options.optimize {
+        for report in optimize::optimize_model(&mut model, &optimize_options).iter() {
+            info!("Optimized vertex cache order. {}", report);
+        }
+    }
+
+    Ok(model)
 }
 
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+
+    #[test]
+    fn test_vertex_attributes_cover_vertex() {
+        let attributes = Vertex::attributes();
+        let mut end = 0;
+        for attribute in attributes.iter() {
+            assert_eq!(attribute.offset, end, "attribute `{}` does not follow the one before it", attribute.name);
+            end = attribute.offset + attribute.components * attribute.attribute_type.size();
+        }
+
+        assert_eq!(end, Vertex::stride());
+    }
+
+    #[test]
+    fn test_split_vertex_colors_rgb() {
+        let source = "v 1 2 3 0.5 0.25 0\nv 4 5 6 1 0 1\nf 1 2 1";
+        let (stripped, colors) = split_vertex_colors(source);
+
+        assert_eq!(stripped, "v 1 2 3\nv 4 5 6\nf 1 2 1");
+        assert_eq!(colors, vec![Vector4::new(0.5, 0.25, 0.0, 1.0), Vector4::new(1.0, 0.0, 1.0, 1.0)]);
+    }
+
+    #[test]
+    fn test_split_vertex_colors_rgba() {
+        let source = "o object\nv 1 2 3 0.5 0.25 0 0.75\n";
+        let (stripped, colors) = split_vertex_colors(source);
+
+        assert_eq!(stripped, "o object\nv 1 2 3\n");
+        assert_eq!(colors, vec![Vector4::new(0.5, 0.25, 0.0, 0.75)]);
+    }
+
+    #[test]
+    fn test_split_vertex_colors_mixed_lines() {
+        let source = "v 1 2 3\nv 4 5 6 0 1 0\nvn 0 0 1\nv 7 8 9 1.0";
+        let (stripped, colors) = split_vertex_colors(source);
+
+        assert_eq!(stripped, "v 1 2 3\nv 4 5 6\nvn 0 0 1\nv 7 8 9 1.0");
+        assert_eq!(colors, vec![
+            Vertex::default_color(),
+            Vector4::new(0.0, 1.0, 0.0, 1.0),
+            Vertex::default_color(),
+        ]);
+    }
+
+    #[test]
+    fn test_split_vertex_colors_plain_lines() {
+        let source = "v 1 2 3\nv 4 5 6 1.0\nvt 0 1\nf 1 2 1";
+        let (stripped, colors) = split_vertex_colors(source);
+
+        assert!(matches!(stripped, Cow::Borrowed(_)));
+        assert_eq!(stripped, source);
+        assert!(colors.is_empty());
+    }
+
+    #[test]
+    fn test_object_vertex_colors_slices_by_vertex_offset() {
+        let vertex_colors: Vec<Vector4<f32>> = (0..5)
+            .map(|i| Vector4::new(i as f32, 0.0, 0.0, 1.0))
+            .collect();
+
+        assert_eq!(object_vertex_colors(&vertex_colors, 0, 2), &vertex_colors[0..2]);
+        assert_eq!(object_vertex_colors(&vertex_colors, 2, 3), &vertex_colors[2..5]);
+    }
+
+    #[test]
+    fn test_object_vertex_colors_past_the_end() {
+        let vertex_colors = vec![Vector4::new(1.0, 0.0, 0.0, 1.0); 3];
+
+        assert_eq!(object_vertex_colors(&vertex_colors, 2, 4), &vertex_colors[2..3]);
+        assert!(object_vertex_colors(&vertex_colors, 5, 2).is_empty());
+        assert!(object_vertex_colors(&[], 0, 4).is_empty());
+    }
+
+    #[test]
+    fn test_vertex_streams() {
+        let vertex = Vertex {
+            position: Vector3::zero(),
+            normal: Vector3::unit_z(),
+            tex_coords: Vector2::zero(),
+            tangent: Vector3::unit_x(),
+            bitangent: Vector3::unit_y(),
+            color: Vertex::default_color(),
+            tex_coords2: Vector2::zero(),
+        };
+        let colored = Vertex { color: Vector4::new(1.0, 0.0, 0.0, 1.0), ..vertex };
+        let mapped = Vertex { tex_coords2: Vector2::new(0.5, 0.5), ..vertex };
+
+        assert_eq!(VertexStreams::of(&[vertex, vertex]), VertexStreams::default());
+        assert_eq!(VertexStreams::of(&[vertex, colored]), VertexStreams { colors: true, tex_coords2: false });
+        assert_eq!(VertexStreams::of(&[mapped, vertex]), VertexStreams { colors: false, tex_coords2: true });
+        assert_eq!(VertexStreams::of(&[]), VertexStreams::default());
+    }
+}
@@ -0,0 +1,238 @@
+use crate::geometry::{
+    vector_key,
+};
+use crate::model;
+use crate::model::{
+    Mesh,
+    Vertex,
+};
+use cglinalg::{
+    Magnitude,
+    Vector3,
+};
+use std::collections::{
+    HashMap,
+};
+
+
+/// The smoothing group of faces that are shaded flat, written `s off` or `s 0` in OBJ files.
+pub const SMOOTHING_GROUP_OFF: u32 = 0;
+
+/// When to compute vertex normals instead of using the normals of the source file.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum NormalGeneration {
+    /// Keep the normals from the source file, even when some are missing.
+    Never,
+    /// Compute the normals of a mesh when any of its vertices lacks one.
+    Missing,
+    /// Always compute the normals, replacing the normals from the source file.
+    Always,
+}
+
+impl Default for NormalGeneration {
+    fn default() -> NormalGeneration {
+        NormalGeneration::Missing
+    }
+}
+
+/// Determine whether any vertex of a triangle soup lacks a normal.
+pub fn has_missing_normals(vertices: &[Vertex]) -> bool {
+    vertices.iter().any(|vertex| !(vertex.normal.magnitude_squared() > 0.0))
+}
+
+/// The interior angle of a triangle at one of its corners.
+fn corner_angle(positions: &[Vector3<f32>; 3], corner: usize) -> f32 {
+    let edge1 = positions[(corner + 1) % 3] - positions[corner];
+    let edge2 = positions[(corner + 2) % 3] - positions[corner];
+    let denominator = f32::sqrt(edge1.magnitude_squared() * edge2.magnitude_squared());
+    if denominator > 0.0 {
+        f32::acos(f32::max(-1.0, f32::min(1.0, edge1.dot(&edge2) / denominator)))
+    } else {
+        0.0
+    }
+}
+
+/// Compute the vertex normals of a triangle soup, where each consecutive three vertices
+/// form a triangle and `smoothing_groups` holds the smoothing group of each triangle.
+/// Triangles in the same smoothing group share the angle weighted average normal at
+/// each position they meet at, while triangles in different groups, or with smoothing
+/// turned off, keep separate normals. Corners on either side of an edge between
+/// smoothing groups therefore get different normals, making the edge hard.
+pub fn generate_normals(vertices: &mut [Vertex], smoothing_groups: &[u32]) {
+    let triangle_count = vertices.len() / 3;
+    debug_assert!(smoothing_groups.len() >= triangle_count);
+
+    let corner_key = |triangle: usize, position: &Vector3<f32>| {
+        let group = smoothing_groups.get(triangle).copied().unwrap_or(SMOOTHING_GROUP_OFF);
+        // Faces with smoothing turned off never share a normal with another face.
+        let face = if group == SMOOTHING_GROUP_OFF { triangle } else { usize::MAX };
+
+        (vector_key(position), group, face)
+    };
+
+    let mut face_normals = Vec::with_capacity(triangle_count);
+    let mut accumulated = HashMap::new();
+    for triangle in 0..triangle_count {
+        let p0 = vertices[3 * triangle].position;
+        let p1 = vertices[3 * triangle + 1].position;
+        let p2 = vertices[3 * triangle + 2].position;
+        let face_normal = (p1 - p0).cross(&(p2 - p0));
+        face_normals.push(face_normal);
+        if !(face_normal.magnitude_squared() > 0.0) {
+            continue;
+        }
+        let unit_normal = face_normal.normalize();
+        let positions = [p0, p1, p2];
+        for corner in 0..3 {
+            let key = corner_key(triangle, &positions[corner]);
+            let normal = accumulated.entry(key).or_insert(Vector3::zero());
+            *normal += unit_normal * corner_angle(&positions, corner);
+        }
+    }
+
+    for triangle in 0..triangle_count {
+        for corner in 0..3 {
+            let vertex = &mut vertices[3 * triangle + corner];
+            let normal = accumulated.get(&corner_key(triangle, &vertex.position))
+                .copied()
+                .unwrap_or(Vector3::zero());
+            vertex.normal = if normal.magnitude_squared() > 0.0 {
+                normal.normalize()
+            } else if face_normals[triangle].magnitude_squared() > 0.0 {
+                face_normals[triangle].normalize()
+            } else {
+                Vector3::new(0.0, 1.0, 0.0)
+            };
+        }
+    }
+}
+
+/// Recompute the vertex normals of a mesh, splitting vertices wherever triangles in
+/// different smoothing groups meet. When `smoothing_groups` is `None` the whole mesh
+/// is one smoothing group.
+pub fn generate_mesh_normals(mesh: &mut Mesh, smoothing_groups: Option<&[u32]>) {
+    let vertex_count = mesh.vertices.len();
+    let mut corners = Vec::with_capacity(mesh.vertex_indices.len());
+    let mut groups = Vec::with_capacity(mesh.vertex_indices.len() / 3);
+    for (triangle, indices) in mesh.vertex_indices.chunks_exact(3).enumerate() {
+        if indices.iter().any(|&index| index as usize >= vertex_count) {
+            continue;
+        }
+        for &index in indices.iter() {
+            corners.push(mesh.vertices[index as usize]);
+        }
+        groups.push(smoothing_groups.map_or(1, |groups| groups[triangle]));
+    }
+
+    generate_normals(&mut corners, &groups);
+    let (vertices, vertex_indices) = model::deduplicate_vertices(&corners);
+    mesh.vertices = vertices;
+    mesh.vertex_indices = vertex_indices;
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cglinalg::{
+        Vector2,
+    };
+    use std::collections::{
+        HashMap,
+    };
+
+
+    fn vertex(x: f32, y: f32, z: f32) -> Vertex {
+        Vertex {
+            position: Vector3::new(x, y, z),
+            normal: Vector3::zero(),
+            tex_coords: Vector2::zero(),
+            tangent: Vector3::zero(),
+            bitangent: Vector3::zero(),
+            color: Vertex::default_color(),
+            tex_coords2: Vector2::zero(),
+        }
+    }
+
+    /// Two triangles folded at a right angle along the edge from the origin to the
+    /// unit x point. The first faces +z and the second faces +y.
+    fn fold() -> Vec<Vertex> {
+        vec![
+            vertex(0.0, 0.0, 0.0), vertex(1.0, 0.0, 0.0), vertex(0.0, 1.0, 0.0),
+            vertex(0.0, 0.0, 0.0), vertex(0.0, 0.0, 1.0), vertex(1.0, 0.0, 0.0),
+        ]
+    }
+
+    fn assert_normal(result: Vector3<f32>, expected: Vector3<f32>) {
+        assert!((result - expected).magnitude() <= 1e-6, "expected normal {:?}, got {:?}", expected, result);
+    }
+
+    fn assert_face_normals(vertices: &[Vertex]) {
+        for corner in 0..3 {
+            assert_normal(vertices[corner].normal, Vector3::unit_z());
+            assert_normal(vertices[3 + corner].normal, Vector3::unit_y());
+        }
+    }
+
+    #[test]
+    fn test_smooth_edge_in_one_group() {
+        let mut vertices = fold();
+        generate_normals(&mut vertices, &[1, 1]);
+        let smooth = Vector3::new(0.0, 1.0, 1.0).normalize();
+
+        // Both triangles meet the shared corners at the same angle, so the faces
+        // contribute equally there.
+        for &corner in [0, 1, 3, 5].iter() {
+            assert_normal(vertices[corner].normal, smooth);
+        }
+        assert_normal(vertices[2].normal, Vector3::unit_z());
+        assert_normal(vertices[4].normal, Vector3::unit_y());
+    }
+
+    #[test]
+    fn test_hard_edge_between_groups() {
+        let mut vertices = fold();
+        generate_normals(&mut vertices, &[1, 2]);
+
+        assert_face_normals(&vertices);
+    }
+
+    #[test]
+    fn test_hard_edge_with_smoothing_off() {
+        let mut vertices = fold();
+        generate_normals(&mut vertices, &[SMOOTHING_GROUP_OFF, SMOOTHING_GROUP_OFF]);
+
+        assert_face_normals(&vertices);
+    }
+
+    #[test]
+    fn test_hard_edge_with_smoothing_off_on_one_side() {
+        let mut vertices = fold();
+        generate_normals(&mut vertices, &[1, SMOOTHING_GROUP_OFF]);
+
+        assert_face_normals(&vertices);
+    }
+
+    #[test]
+    fn test_mesh_normals_split_vertices_along_hard_edges() {
+        let vertices = vec![vertex(0.0, 0.0, 0.0), vertex(1.0, 0.0, 0.0), vertex(0.0, 1.0, 0.0), vertex(0.0, 0.0, 1.0)];
+        let vertex_indices = vec![0, 1, 2, 0, 3, 1];
+        let mut smooth_mesh = Mesh::new(String::from("smooth"), vertices.clone(), vertex_indices.clone(), HashMap::new());
+        let mut hard_mesh = Mesh::new(String::from("hard"), vertices, vertex_indices, HashMap::new());
+        generate_mesh_normals(&mut smooth_mesh, None);
+        generate_mesh_normals(&mut hard_mesh, Some(&[SMOOTHING_GROUP_OFF, SMOOTHING_GROUP_OFF]));
+
+        assert_eq!(smooth_mesh.vertices.len(), 4);
+        assert_eq!(hard_mesh.vertices.len(), 6);
+        assert_eq!(hard_mesh.vertex_indices.len(), 6);
+    }
+
+    #[test]
+    fn test_has_missing_normals() {
+        let mut vertices = fold();
+        assert!(has_missing_normals(&vertices));
+
+        generate_normals(&mut vertices, &[1, 1]);
+        assert!(!has_missing_normals(&vertices));
+    }
+}
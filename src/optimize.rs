@@ -0,0 +1,527 @@
+use crate::model::{
+    Mesh,
+    Model,
+};
+use cglinalg::{
+    Magnitude,
+    Vector3,
+};
+use std::cmp::{
+    Ordering,
+};
+use std::collections::{
+    VecDeque,
+};
+use std::fmt;
+
+
+/// Statistics describing how well an index buffer uses a FIFO post-transform
+/// vertex cache.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct CacheStatistics {
+    /// The number of vertices transformed, i.e. the number of cache misses.
+    pub vertices_transformed: usize,
+    /// The average cache miss ratio: vertices transformed per triangle. Ranges from
+    /// about 0.5 for an ideal ordering to 3.0 for the worst ordering.
+    pub acmr: f32,
+    /// The average transform to vertex ratio: vertices transformed per unique
+    /// vertex. An ideal ordering scores 1.0.
+    pub atvr: f32,
+}
+
+impl fmt::Display for CacheStatistics {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ACMR = {:.3}, ATVR = {:.3}", self.acmr, self.atvr)
+    }
+}
+
+/// Simulate a FIFO post-transform vertex cache of size `cache_size` running over
+/// an index buffer.
+pub fn analyze_vertex_cache(vertex_indices: &[u32], vertex_count: usize, cache_size: usize) -> CacheStatistics {
+    let mut cache = VecDeque::with_capacity(cache_size);
+    let mut used = vec![false; vertex_count];
+    let mut unique_vertices = 0;
+    let mut vertices_transformed = 0;
+    for &index in vertex_indices.iter() {
+        if (index as usize) < vertex_count && !used[index as usize] {
+            used[index as usize] = true;
+            unique_vertices += 1;
+        }
+        if !cache.contains(&index) {
+            vertices_transformed += 1;
+            if cache.len() == cache_size {
+                cache.pop_front();
+            }
+            cache.push_back(index);
+        }
+    }
+
+    let triangle_count = vertex_indices.len() / 3;
+    let acmr = if triangle_count > 0 {
+        vertices_transformed as f32 / triangle_count as f32
+    } else {
+        0.0
+    };
+    let atvr = if unique_vertices > 0 {
+        vertices_transformed as f32 / unique_vertices as f32
+    } else {
+        0.0
+    };
+
+    CacheStatistics {
+        vertices_transformed: vertices_transformed,
+        acmr: acmr,
+        atvr: atvr,
+    }
+}
+
+/// The parameters of the index buffer optimizer.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct OptimizeOptions {
+    /// The size of the post-transform vertex cache to optimize for.
+    pub cache_size: usize,
+    /// Reorder clusters of triangles to reduce overdraw after optimizing for the
+    /// vertex cache.
+    pub optimize_overdraw: bool,
+    /// Reorder the vertex buffer into the order the index buffer first uses each vertex.
+    pub optimize_vertex_fetch: bool,
+}
+
+impl OptimizeOptions {
+    pub fn new() -> OptimizeOptions {
+        OptimizeOptions {
+            cache_size: 16,
+            optimize_overdraw: true,
+            optimize_vertex_fetch: true,
+        }
+    }
+}
+
+impl Default for OptimizeOptions {
+    fn default() -> OptimizeOptions {
+        OptimizeOptions::new()
+    }
+}
+
+/// The vertex cache statistics of a mesh before and after optimization.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OptimizeReport {
+    pub mesh_name: String,
+    pub before: CacheStatistics,
+    pub after: CacheStatistics,
+}
+
+impl fmt::Display for OptimizeReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Mesh \"{}\": before {}; after {}", self.mesh_name, self.before, self.after)
+    }
+}
+
+/// The output of the vertex cache optimizer: a reordered index buffer together with
+/// the triangle offsets where the optimizer had to jump to a disconnected part of
+/// the mesh. Those offsets are the cluster boundaries used for overdraw ordering.
+struct TipsifyOutput {
+    vertex_indices: Vec<u32>,
+    cluster_starts: Vec<usize>,
+}
+
+/// Reorder triangles for vertex cache locality with the Tipsify algorithm from
+/// Sander, Nehab and Barczak, "Fast Triangle Reordering for Vertex Locality and
+/// Reduced Overdraw", SIGGRAPH 2007.
+fn tipsify(vertex_indices: &[u32], vertex_count: usize, cache_size: usize) -> TipsifyOutput {
+    let triangle_count = vertex_indices.len() / 3;
+    let mut adjacency = vec![vec![]; vertex_count];
+    let mut live_triangles = vec![0_usize; vertex_count];
+    for (triangle, indices) in vertex_indices.chunks_exact(3).enumerate() {
+        for &index in indices.iter() {
+            adjacency[index as usize].push(triangle);
+            live_triangles[index as usize] += 1;
+        }
+    }
+
+    let cache_size = cache_size as isize;
+    let mut timestamps = vec![0_isize; vertex_count];
+    let mut dead_end_stack = vec![];
+    let mut emitted = vec![false; triangle_count];
+    let mut output = Vec::with_capacity(vertex_indices.len());
+    let mut cluster_starts = vec![];
+    let mut time = cache_size + 1;
+    let mut cursor = 0;
+
+    let mut fanning_vertex = (0..vertex_count).find(|&vertex| live_triangles[vertex] > 0);
+    let mut hard_boundary = true;
+    while let Some(vertex) = fanning_vertex {
+        if hard_boundary {
+            cluster_starts.push(output.len() / 3);
+        }
+
+        let mut candidates = vec![];
+        for &triangle in adjacency[vertex].iter() {
+            if emitted[triangle] {
+                continue;
+            }
+            for &index in vertex_indices[3 * triangle..3 * triangle + 3].iter() {
+                output.push(index);
+                dead_end_stack.push(index);
+                candidates.push(index);
+                live_triangles[index as usize] -= 1;
+                if time - timestamps[index as usize] > cache_size {
+                    timestamps[index as usize] = time;
+                    time += 1;
+                }
+            }
+            emitted[triangle] = true;
+        }
+
+        // Choose the candidate with live triangles that has been in the cache the
+        // longest without being evicted by its own remaining triangles.
+        let mut best = None;
+        let mut best_priority = -1;
+        for &candidate in candidates.iter() {
+            let candidate = candidate as usize;
+            if live_triangles[candidate] == 0 {
+                continue;
+            }
+            let mut priority = 0;
+            if time - timestamps[candidate] + 2 * live_triangles[candidate] as isize <= cache_size {
+                priority = time - timestamps[candidate];
+            }
+            if priority > best_priority {
+                best_priority = priority;
+                best = Some(candidate);
+            }
+        }
+
+        hard_boundary = best.is_none();
+        fanning_vertex = match best {
+            Some(candidate) => Some(candidate),
+            None => skip_dead_end(&live_triangles, &mut dead_end_stack, &mut cursor),
+        };
+    }
+
+    TipsifyOutput {
+        vertex_indices: output,
+        cluster_starts: cluster_starts,
+    }
+}
+
+/// Find the next vertex to fan around once the current neighborhood is exhausted:
+/// first a recently used vertex from the dead end stack, then the next vertex in
+/// input order that still has live triangles.
+fn skip_dead_end(live_triangles: &[usize], dead_end_stack: &mut Vec<u32>, cursor: &mut usize) -> Option<usize> {
+    while let Some(vertex) = dead_end_stack.pop() {
+        if live_triangles[vertex as usize] > 0 {
+            return Some(vertex as usize);
+        }
+    }
+    while *cursor < live_triangles.len() {
+        if live_triangles[*cursor] > 0 {
+            return Some(*cursor);
+        }
+        *cursor += 1;
+    }
+
+    None
+}
+
+/// Reorder the clusters produced by the vertex cache optimizer so that clusters
+/// facing outward from the center of the mesh are drawn first. Surfaces drawn
+/// early then tend to occlude the surfaces drawn later, reducing overdraw without
+/// breaking up the cache-friendly order inside each cluster.
+fn optimize_overdraw(mesh: &Mesh, vertex_indices: &[u32], cluster_starts: &[usize]) -> Vec<u32> {
+    let triangle_count = vertex_indices.len() / 3;
+    let mut mesh_centroid = Vector3::zero();
+    let mut mesh_area = 0.0;
+    let mut clusters = Vec::with_capacity(cluster_starts.len());
+    for (i, &start) in cluster_starts.iter().enumerate() {
+        let end = if i + 1 < cluster_starts.len() { cluster_starts[i + 1] } else { triangle_count };
+        let mut centroid = Vector3::zero();
+        let mut normal = Vector3::zero();
+        let mut area = 0.0;
+        for triangle in start..end {
+            let p0 = mesh.vertices[vertex_indices[3 * triangle] as usize].position;
+            let p1 = mesh.vertices[vertex_indices[3 * triangle + 1] as usize].position;
+            let p2 = mesh.vertices[vertex_indices[3 * triangle + 2] as usize].position;
+            let face_normal = (p1 - p0).cross(&(p2 - p0));
+            let face_area = 0.5 * face_normal.magnitude();
+            centroid += ((p0 + p1 + p2) / 3.0) * face_area;
+            normal += face_normal;
+            area += face_area;
+        }
+        mesh_centroid += centroid;
+        mesh_area += area;
+        if area > 0.0 {
+            centroid /= area;
+        }
+        clusters.push((start, end, centroid, normal));
+    }
+    if mesh_area > 0.0 {
+        mesh_centroid /= mesh_area;
+    }
+
+    let mut sorted: Vec<(f32, usize, usize)> = clusters.iter()
+        .map(|(start, end, centroid, normal)| {
+            let score = if normal.magnitude_squared() > 0.0 {
+                (centroid - mesh_centroid).dot(&normal.normalize())
+            } else {
+                0.0
+            };
+            (score, *start, *end)
+        })
+        .collect();
+    sorted.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(Ordering::Equal));
+
+    let mut output = Vec::with_capacity(vertex_indices.len());
+    for (_, start, end) in sorted.iter() {
+        output.extend_from_slice(&vertex_indices[3 * start..3 * end]);
+    }
+
+    output
+}
+
+/// Check that every index of an index buffer refers to a vertex.
+#[inline]
+fn indices_in_range(vertex_indices: &[u32], vertex_count: usize) -> bool {
+    vertex_indices.iter().all(|&index| (index as usize) < vertex_count)
+}
+
+/// Reorder the vertex buffer of a mesh into the order the index buffer first refers
+/// to each vertex, so vertex fetches walk memory sequentially. Unused vertices are
+/// dropped and the bounds recomputed. A mesh with out of range indices is left as is.
+pub fn optimize_vertex_fetch(mesh: &mut Mesh) {
+    if !indices_in_range(&mesh.vertex_indices, mesh.vertices.len()) {
+        return;
+    }
+
+    let mut remap = vec![u32::MAX; mesh.vertices.len()];
+    let mut vertices = Vec::with_capacity(mesh.vertices.len());
+    for index in mesh.vertex_indices.iter_mut() {
+        let old_index = *index as usize;
+        if remap[old_index] == u32::MAX {
+            remap[old_index] = vertices.len() as u32;
+            vertices.push(mesh.vertices[old_index]);
+        }
+        *index = remap[old_index];
+    }
+
+    mesh.vertices = vertices;
+    mesh.update_bounds();
+}
+
+/// Optimize the index buffer of a mesh for the post-transform vertex cache, then
+/// for overdraw and vertex fetch as selected in `options`. The mesh must be indexed
+/// for the vertex cache to have any vertices to reuse.
+pub fn optimize_mesh(mesh: &mut Mesh, options: &OptimizeOptions) -> OptimizeReport {
+    let vertex_count = mesh.vertices.len();
+    let before = analyze_vertex_cache(&mesh.vertex_indices, vertex_count, options.cache_size);
+    let valid = mesh.vertex_indices.len() % 3 == 0 &&
+        indices_in_range(&mesh.vertex_indices, vertex_count);
+    if !valid || mesh.vertex_indices.is_empty() {
+        return OptimizeReport {
+            mesh_name: mesh.name.clone(),
+            before: before,
+            after: before,
+        };
+    }
+
+    let tipsified = tipsify(&mesh.vertex_indices, vertex_count, options.cache_size);
+    mesh.vertex_indices = if options.optimize_overdraw {
+        optimize_overdraw(mesh, &tipsified.vertex_indices, &tipsified.cluster_starts)
+    } else {
+        tipsified.vertex_indices
+    };
+    if options.optimize_vertex_fetch {
+        optimize_vertex_fetch(mesh);
+    }
+    let after = analyze_vertex_cache(&mesh.vertex_indices, mesh.vertices.len(), options.cache_size);
+
+    OptimizeReport {
+        mesh_name: mesh.name.clone(),
+        before: before,
+        after: after,
+    }
+}
+
+/// Optimize every mesh of a model, including the meshes of its levels of detail.
+pub fn optimize_model(model: &mut Model, options: &OptimizeOptions) -> Vec<OptimizeReport> {
+    let mut reports = vec![];
+    for mesh in model.meshes.iter_mut() {
+        reports.push(optimize_mesh(mesh, options));
+    }
+    for lod in model.lods.iter_mut() {
+        for mesh in lod.meshes.iter_mut() {
+            reports.push(optimize_mesh(mesh, options));
+        }
+    }
+
+    reports
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bounds::{
+        Aabb,
+    };
+    use crate::primitives;
+
+
+    /// Shuffle the triangles of an index buffer with a fixed seed, keeping the
+    /// corners of each triangle in order.
+    fn shuffle_triangles(vertex_indices: &[u32]) -> Vec<u32> {
+        let mut triangles: Vec<&[u32]> = vertex_indices.chunks_exact(3).collect();
+        let mut state: u32 = 0x2545F491;
+        for i in (1..triangles.len()).rev() {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            triangles.swap(i, state as usize % (i + 1));
+        }
+
+        triangles.concat()
+    }
+
+    /// A grid of 16 by 16 quads with its triangles in random order.
+    fn shuffled_grid() -> Mesh {
+        let mut mesh = primitives::plane(1.0, 1.0, 16, 16);
+        mesh.vertex_indices = shuffle_triangles(&mesh.vertex_indices);
+
+        mesh
+    }
+
+    /// The triangles of a mesh by the bit patterns of their corner positions, each
+    /// one rotated to start at its smallest corner so the winding is preserved.
+    fn triangle_set(mesh: &Mesh) -> Vec<[[u32; 3]; 3]> {
+        let mut triangles: Vec<[[u32; 3]; 3]> = mesh.vertex_indices.chunks_exact(3)
+            .map(|triangle| {
+                let corners: Vec<[u32; 3]> = triangle.iter()
+                    .map(|&index| {
+                        let position = mesh.vertices[index as usize].position;
+                        [position.x.to_bits(), position.y.to_bits(), position.z.to_bits()]
+                    })
+                    .collect();
+                let first = (0..3).min_by_key(|&i| corners[i]).unwrap();
+                [corners[first], corners[(first + 1) % 3], corners[(first + 2) % 3]]
+            })
+            .collect();
+        triangles.sort();
+
+        triangles
+    }
+
+    #[test]
+    fn test_analyze_vertex_cache_quad() {
+        let statistics = analyze_vertex_cache(&[0, 1, 2, 2, 1, 3], 4, 16);
+
+        assert_eq!(statistics.vertices_transformed, 4);
+        assert_eq!(statistics.acmr, 2.0);
+        assert_eq!(statistics.atvr, 1.0);
+    }
+
+    #[test]
+    fn test_analyze_vertex_cache_eviction() {
+        // With a cache of three vertices, vertex 0 is evicted before the last triangle.
+        let statistics = analyze_vertex_cache(&[0, 1, 2, 1, 2, 3, 0, 2, 3], 4, 3);
+
+        assert_eq!(statistics.vertices_transformed, 5);
+        assert_eq!(statistics.acmr, 5.0 / 3.0);
+        assert_eq!(statistics.atvr, 5.0 / 4.0);
+    }
+
+    #[test]
+    fn test_optimize_mesh_improves_grid() {
+        let mut mesh = shuffled_grid();
+        let report = optimize_mesh(&mut mesh, &OptimizeOptions::new());
+
+        assert_eq!(report.before, analyze_vertex_cache(&shuffled_grid().vertex_indices, 17 * 17, 16));
+        assert!(report.before.acmr > 1.5, "before: {}", report.before);
+        assert!(report.after.acmr < 0.9, "after: {}", report.after);
+        assert!(report.after.atvr < 1.7, "after: {}", report.after);
+        assert!(report.after.acmr < report.before.acmr);
+        assert!(report.after.atvr >= 1.0);
+    }
+
+    #[test]
+    fn test_optimize_mesh_keeps_triangles_and_winding() {
+        let expected = triangle_set(&shuffled_grid());
+        for &optimize_overdraw in [false, true].iter() {
+            let mut mesh = shuffled_grid();
+            let mut options = OptimizeOptions::new();
+            options.optimize_overdraw = optimize_overdraw;
+            optimize_mesh(&mut mesh, &options);
+
+            assert_eq!(triangle_set(&mesh), expected);
+        }
+    }
+
+    #[test]
+    fn test_optimize_mesh_sphere_keeps_triangles() {
+        let mut mesh = primitives::uv_sphere(1.0, 24, 12);
+        mesh.vertex_indices = shuffle_triangles(&mesh.vertex_indices);
+        let expected = triangle_set(&mesh);
+        optimize_mesh(&mut mesh, &OptimizeOptions::new());
+
+        assert_eq!(triangle_set(&mesh), expected);
+    }
+
+    #[test]
+    fn test_optimize_vertex_fetch_keeps_geometry() {
+        let mut mesh = shuffled_grid();
+        let expected = triangle_set(&mesh);
+        optimize_vertex_fetch(&mut mesh);
+
+        assert_eq!(triangle_set(&mesh), expected);
+        assert_eq!(mesh.vertices.len(), 17 * 17);
+    }
+
+    #[test]
+    fn test_optimize_vertex_fetch_orders_by_first_use() {
+        let mut mesh = shuffled_grid();
+        optimize_vertex_fetch(&mut mesh);
+        let mut next_vertex = 0;
+        for &index in mesh.vertex_indices.iter() {
+            assert!(index <= next_vertex);
+            if index == next_vertex {
+                next_vertex += 1;
+            }
+        }
+
+        assert_eq!(next_vertex as usize, mesh.vertices.len());
+    }
+
+    #[test]
+    fn test_optimize_vertex_fetch_drops_unused_vertices() {
+        let mut mesh = primitives::plane(1.0, 1.0, 2, 2);
+        mesh.vertex_indices.truncate(6);
+        optimize_vertex_fetch(&mut mesh);
+
+        assert_eq!(mesh.vertices.len(), 4);
+        assert!(mesh.vertex_indices.iter().all(|&index| index < 4));
+    }
+
+    #[test]
+    fn test_optimize_vertex_fetch_updates_bounds() {
+        let mut mesh = primitives::plane(1.0, 1.0, 2, 2);
+        mesh.vertex_indices.truncate(6);
+        let full = mesh.aabb;
+        let expected = Aabb::from_points(mesh.vertex_indices.iter().map(|&index| &mesh.vertices[index as usize].position));
+        optimize_vertex_fetch(&mut mesh);
+
+        assert_eq!(mesh.aabb, expected);
+        assert_ne!(mesh.aabb, full);
+    }
+
+    #[test]
+    fn test_optimize_vertex_fetch_ignores_out_of_range_indices() {
+        let mut mesh = primitives::plane(1.0, 1.0, 2, 2);
+        let vertex_count = mesh.vertices.len() as u32;
+        mesh.vertex_indices.truncate(6);
+        mesh.vertex_indices[4] = vertex_count;
+        let expected = mesh.clone();
+        optimize_vertex_fetch(&mut mesh);
+
+        assert_eq!(mesh.vertices, expected.vertices);
+        assert_eq!(mesh.vertex_indices, expected.vertex_indices);
+    }
+}
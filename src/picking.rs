@@ -0,0 +1,237 @@
+use crate::bvh::{
+    Bvh,
+    Ray,
+};
+use crate::model::{
+    Model,
+};
+use cglinalg::{
+    Magnitude,
+    Matrix4,
+    Vector3,
+    Vector4,
+};
+
+
+/// The result of picking a model with a ray.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PickHit {
+    pub mesh_index: usize,
+    pub mesh_name: String,
+    pub triangle_index: usize,
+    /// The ray parameter of the hit point. This is the distance from the ray origin
+    /// when the ray direction has unit length, as rays from `cursor_ray` do.
+    pub distance: f32,
+    pub position: Vector3<f32>,
+    /// The interpolated vertex normal at the hit point.
+    pub normal: Vector3<f32>,
+}
+
+/// Transform a point in normalized device coordinates back into world space.
+#[inline]
+fn unproject_point(inverse_view_projection: &Matrix4<f32>, x: f32, y: f32, z: f32) -> Option<Vector3<f32>> {
+    let point = inverse_view_projection * Vector4::new(x, y, z, 1.0);
+    if point.w == 0.0 {
+        return None;
+    }
+
+    Some(Vector3::new(point.x / point.w, point.y / point.w, point.z / point.w))
+}
+
+/// Construct the ray from the camera through a cursor position. The cursor position
+/// is in screen coordinates with the origin at the top left corner of the window, as
+/// reported by GLFW. The ray starts on the near plane and has a unit length direction.
+pub fn cursor_ray(
+    cursor_x: f64,
+    cursor_y: f64,
+    window_width: u32,
+    window_height: u32,
+    projection: &Matrix4<f32>,
+    view: &Matrix4<f32>) -> Option<Ray>
+{
+    if window_width == 0 || window_height == 0 {
+        return None;
+    }
+
+    let ndc_x = (2.0 * cursor_x / window_width as f64 - 1.0) as f32;
+    let ndc_y = (1.0 - 2.0 * cursor_y / window_height as f64) as f32;
+    let inverse_view_projection = (projection * view).inverse()?;
+    let near = unproject_point(&inverse_view_projection, ndc_x, ndc_y, -1.0)?;
+    let far = unproject_point(&inverse_view_projection, ndc_x, ndc_y, 1.0)?;
+    let direction = far - near;
+    if direction.magnitude_squared() == 0.0 {
+        return None;
+    }
+
+    Some(Ray::new(near, direction.normalize()))
+}
+
+/// Find the mesh of a model closest along a ray in world space. The hierarchy must
+/// have been built from the full level of detail of the model, in model space, and
+/// the model matrix places the model in the world. Returns `None` when the model
+/// matrix is not invertible.
+pub fn pick(model: &Model, bvh: &Bvh, model_mat: &Matrix4<f32>, ray: &Ray) -> Option<PickHit> {
+    // Intersect in model space. The direction is transformed without normalizing, so
+    // the ray parameter of a hit is the same in both spaces.
+    let inverse_model_mat = model_mat.inverse()?;
+    let model_ray = Ray::new(
+        (inverse_model_mat * ray.origin.extend(1.0)).contract(),
+        (inverse_model_mat * ray.direction.extend(0.0)).contract()
+    );
+    let hit = bvh.closest_hit(&model_ray, f32::INFINITY)?;
+    let mesh = &model.meshes[hit.mesh_index];
+    let first = 3 * hit.triangle_index;
+    let v0 = &mesh.vertices[mesh.vertex_indices[first] as usize];
+    let v1 = &mesh.vertices[mesh.vertex_indices[first + 1] as usize];
+    let v2 = &mesh.vertices[mesh.vertex_indices[first + 2] as usize];
+    let weights = hit.barycentrics;
+    let normal = v0.normal * weights.x + v1.normal * weights.y + v2.normal * weights.z;
+    let face_normal = (v1.position - v0.position).cross(&(v2.position - v0.position));
+    let normal = if normal.magnitude_squared() > 0.0 {
+        normal
+    } else if face_normal.magnitude_squared() > 0.0 {
+        // Fall back to the face normal for meshes without vertex normals.
+        face_normal
+    } else {
+        // A degenerate triangle has no face normal, so face the ray instead.
+        -model_ray.direction
+    };
+    // Normals transform by the inverse transpose of the model matrix.
+    let normal = (inverse_model_mat.transpose() * normal.extend(0.0)).contract();
+
+    Some(PickHit {
+        mesh_index: hit.mesh_index,
+        mesh_name: mesh.name.clone(),
+        triangle_index: hit.triangle_index,
+        distance: hit.distance,
+        position: ray.at(hit.distance),
+        normal: normal.normalize(),
+    })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives;
+    use crate::transform;
+
+
+    fn assert_vector_near(result: &Vector3<f32>, expected: &Vector3<f32>) {
+        assert!((result - expected).magnitude() < 1e-4, "{:?} != {:?}", result, expected);
+    }
+
+    /// An OpenGL perspective projection with a vertical field of view of a quarter turn.
+    fn projection(aspect: f32) -> Matrix4<f32> {
+        let (near, far) = (0.1, 100.0);
+        let mut matrix = Matrix4::zero();
+        matrix[0][0] = 1.0 / aspect;
+        matrix[1][1] = 1.0;
+        matrix[2][2] = (near + far) / (near - far);
+        matrix[2][3] = -1.0;
+        matrix[3][2] = 2.0 * near * far / (near - far);
+
+        matrix
+    }
+
+    /// The view matrix of a camera at (1, 2, 3) looking down the negative x axis.
+    fn view() -> Matrix4<f32> {
+        let mut camera_to_world = Matrix4::zero();
+        camera_to_world[0][2] = -1.0;
+        camera_to_world[1][1] = 1.0;
+        camera_to_world[2][0] = 1.0;
+        camera_to_world[3][0] = 1.0;
+        camera_to_world[3][1] = 2.0;
+        camera_to_world[3][2] = 3.0;
+        camera_to_world[3][3] = 1.0;
+
+        camera_to_world.inverse().unwrap()
+    }
+
+    /// Scale by two along x, then rotate a quarter turn about y, taking (x, y, z) to
+    /// (z, y, -x), then translate five units along x. The unit cube lands on
+    /// `[4.5, 5.5] x [-0.5, 0.5] x [-1, 1]`.
+    fn model_mat() -> Matrix4<f32> {
+        let mut matrix = Matrix4::zero();
+        matrix[0][2] = -2.0;
+        matrix[1][1] = 1.0;
+        matrix[2][0] = 1.0;
+        matrix[3][0] = 5.0;
+        matrix[3][3] = 1.0;
+
+        matrix
+    }
+
+    /// A sphere that lands ten units behind the cube along the world x axis, and the
+    /// unit cube.
+    fn model() -> Model {
+        let mut sphere = primitives::uv_sphere(1.0, 16, 8);
+        transform::transform_mesh(&mut sphere, &Matrix4::from_affine_translation(&Vector3::new(0.0, 0.0, 10.0)));
+
+        Model::new(String::from("picking"), vec![sphere, primitives::cube(1.0)], vec![], false)
+    }
+
+    #[test]
+    fn test_cursor_ray_through_center_follows_forward() {
+        let ray = cursor_ray(400.0, 300.0, 800, 600, &projection(800.0 / 600.0), &view()).unwrap();
+
+        assert_vector_near(&ray.direction, &Vector3::new(-1.0, 0.0, 0.0));
+        assert_vector_near(&ray.origin, &Vector3::new(0.9, 2.0, 3.0));
+    }
+
+    #[test]
+    fn test_cursor_ray_through_top_left_corner() {
+        let ray = cursor_ray(0.0, 0.0, 800, 600, &projection(800.0 / 600.0), &view()).unwrap();
+
+        assert_vector_near(&ray.direction, &Vector3::new(-1.0, 1.0, 4.0 / 3.0).normalize());
+        assert!((ray.direction.magnitude() - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_cursor_ray_empty_window() {
+        assert!(cursor_ray(0.0, 0.0, 0, 600, &projection(1.0), &view()).is_none());
+        assert!(cursor_ray(0.0, 0.0, 800, 0, &projection(1.0), &view()).is_none());
+    }
+
+    #[test]
+    fn test_pick_transformed_cube() {
+        let model = model();
+        let bvh = Bvh::new(&model);
+        let rays = [
+            (Ray::new(Vector3::new(0.0, 0.1, 0.2), Vector3::unit_x()), 4.5, Vector3::new(4.5, 0.1, 0.2), -Vector3::unit_x()),
+            (Ray::new(Vector3::new(5.2, 10.0, 0.3), -Vector3::unit_y()), 9.5, Vector3::new(5.2, 0.5, 0.3), Vector3::unit_y()),
+            (Ray::new(Vector3::new(5.1, 0.2, 10.0), -Vector3::unit_z()), 9.0, Vector3::new(5.1, 0.2, 1.0), Vector3::unit_z()),
+        ];
+        for (ray, distance, position, normal) in rays.iter() {
+            let hit = pick(&model, &bvh, &model_mat(), ray).unwrap();
+
+            assert_eq!(hit.mesh_index, 1);
+            assert_eq!(hit.mesh_name, "cube");
+            assert!((hit.distance - distance).abs() < 1e-4, "{} != {}", hit.distance, distance);
+            assert_vector_near(&hit.position, position);
+            assert_vector_near(&hit.normal, normal);
+        }
+    }
+
+    #[test]
+    fn test_pick_behind_cube() {
+        let model = model();
+        let bvh = Bvh::new(&model);
+        let ray = Ray::new(Vector3::new(30.0, 0.1, 0.1), -Vector3::unit_x());
+        let hit = pick(&model, &bvh, &model_mat(), &ray).unwrap();
+
+        assert_eq!(hit.mesh_index, 0);
+        assert!((hit.distance - 14.0).abs() < 0.05);
+        assert!(hit.normal.x > 0.95);
+    }
+
+    #[test]
+    fn test_pick_miss() {
+        let model = model();
+        let bvh = Bvh::new(&model);
+        let ray = Ray::new(Vector3::new(0.0, 5.0, 0.0), Vector3::unit_x());
+
+        assert!(pick(&model, &bvh, &model_mat(), &ray).is_none());
+        assert!(pick(&model, &bvh, &Matrix4::zero(), &Ray::new(Vector3::zero(), Vector3::unit_x())).is_none());
+    }
+}
@@ -0,0 +1,757 @@
+use crate::backend;
+use crate::backend::{
+    OpenGLContext,
+    Program,
+    ShaderCompilationError,
+    ShaderHandle,
+    ShaderStage,
+};
+use log::{
+    error,
+};
+use std::collections::{
+    BTreeMap,
+    HashMap,
+    HashSet,
+};
+use std::error;
+use std::fmt;
+use std::io::{
+    Cursor,
+    Read,
+};
+use std::path::{
+    Path,
+};
+
+
+/// The name the line map gives to the `#define` lines injected by the preprocessor.
+const DEFINES_FILE_NAME: &str = "<defines>";
+
+
+/// A virtual directory of shader sources. Shaders are named by their paths in the
+/// directory, such as `include/octahedral.glsl`.
+#[derive(Clone, Debug, Default)]
+pub struct ShaderLibrary {
+    sources: HashMap<String, String>,
+}
+
+impl ShaderLibrary {
+    pub fn new() -> ShaderLibrary {
+        ShaderLibrary {
+            sources: HashMap::new(),
+        }
+    }
+
+    /// Add a shader to the library, replacing any shader with the same name.
+    pub fn insert(&mut self, name: &str, source: &str) {
+        self.sources.insert(String::from(name), String::from(source));
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.sources.get(name).map(|source| source.as_str())
+    }
+
+    pub fn len(&self) -> usize {
+        self.sources.len()
+    }
+}
+
+/// The preprocessor definitions of a shader variant. The definitions are sorted by
+/// name, so the same definitions made in any order compare and hash equal.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ShaderDefines {
+    defines: BTreeMap<String, String>,
+}
+
+impl ShaderDefines {
+    pub fn new() -> ShaderDefines {
+        ShaderDefines {
+            defines: BTreeMap::new(),
+        }
+    }
+
+    /// Define a macro with a value, such as `NUM_POINT_LIGHTS 4`.
+    pub fn with_define<T: ToString>(mut self, name: &str, value: T) -> Self {
+        self.defines.insert(String::from(name), value.to_string());
+        self
+    }
+
+    /// Define a feature flag, such as `HAS_NORMAL_MAP`, with the value `1`.
+    pub fn with_flag(self, name: &str) -> Self {
+        self.with_define(name, 1)
+    }
+
+    /// Add every definition of `other`, replacing definitions with the same name.
+    pub fn with_defines(mut self, other: &ShaderDefines) -> Self {
+        for (name, value) in other.defines.iter() {
+            self.defines.insert(name.clone(), value.clone());
+        }
+        self
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.defines.iter().map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.defines.is_empty()
+    }
+}
+
+/// The original file and line of every line of a preprocessed shader.
+#[derive(Clone, Debug, Default)]
+pub struct LineMap {
+    files: Vec<String>,
+    /// The index into `files` and the one-based line number of each output line.
+    lines: Vec<(usize, usize)>,
+}
+
+impl LineMap {
+    fn file_index(&mut self, file_name: &str) -> usize {
+        match self.files.iter().position(|name| name == file_name) {
+            Some(index) => index,
+            None => {
+                self.files.push(String::from(file_name));
+                self.files.len() - 1
+            }
+        }
+    }
+
+    /// The file and the line in it that a one-based line of the preprocessed
+    /// source came from.
+    pub fn source_location(&self, line: usize) -> Option<(&str, usize)> {
+        if line == 0 {
+            return None;
+        }
+
+        self.lines.get(line - 1).map(|&(file, line)| (self.files[file].as_str(), line))
+    }
+
+    /// Rewrite the line references in an OpenGL info log, such as `0(12)` or `0:12`,
+    /// to the files and lines they came from.
+    pub fn remap_log(&self, log: &str) -> String {
+        let lines: Vec<String> = log.lines()
+            .map(|line| self.remap_log_line(line))
+            .collect();
+
+        lines.join("\n")
+    }
+
+    fn remap_log_line(&self, line: &str) -> String {
+        let (start, end, number) = match find_line_reference(line) {
+            Some(val) => val,
+            None => return String::from(line),
+        };
+        match self.source_location(number) {
+            Some((file, original_line)) => {
+                format!("{}{}:{}{}", &line[..start], file, original_line, &line[end..])
+            }
+            None => String::from(line),
+        }
+    }
+}
+
+/// Find the first reference to a line of source string 0 in a line of a driver log.
+/// NVIDIA drivers write these as `0(12)`, while Mesa, AMD and Apple drivers write
+/// them as `0:12`. Returns the byte range of the reference and the line number.
+fn find_line_reference(line: &str) -> Option<(usize, usize, usize)> {
+    let bytes = line.as_bytes();
+    for start in 0..bytes.len() {
+        if bytes[start] != b'0' || (start > 0 && bytes[start - 1].is_ascii_digit()) {
+            continue;
+        }
+        let separator = match bytes.get(start + 1) {
+            Some(&separator) if separator == b'(' || separator == b':' => separator,
+            _ => continue,
+        };
+        let digits_start = start + 2;
+        let mut digits_end = digits_start;
+        while digits_end < bytes.len() && bytes[digits_end].is_ascii_digit() {
+            digits_end += 1;
+        }
+        if digits_end == digits_start {
+            continue;
+        }
+        let end = if separator == b'(' {
+            if bytes.get(digits_end) != Some(&b')') {
+                continue;
+            }
+            digits_end + 1
+        } else {
+            digits_end
+        };
+        if let Ok(number) = line[digits_start..digits_end].parse() {
+            return Some((start, end, number));
+        }
+    }
+
+    None
+}
+
+/// A shader with its includes resolved and its definitions injected.
+#[derive(Clone, Debug)]
+pub struct PreprocessedShader {
+    source: String,
+    line_map: LineMap,
+}
+
+impl PreprocessedShader {
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn line_map(&self) -> &LineMap {
+        &self.line_map
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PreprocessError {
+    /// The shader, or a file it includes, is not in the library. Includes record
+    /// the file and line of the `#include` directive.
+    ShaderNotFound {
+        name: String,
+        included_from: Option<(String, usize)>,
+    },
+    /// An `#include` directive without a quoted or bracketed file name.
+    MalformedInclude {
+        file_name: String,
+        line: usize,
+    },
+}
+
+impl fmt::Display for PreprocessError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PreprocessError::ShaderNotFound { name, included_from: None } => {
+                write!(f, "The shader `{}` is not in the shader library.", name)
+            }
+            PreprocessError::ShaderNotFound { name, included_from: Some((file_name, line)) } => {
+                write!(f,
+                    "{}:{}: The included shader `{}` is not in the shader library.",
+                    file_name, line, name
+                )
+            }
+            PreprocessError::MalformedInclude { file_name, line } => {
+                write!(f,
+                    "{}:{}: Expected a file name in quotes or angle brackets after `#include`.",
+                    file_name, line
+                )
+            }
+        }
+    }
+}
+
+impl error::Error for PreprocessError {}
+
+/// The argument of a preprocessor directive, if the line is that directive. GLSL
+/// allows whitespace before and after the `#`.
+fn directive_argument<'a>(line: &'a str, directive: &str) -> Option<&'a str> {
+    let line = line.trim_start();
+    if !line.starts_with('#') {
+        return None;
+    }
+    let line = line[1..].trim_start();
+    if !line.starts_with(directive) {
+        return None;
+    }
+    let argument = &line[directive.len()..];
+    match argument.chars().next() {
+        Some(ch) if !ch.is_whitespace() => None,
+        _ => Some(argument.trim()),
+    }
+}
+
+/// The file name of an `#include "name"` or `#include <name>` directive.
+fn include_name(argument: &str) -> Option<&str> {
+    let closing = match argument.chars().next() {
+        Some('"') => '"',
+        Some('<') => '>',
+        _ => return None,
+    };
+    let end = argument[1..].find(closing)? + 1;
+    let name = &argument[1..end];
+    if name.is_empty() {
+        return None;
+    }
+
+    Some(name)
+}
+
+/// The change in `#if` nesting depth made by a line.
+fn conditional_depth_change(line: &str) -> isize {
+    let opens = ["if", "ifdef", "ifndef"].iter()
+        .any(|directive| directive_argument(line, directive).is_some());
+    if opens {
+        1
+    } else if directive_argument(line, "endif").is_some() {
+        -1
+    } else {
+        0
+    }
+}
+
+/// The name of the macro guarding the expansion of an included file.
+fn include_guard(file: usize, file_name: &str) -> String {
+    let name: String = file_name.chars()
+        .map(|ch| if ch.is_ascii_alphanumeric() { ch.to_ascii_uppercase() } else { '_' })
+        .collect();
+
+    format!("INCLUDED_{}_{}", file, name)
+}
+
+struct Preprocessor<'a> {
+    library: &'a ShaderLibrary,
+    source: String,
+    line_map: LineMap,
+    /// The files already expanded outside of any `#if` block, which can be skipped.
+    included: HashSet<String>,
+    /// The files being expanded, from the top level file down.
+    include_stack: Vec<String>,
+    /// The `#if` nesting depth at the current output line.
+    conditional_depth: isize,
+}
+
+impl<'a> Preprocessor<'a> {
+    fn push_line(&mut self, line: &str, file: usize, line_number: usize) {
+        self.source.push_str(line);
+        self.source.push('\n');
+        self.line_map.lines.push((file, line_number));
+    }
+
+    fn push_defines(&mut self, defines: &ShaderDefines) {
+        let file = self.line_map.file_index(DEFINES_FILE_NAME);
+        for (index, (name, value)) in defines.iter().enumerate() {
+            let line = format!("#define {} {}", name, value);
+            self.push_line(&line, file, index + 1);
+        }
+    }
+
+    /// Resolve an include against the directory of the including file first, and
+    /// then against the root of the library.
+    fn resolve(&self, including_file: &str, name: &str) -> Option<String> {
+        if let Some(separator) = including_file.rfind('/') {
+            let relative = format!("{}/{}", &including_file[..separator], name);
+            if self.library.get(&relative).is_some() {
+                return Some(relative);
+            }
+        }
+
+        self.library.get(name).map(|_| String::from(name))
+    }
+
+    /// Expand a file into the output. The top level file passes its definitions,
+    /// which follow its `#version` directive. Included files drop theirs.
+    fn expand(
+        &mut self,
+        file_name: &str,
+        source: &str,
+        defines: Option<&ShaderDefines>) -> Result<(), PreprocessError>
+    {
+        if self.conditional_depth <= 0 {
+            self.included.insert(String::from(file_name));
+        }
+        self.include_stack.push(String::from(file_name));
+        let file = self.line_map.file_index(file_name);
+        for (index, line) in source.lines().enumerate() {
+            let line_number = index + 1;
+            if directive_argument(line, "version").is_some() {
+                if let Some(defines) = defines {
+                    self.push_line(line, file, line_number);
+                    self.push_defines(defines);
+                }
+            } else if let Some(argument) = directive_argument(line, "include") {
+                let name = include_name(argument).ok_or_else(|| {
+                    PreprocessError::MalformedInclude {
+                        file_name: String::from(file_name),
+                        line: line_number,
+                    }
+                })?;
+                let resolved = self.resolve(file_name, name).ok_or_else(|| {
+                    PreprocessError::ShaderNotFound {
+                        name: String::from(name),
+                        included_from: Some((String::from(file_name), line_number)),
+                    }
+                })?;
+                if !self.included.contains(&resolved) && !self.include_stack.contains(&resolved) {
+                    self.expand_guarded(&resolved, file, line_number)?;
+                }
+            } else {
+                self.conditional_depth += conditional_depth_change(line);
+                self.push_line(line, file, line_number);
+            }
+        }
+        self.include_stack.pop();
+
+        Ok(())
+    }
+
+    /// Expand an included file inside an include guard. A file included inside an
+    /// `#if` block may be included again in another branch, so its text is repeated
+    /// there and the guard keeps the compiler from seeing it twice. The guard lines
+    /// map to the `#include` directive.
+    fn expand_guarded(
+        &mut self,
+        file_name: &str,
+        including_file: usize,
+        line_number: usize) -> Result<(), PreprocessError>
+    {
+        let guard = include_guard(self.line_map.file_index(file_name), file_name);
+        self.push_line(&format!("#ifndef {}", guard), including_file, line_number);
+        self.push_line(&format!("#define {}", guard), including_file, line_number);
+        let library = self.library;
+        let source = library.get(file_name).unwrap();
+        self.expand(file_name, source, None)?;
+        self.push_line("#endif", including_file, line_number);
+
+        Ok(())
+    }
+}
+
+/// Resolve the `#include` directives of a shader in the library and inject the
+/// definitions after its `#version` directive. Each file is included at most once:
+/// the preprocessor wraps included files in include guards of its own, so they need
+/// none, and it skips files already included outside of any `#if` block. The line
+/// map of the result points every line back to the file and line it came from.
+pub fn preprocess(
+    library: &ShaderLibrary,
+    name: &str,
+    defines: &ShaderDefines) -> Result<PreprocessedShader, PreprocessError>
+{
+    let source = library.get(name).ok_or_else(|| {
+        PreprocessError::ShaderNotFound {
+            name: String::from(name),
+            included_from: None,
+        }
+    })?;
+    let mut preprocessor = Preprocessor {
+        library: library,
+        source: String::with_capacity(source.len()),
+        line_map: LineMap::default(),
+        included: HashSet::new(),
+        include_stack: vec![],
+        conditional_depth: 0,
+    };
+    let has_version = source.lines().any(|line| directive_argument(line, "version").is_some());
+    if !has_version {
+        preprocessor.push_defines(defines);
+    }
+    preprocessor.expand(name, source, Some(defines))?;
+
+    Ok(PreprocessedShader {
+        source: preprocessor.source,
+        line_map: preprocessor.line_map,
+    })
+}
+
+/// Preprocess and link the stages of a shader program, named by the shaders in the
+/// library. The logs of compile errors point back to the original files and lines.
+pub fn compile_variant(
+    context: &OpenGLContext,
+    library: &ShaderLibrary,
+    stages: &[(ShaderStage, &str)],
+    defines: &ShaderDefines) -> Result<Program, ShaderCompilationError>
+{
+    backend::check_stages_supported(context, stages.iter().cloned())?;
+    let mut shaders = Vec::with_capacity(stages.len());
+    for &(stage, name) in stages.iter() {
+        match preprocess(library, name, defines) {
+            Ok(shader) => shaders.push(shader),
+            Err(e) => {
+                error!("{}", e);
+                return Err(ShaderCompilationError::from_preprocessor(stage, String::from(name), e.to_string()));
+            }
+        }
+    }
+
+    let mut readers: Vec<Cursor<&str>> = shaders.iter()
+        .map(|shader| Cursor::new(shader.source()))
+        .collect();
+    let compile_stages: Vec<(ShaderStage, &mut dyn Read, &Path)> = stages.iter()
+        .zip(readers.iter_mut())
+        .map(|(&(stage, name), reader)| (stage, reader as &mut dyn Read, Path::new(name)))
+        .collect();
+    let program = backend::compile_and_link(compile_stages).map_err(|e| {
+        match e.stage().and_then(|stage| stages.iter().position(|&(other, _)| other == stage)) {
+            Some(index) => e.map_log(|log| shaders[index].line_map().remap_log(log)),
+            None => e,
+        }
+    })?;
+
+    Ok(Program::from_id(context, program))
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct VariantKey {
+    stages: Vec<(ShaderStage, String)>,
+    defines: ShaderDefines,
+}
+
+/// The compiled variants of the shader programs in a library. A program is compiled
+/// the first time its stages are requested with a set of definitions, and reused
+/// after that.
+pub struct ShaderVariantCache {
+    library: ShaderLibrary,
+    programs: HashMap<VariantKey, Program>,
+}
+
+impl ShaderVariantCache {
+    pub fn new(library: ShaderLibrary) -> ShaderVariantCache {
+        ShaderVariantCache {
+            library: library,
+            programs: HashMap::new(),
+        }
+    }
+
+    pub fn library(&self) -> &ShaderLibrary {
+        &self.library
+    }
+
+    /// The program for the stages, in pipeline order, with the definitions. The
+    /// returned handle keeps the program alive even if the cache is dropped.
+    pub fn get_or_compile(
+        &mut self,
+        context: &OpenGLContext,
+        stages: &[(ShaderStage, &str)],
+        defines: &ShaderDefines) -> Result<ShaderHandle, ShaderCompilationError>
+    {
+        let key = VariantKey {
+            stages: stages.iter().map(|&(stage, name)| (stage, String::from(name))).collect(),
+            defines: defines.clone(),
+        };
+        if let Some(program) = self.programs.get(&key) {
+            return Ok(program.handle().clone());
+        }
+
+        let program = compile_variant(context, &self.library, stages, defines)?;
+        let handle = program.handle().clone();
+        self.programs.insert(key, program);
+
+        Ok(handle)
+    }
+
+    /// The number of compiled variants.
+    pub fn len(&self) -> usize {
+        self.programs.len()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+
+    fn library(shaders: &[(&str, &str)]) -> ShaderLibrary {
+        let mut library = ShaderLibrary::new();
+        for &(name, source) in shaders.iter() {
+            library.insert(name, source);
+        }
+
+        library
+    }
+
+    fn occurrences(source: &str, text: &str) -> usize {
+        source.lines().filter(|line| line.trim() == text).count()
+    }
+
+    #[test]
+    fn test_defines_follow_version() {
+        let library = library(&[("model.vert", "#version 330\nvoid main() {}\n")]);
+        let defines = ShaderDefines::new().with_define("NUM_LIGHTS", 4).with_flag("HAS_NORMAL_MAP");
+        let shader = preprocess(&library, "model.vert", &defines).unwrap();
+        let lines: Vec<&str> = shader.source().lines().collect();
+
+        assert_eq!(lines, vec![
+            "#version 330",
+            "#define HAS_NORMAL_MAP 1",
+            "#define NUM_LIGHTS 4",
+            "void main() {}",
+        ]);
+        assert_eq!(shader.line_map().source_location(1), Some(("model.vert", 1)));
+        assert_eq!(shader.line_map().source_location(3), Some((DEFINES_FILE_NAME, 2)));
+        assert_eq!(shader.line_map().source_location(4), Some(("model.vert", 2)));
+        assert_eq!(shader.line_map().source_location(0), None);
+        assert_eq!(shader.line_map().source_location(5), None);
+    }
+
+    #[test]
+    fn test_defines_without_version() {
+        let library = library(&[("common.glsl", "float x;\n")]);
+        let defines = ShaderDefines::new().with_flag("A");
+        let shader = preprocess(&library, "common.glsl", &defines).unwrap();
+
+        assert_eq!(shader.source(), "#define A 1\nfloat x;\n");
+    }
+
+    #[test]
+    fn test_nested_and_relative_includes() {
+        let library = library(&[
+            ("model.frag", "#version 330\n#include \"include/lights.glsl\"\nvoid main() {}\n"),
+            ("include/lights.glsl", "// lights\n#include \"camera.glsl\"\nuniform int light_count;\n"),
+            ("include/camera.glsl", "uniform vec3 camera_position;\n"),
+            ("camera.glsl", "uniform vec3 wrong_camera;\n"),
+        ]);
+        let shader = preprocess(&library, "model.frag", &ShaderDefines::new()).unwrap();
+
+        assert_eq!(occurrences(shader.source(), "uniform vec3 camera_position;"), 1);
+        assert_eq!(occurrences(shader.source(), "uniform vec3 wrong_camera;"), 0);
+        assert!(!shader.source().contains("#include"));
+
+        let location = |text: &str| {
+            let line = shader.source().lines().position(|line| line == text).unwrap() + 1;
+            shader.line_map().source_location(line).unwrap()
+        };
+        assert_eq!(location("#version 330"), ("model.frag", 1));
+        assert_eq!(location("// lights"), ("include/lights.glsl", 1));
+        assert_eq!(location("uniform vec3 camera_position;"), ("include/camera.glsl", 1));
+        assert_eq!(location("uniform int light_count;"), ("include/lights.glsl", 3));
+        assert_eq!(location("void main() {}"), ("model.frag", 3));
+    }
+
+    #[test]
+    fn test_missing_include_reports_file_and_line() {
+        let library = library(&[
+            ("model.frag", "#version 330\n#include \"include/lights.glsl\"\n"),
+            ("include/lights.glsl", "// lights\n\n#include \"shadows.glsl\"\n"),
+        ]);
+        let error = preprocess(&library, "model.frag", &ShaderDefines::new()).unwrap_err();
+
+        assert_eq!(error, PreprocessError::ShaderNotFound {
+            name: String::from("shadows.glsl"),
+            included_from: Some((String::from("include/lights.glsl"), 3)),
+        });
+        assert!(error.to_string().starts_with("include/lights.glsl:3:"));
+    }
+
+    #[test]
+    fn test_missing_shader() {
+        let error = preprocess(&ShaderLibrary::new(), "model.frag", &ShaderDefines::new()).unwrap_err();
+
+        assert_eq!(error, PreprocessError::ShaderNotFound {
+            name: String::from("model.frag"),
+            included_from: None,
+        });
+    }
+
+    #[test]
+    fn test_malformed_include() {
+        let library = library(&[("model.frag", "#version 330\n\n  #  include lights.glsl\n")]);
+        let error = preprocess(&library, "model.frag", &ShaderDefines::new()).unwrap_err();
+
+        assert_eq!(error, PreprocessError::MalformedInclude {
+            file_name: String::from("model.frag"),
+            line: 3,
+        });
+    }
+
+    #[test]
+    fn test_repeated_include_is_skipped() {
+        let library = library(&[
+            ("model.frag", "#include \"a.glsl\"\n#include \"b.glsl\"\n#include \"a.glsl\"\n"),
+            ("a.glsl", "float a;\n"),
+            ("b.glsl", "#include \"a.glsl\"\nfloat b;\n"),
+        ]);
+        let shader = preprocess(&library, "model.frag", &ShaderDefines::new()).unwrap();
+
+        assert_eq!(occurrences(shader.source(), "float a;"), 1);
+        assert_eq!(occurrences(shader.source(), "float b;"), 1);
+    }
+
+    #[test]
+    fn test_include_in_both_branches_of_a_conditional() {
+        let library = library(&[
+            ("model.frag", "#ifdef A\n#include \"x.glsl\"\n#else\n#include \"x.glsl\"\n#endif\n#include \"x.glsl\"\n"),
+            ("x.glsl", "float x;\n"),
+        ]);
+        let shader = preprocess(&library, "model.frag", &ShaderDefines::new()).unwrap();
+        let guard = shader.source().lines()
+            .find(|line| line.starts_with("#ifndef INCLUDED_"))
+            .map(|line| line["#ifndef ".len()..].to_owned())
+            .unwrap();
+
+        // Each branch gets its own guarded copy, and the one after the block is
+        // guarded too since either branch may have been taken.
+        assert_eq!(occurrences(shader.source(), "float x;"), 3);
+        assert_eq!(occurrences(shader.source(), &format!("#ifndef {}", guard)), 3);
+        assert_eq!(occurrences(shader.source(), &format!("#define {}", guard)), 3);
+
+        let line = shader.source().lines().position(|line| line == format!("#ifndef {}", guard)).unwrap() + 1;
+        assert_eq!(shader.line_map().source_location(line), Some(("model.frag", 2)));
+    }
+
+    #[test]
+    fn test_include_after_conditional_block() {
+        let library = library(&[
+            ("model.frag", "#if A\n#include \"x.glsl\"\n#endif\n#include \"x.glsl\"\n#include \"x.glsl\"\n"),
+            ("x.glsl", "float x;\n"),
+        ]);
+        let shader = preprocess(&library, "model.frag", &ShaderDefines::new()).unwrap();
+
+        assert_eq!(occurrences(shader.source(), "float x;"), 2);
+    }
+
+    #[test]
+    fn test_include_cycle() {
+        let library = library(&[
+            ("a.glsl", "#include \"b.glsl\"\nfloat a;\n"),
+            ("b.glsl", "#ifdef B\n#include \"a.glsl\"\n#endif\nfloat b;\n"),
+        ]);
+        let shader = preprocess(&library, "a.glsl", &ShaderDefines::new()).unwrap();
+
+        assert_eq!(occurrences(shader.source(), "float a;"), 1);
+        assert_eq!(occurrences(shader.source(), "float b;"), 1);
+    }
+
+    #[test]
+    fn test_find_line_reference_nvidia() {
+        let line = "0(12) : error C1008: undefined variable \"x\"";
+
+        assert_eq!(find_line_reference(line), Some((0, 5, 12)));
+    }
+
+    #[test]
+    fn test_find_line_reference_mesa() {
+        let line = "0:12(5): error: `x' undeclared";
+
+        assert_eq!(find_line_reference(line), Some((0, 4, 12)));
+        assert_eq!(find_line_reference("ERROR: 0:7: 'x' : undeclared identifier"), Some((7, 10, 7)));
+    }
+
+    #[test]
+    fn test_find_line_reference_none() {
+        assert_eq!(find_line_reference("error: something went wrong"), None);
+        assert_eq!(find_line_reference("10:12 is not string 0"), None);
+        assert_eq!(find_line_reference("0(12 unclosed"), None);
+        assert_eq!(find_line_reference("0: no line"), None);
+    }
+
+    #[test]
+    fn test_remap_log() {
+        let library = library(&[
+            ("model.frag", "#version 330\n#include \"lights.glsl\"\nvoid main() {}\n"),
+            ("lights.glsl", "float broken\n"),
+        ]);
+        let defines = ShaderDefines::new().with_flag("A");
+        let shader = preprocess(&library, "model.frag", &defines).unwrap();
+        let broken = shader.source().lines().position(|line| line == "float broken").unwrap() + 1;
+        let main = shader.source().lines().position(|line| line == "void main() {}").unwrap() + 1;
+        let log = format!(
+            "0({}) : error C0000: syntax error\n0:{}(1): error: syntax error\nlinking failed",
+            broken, main
+        );
+
+        assert_eq!(shader.line_map().remap_log(&log), String::from(
+            "lights.glsl:1 : error C0000: syntax error\nmodel.frag:3(1): error: syntax error\nlinking failed"
+        ));
+    }
+
+    #[test]
+    fn test_remap_log_out_of_range() {
+        let library = library(&[("model.frag", "void main() {}\n")]);
+        let shader = preprocess(&library, "model.frag", &ShaderDefines::new()).unwrap();
+
+        assert_eq!(shader.line_map().remap_log("0(99) : error"), "0(99) : error");
+    }
+}
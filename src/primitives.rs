@@ -0,0 +1,624 @@
+use crate::model;
+use crate::model::{
+    Mesh,
+    Vertex,
+};
+use cglinalg::{
+    Magnitude,
+    Vector2,
+    Vector3,
+};
+use std::collections::{
+    HashMap,
+};
+use std::f32::consts::{
+    PI,
+};
+
+
+/// Collects the vertices and triangles of a primitive. Every primitive is centered
+/// on the origin with +Y up, and its triangles wind counterclockwise when seen from
+/// outside.
+struct MeshBuilder {
+    vertices: Vec<Vertex>,
+    vertex_indices: Vec<u32>,
+}
+
+impl MeshBuilder {
+    fn new() -> MeshBuilder {
+        MeshBuilder {
+            vertices: vec![],
+            vertex_indices: vec![],
+        }
+    }
+
+    fn vertex(&mut self, position: Vector3<f32>, normal: Vector3<f32>, tex_coords: Vector2<f32>) -> u32 {
+        self.vertices.push(Vertex {
+            position: position,
+            normal: normal,
+            tex_coords: tex_coords,
+            tangent: Vector3::zero(),
+            bitangent: Vector3::zero(),
+            color: Vertex::default_color(),
+            tex_coords2: Vector2::zero(),
+        });
+
+        (self.vertices.len() - 1) as u32
+    }
+
+    /// Add a triangle, unless two of its corners share a position, as happens at the
+    /// poles of a sphere or the apex of a cone.
+    fn triangle(&mut self, a: u32, b: u32, c: u32) {
+        let pa = self.vertices[a as usize].position;
+        let pb = self.vertices[b as usize].position;
+        let pc = self.vertices[c as usize].position;
+        if pa == pb || pb == pc || pc == pa {
+            return;
+        }
+        self.vertex_indices.push(a);
+        self.vertex_indices.push(b);
+        self.vertex_indices.push(c);
+    }
+
+    /// Add a quad with corners in counterclockwise order.
+    fn quad(&mut self, a: u32, b: u32, c: u32, d: u32) {
+        self.triangle(a, b, c);
+        self.triangle(a, c, d);
+    }
+
+    /// Connect a grid of `rows + 1` by `columns + 1` vertices, stored row by row
+    /// starting at `first`. Rows run down the surface and columns run to the right
+    /// when seen from outside.
+    fn grid(&mut self, first: u32, rows: u32, columns: u32) {
+        let stride = columns + 1;
+        for i in 0..rows {
+            for j in 0..columns {
+                let a = first + i * stride + j;
+                let b = first + (i + 1) * stride + j;
+                self.quad(a, b, b + 1, a + 1);
+            }
+        }
+    }
+
+    /// Add a flat disk facing up or down, centered on `center`.
+    fn disk(&mut self, center: Vector3<f32>, radius: f32, sectors: u32, facing_up: bool) {
+        let normal = if facing_up { Vector3::new(0.0, 1.0, 0.0) } else { Vector3::new(0.0, -1.0, 0.0) };
+        let middle = self.vertex(center, normal, Vector2::new(0.5, 0.5));
+        let first = self.vertices.len() as u32;
+        for j in 0..(sectors + 1) {
+            let theta = 2.0 * PI * j as f32 / sectors as f32;
+            let (sin_theta, cos_theta) = sin_cos(theta);
+            let position = center + Vector3::new(radius * sin_theta, 0.0, radius * cos_theta);
+            let tex_coords = if facing_up {
+                Vector2::new(0.5 + 0.5 * sin_theta, 0.5 - 0.5 * cos_theta)
+            } else {
+                Vector2::new(0.5 + 0.5 * sin_theta, 0.5 + 0.5 * cos_theta)
+            };
+            self.vertex(position, normal, tex_coords);
+        }
+        for j in 0..sectors {
+            if facing_up {
+                self.triangle(middle, first + j, first + j + 1);
+            } else {
+                self.triangle(middle, first + j + 1, first + j);
+            }
+        }
+    }
+
+    fn build(mut self, name: &str) -> Mesh {
+        generate_tangents(&mut self.vertices, &self.vertex_indices);
+
+        Mesh::new(name.to_owned(), self.vertices, self.vertex_indices, HashMap::new())
+    }
+}
+
+/// Find a unit vector perpendicular to `normal`.
+fn perpendicular(normal: &Vector3<f32>) -> Vector3<f32> {
+    let axis = if normal.x.abs() < 0.9 { Vector3::new(1.0, 0.0, 0.0) } else { Vector3::new(0.0, 1.0, 0.0) };
+
+    axis.cross(normal).normalize()
+}
+
+/// Compute tangent frames that follow the texture coordinates of a mesh.
+fn generate_tangents(vertices: &mut [Vertex], vertex_indices: &[u32]) {
+    let mut tangents = vec![Vector3::zero(); vertices.len()];
+    let mut bitangents = vec![Vector3::zero(); vertices.len()];
+    for indices in vertex_indices.chunks_exact(3) {
+        let v0 = &vertices[indices[0] as usize];
+        let v1 = &vertices[indices[1] as usize];
+        let v2 = &vertices[indices[2] as usize];
+        let edge1 = v1.position - v0.position;
+        let edge2 = v2.position - v0.position;
+        let delta_uv1 = v1.tex_coords - v0.tex_coords;
+        let delta_uv2 = v2.tex_coords - v0.tex_coords;
+        let determinant = delta_uv1.x * delta_uv2.y - delta_uv2.x * delta_uv1.y;
+        if determinant.abs() <= f32::EPSILON {
+            continue;
+        }
+        let tangent = (edge1 * delta_uv2.y - edge2 * delta_uv1.y) * (1.0 / determinant);
+        let bitangent = (edge2 * delta_uv1.x - edge1 * delta_uv2.x) * (1.0 / determinant);
+        for &index in indices.iter() {
+            tangents[index as usize] += tangent;
+            bitangents[index as usize] += bitangent;
+        }
+    }
+
+    for (i, vertex) in vertices.iter_mut().enumerate() {
+        let normal = vertex.normal;
+        let tangent = tangents[i] - normal * normal.dot(&tangents[i]);
+        let tangent = if tangent.magnitude_squared() > 0.0 {
+            tangent.normalize()
+        } else {
+            perpendicular(&normal)
+        };
+        let bitangent = normal.cross(&tangent);
+        let sign = if bitangent.dot(&bitangents[i]) < 0.0 { -1.0 } else { 1.0 };
+        vertex.tangent = tangent;
+        vertex.bitangent = bitangent * sign;
+    }
+}
+
+/// Compute the sine and cosine of an angle, snapping results that are zero up to
+/// rounding to exactly zero. This way the vertices closing a seam, or meeting at a
+/// pole, share exactly the same position.
+#[inline]
+fn sin_cos(angle: f32) -> (f32, f32) {
+    let snap = |value: f32| if value.abs() < 1e-6 { 0.0 } else { value };
+    let (sin, cos) = angle.sin_cos();
+
+    (snap(sin), snap(cos))
+}
+
+/// The direction from the center of a sphere to the point at polar angle `phi`,
+/// measured from +Y, and azimuth `theta`, measured from +Z towards +X.
+#[inline]
+fn spherical_direction(phi: f32, theta: f32) -> Vector3<f32> {
+    let (sin_phi, cos_phi) = sin_cos(phi);
+    let (sin_theta, cos_theta) = sin_cos(theta);
+
+    Vector3::new(sin_phi * sin_theta, cos_phi, sin_phi * cos_theta)
+}
+
+/// Construct an axis-aligned cube with edge length `size`. Every face has its own
+/// vertices, so the edges are hard, and its own copy of the full texture.
+pub fn cube(size: f32) -> Mesh {
+    // Each face as its normal and the directions its texture coordinates run in.
+    let faces = [
+        (Vector3::new( 1.0, 0.0, 0.0), Vector3::new( 0.0, 0.0, -1.0), Vector3::new(0.0, 1.0,  0.0)),
+        (Vector3::new(-1.0, 0.0, 0.0), Vector3::new( 0.0, 0.0,  1.0), Vector3::new(0.0, 1.0,  0.0)),
+        (Vector3::new( 0.0, 1.0, 0.0), Vector3::new( 1.0, 0.0,  0.0), Vector3::new(0.0, 0.0, -1.0)),
+        (Vector3::new(0.0, -1.0, 0.0), Vector3::new( 1.0, 0.0,  0.0), Vector3::new(0.0, 0.0,  1.0)),
+        (Vector3::new( 0.0, 0.0, 1.0), Vector3::new( 1.0, 0.0,  0.0), Vector3::new(0.0, 1.0,  0.0)),
+        (Vector3::new(0.0, 0.0, -1.0), Vector3::new(-1.0, 0.0,  0.0), Vector3::new(0.0, 1.0,  0.0)),
+    ];
+    let half_size = 0.5 * size;
+    let corners = [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)];
+
+    let mut builder = MeshBuilder::new();
+    for (normal, u_axis, v_axis) in faces.iter() {
+        let first = builder.vertices.len() as u32;
+        for &(s, t) in corners.iter() {
+            let position = (normal + u_axis * s + v_axis * t) * half_size;
+            let tex_coords = Vector2::new(0.5 * (s + 1.0), 0.5 * (t + 1.0));
+            builder.vertex(position, *normal, tex_coords);
+        }
+        builder.quad(first, first + 1, first + 2, first + 3);
+    }
+
+    builder.build("cube")
+}
+
+/// Construct a sphere from `stacks` rings of latitude and `sectors` segments of
+/// longitude. The texture coordinates are an equirectangular map.
+pub fn uv_sphere(radius: f32, sectors: u32, stacks: u32) -> Mesh {
+    let sectors = u32::max(sectors, 3);
+    let stacks = u32::max(stacks, 2);
+
+    let mut builder = MeshBuilder::new();
+    for i in 0..(stacks + 1) {
+        let phi = PI * i as f32 / stacks as f32;
+        for j in 0..(sectors + 1) {
+            let theta = 2.0 * PI * j as f32 / sectors as f32;
+            let direction = spherical_direction(phi, theta);
+            let tex_coords = Vector2::new(j as f32 / sectors as f32, 1.0 - i as f32 / stacks as f32);
+            builder.vertex(direction * radius, direction, tex_coords);
+        }
+    }
+    builder.grid(0, stacks, sectors);
+
+    builder.build("uv_sphere")
+}
+
+/// Construct a sphere by repeatedly splitting the faces of an icosahedron. Unlike a
+/// UV sphere, its triangles are all close to the same size.
+pub fn icosphere(radius: f32, subdivisions: u32) -> Mesh {
+    let t = (1.0 + f32::sqrt(5.0)) / 2.0;
+    let mut directions: Vec<Vector3<f32>> = [
+        [-1.0, t, 0.0], [1.0, t, 0.0], [-1.0, -t, 0.0], [1.0, -t, 0.0],
+        [0.0, -1.0, t], [0.0, 1.0, t], [0.0, -1.0, -t], [0.0, 1.0, -t],
+        [t, 0.0, -1.0], [t, 0.0, 1.0], [-t, 0.0, -1.0], [-t, 0.0, 1.0],
+    ].iter()
+        .map(|p| Vector3::new(p[0], p[1], p[2]).normalize())
+        .collect();
+    let mut faces: Vec<[u32; 3]> = vec![
+        [0, 11, 5], [0, 5, 1], [0, 1, 7], [0, 7, 10], [0, 10, 11],
+        [1, 5, 9], [5, 11, 4], [11, 10, 2], [10, 7, 6], [7, 1, 8],
+        [3, 9, 4], [3, 4, 2], [3, 2, 6], [3, 6, 8], [3, 8, 9],
+        [4, 9, 5], [2, 4, 11], [6, 2, 10], [8, 6, 7], [9, 8, 1],
+    ];
+
+    for _ in 0..subdivisions {
+        let mut midpoints: HashMap<(u32, u32), u32> = HashMap::new();
+        let mut midpoint = |a: u32, b: u32, directions: &mut Vec<Vector3<f32>>| {
+            let key = if a < b { (a, b) } else { (b, a) };
+            *midpoints.entry(key).or_insert_with(|| {
+                let direction = (directions[a as usize] + directions[b as usize]).normalize();
+                directions.push(direction);
+                (directions.len() - 1) as u32
+            })
+        };
+        let mut next_faces = Vec::with_capacity(4 * faces.len());
+        for face in faces.iter() {
+            let ab = midpoint(face[0], face[1], &mut directions);
+            let bc = midpoint(face[1], face[2], &mut directions);
+            let ca = midpoint(face[2], face[0], &mut directions);
+            next_faces.push([face[0], ab, ca]);
+            next_faces.push([face[1], bc, ab]);
+            next_faces.push([face[2], ca, bc]);
+            next_faces.push([ab, bc, ca]);
+        }
+        faces = next_faces;
+    }
+
+    // Texture coordinates are assigned per triangle, so that triangles crossing the
+    // seam of the map, or touching a pole, get their own vertices.
+    let mut corners = Vec::with_capacity(3 * faces.len());
+    for face in faces.iter() {
+        let mut tex_coords = [Vector2::zero(); 3];
+        for k in 0..3 {
+            let direction = directions[face[k] as usize];
+            let u = f32::atan2(direction.x, direction.z) / (2.0 * PI);
+            let u = if u < 0.0 { u + 1.0 } else { u };
+            let v = 0.5 + f32::asin(f32::max(-1.0, f32::min(1.0, direction.y))) / PI;
+            tex_coords[k] = Vector2::new(u, v);
+        }
+        let min_u = tex_coords.iter().map(|uv| uv.x).fold(f32::INFINITY, f32::min);
+        let max_u = tex_coords.iter().map(|uv| uv.x).fold(f32::NEG_INFINITY, f32::max);
+        if max_u - min_u > 0.5 {
+            for uv in tex_coords.iter_mut() {
+                if uv.x < 0.5 {
+                    uv.x += 1.0;
+                }
+            }
+        }
+        for k in 0..3 {
+            let direction = directions[face[k] as usize];
+            if direction.x.abs() < 1e-6 && direction.z.abs() < 1e-6 {
+                tex_coords[k].x = 0.5 * (tex_coords[(k + 1) % 3].x + tex_coords[(k + 2) % 3].x);
+            }
+        }
+        for k in 0..3 {
+            let direction = directions[face[k] as usize];
+            corners.push(Vertex {
+                position: direction * radius,
+                normal: direction,
+                tex_coords: tex_coords[k],
+                tangent: Vector3::zero(),
+                bitangent: Vector3::zero(),
+                color: Vertex::default_color(),
+                tex_coords2: Vector2::zero(),
+            });
+        }
+    }
+
+    let (vertices, vertex_indices) = model::deduplicate_vertices(&corners);
+    let builder = MeshBuilder {
+        vertices: vertices,
+        vertex_indices: vertex_indices,
+    };
+
+    builder.build("icosphere")
+}
+
+/// Construct a flat rectangle in the XZ plane facing +Y, split into a grid of
+/// `width_segments` by `depth_segments` quads.
+pub fn plane(width: f32, depth: f32, width_segments: u32, depth_segments: u32) -> Mesh {
+    let width_segments = u32::max(width_segments, 1);
+    let depth_segments = u32::max(depth_segments, 1);
+    let normal = Vector3::new(0.0, 1.0, 0.0);
+
+    let mut builder = MeshBuilder::new();
+    for i in 0..(depth_segments + 1) {
+        let t = i as f32 / depth_segments as f32;
+        for j in 0..(width_segments + 1) {
+            let s = j as f32 / width_segments as f32;
+            let position = Vector3::new((s - 0.5) * width, 0.0, (t - 0.5) * depth);
+            builder.vertex(position, normal, Vector2::new(s, 1.0 - t));
+        }
+    }
+    builder.grid(0, depth_segments, width_segments);
+
+    builder.build("plane")
+}
+
+/// Construct a closed cylinder along the Y axis.
+pub fn cylinder(radius: f32, height: f32, sectors: u32) -> Mesh {
+    let sectors = u32::max(sectors, 3);
+    let half_height = 0.5 * height;
+
+    let mut builder = MeshBuilder::new();
+    for i in 0..2 {
+        let y = if i == 0 { half_height } else { -half_height };
+        for j in 0..(sectors + 1) {
+            let theta = 2.0 * PI * j as f32 / sectors as f32;
+            let normal = spherical_direction(0.5 * PI, theta);
+            let position = Vector3::new(radius * normal.x, y, radius * normal.z);
+            let tex_coords = Vector2::new(j as f32 / sectors as f32, 1.0 - i as f32);
+            builder.vertex(position, normal, tex_coords);
+        }
+    }
+    builder.grid(0, 1, sectors);
+    builder.disk(Vector3::new(0.0, half_height, 0.0), radius, sectors, true);
+    builder.disk(Vector3::new(0.0, -half_height, 0.0), radius, sectors, false);
+
+    builder.build("cylinder")
+}
+
+/// Construct a closed cone along the Y axis with its apex pointing up.
+pub fn cone(radius: f32, height: f32, sectors: u32) -> Mesh {
+    let sectors = u32::max(sectors, 3);
+    let half_height = 0.5 * height;
+
+    let mut builder = MeshBuilder::new();
+    for i in 0..2 {
+        for j in 0..(sectors + 1) {
+            let theta = 2.0 * PI * j as f32 / sectors as f32;
+            let (sin_theta, cos_theta) = sin_cos(theta);
+            let normal = Vector3::new(height * sin_theta, radius, height * cos_theta).normalize();
+            let position = if i == 0 {
+                Vector3::new(0.0, half_height, 0.0)
+            } else {
+                Vector3::new(radius * sin_theta, -half_height, radius * cos_theta)
+            };
+            let tex_coords = Vector2::new(j as f32 / sectors as f32, 1.0 - i as f32);
+            builder.vertex(position, normal, tex_coords);
+        }
+    }
+    builder.grid(0, 1, sectors);
+    builder.disk(Vector3::new(0.0, -half_height, 0.0), radius, sectors, false);
+
+    builder.build("cone")
+}
+
+/// Construct a torus lying in the XZ plane. The tube of radius `minor_radius` is
+/// swept around a circle of radius `major_radius`.
+pub fn torus(major_radius: f32, minor_radius: f32, major_segments: u32, minor_segments: u32) -> Mesh {
+    let major_segments = u32::max(major_segments, 3);
+    let minor_segments = u32::max(minor_segments, 3);
+
+    let mut builder = MeshBuilder::new();
+    for i in 0..(minor_segments + 1) {
+        // Walk the tube downwards across its outer side, matching the row order of `grid`.
+        let phi = -2.0 * PI * i as f32 / minor_segments as f32;
+        let (sin_phi, cos_phi) = sin_cos(phi);
+        for j in 0..(major_segments + 1) {
+            let theta = 2.0 * PI * j as f32 / major_segments as f32;
+            let (sin_theta, cos_theta) = sin_cos(theta);
+            let normal = Vector3::new(cos_phi * sin_theta, sin_phi, cos_phi * cos_theta);
+            let ring_radius = major_radius + minor_radius * cos_phi;
+            let position = Vector3::new(ring_radius * sin_theta, minor_radius * sin_phi, ring_radius * cos_theta);
+            let tex_coords = Vector2::new(
+                j as f32 / major_segments as f32, 1.0 - i as f32 / minor_segments as f32
+            );
+            builder.vertex(position, normal, tex_coords);
+        }
+    }
+    builder.grid(0, minor_segments, major_segments);
+
+    builder.build("torus")
+}
+
+/// Construct a capsule along the Y axis: a cylinder of height `height` capped by two
+/// hemispheres, each made of `hemisphere_stacks` rings.
+pub fn capsule(radius: f32, height: f32, sectors: u32, hemisphere_stacks: u32) -> Mesh {
+    let sectors = u32::max(sectors, 3);
+    let hemisphere_stacks = u32::max(hemisphere_stacks, 1);
+    let half_height = 0.5 * height;
+    let quarter_arc = 0.5 * PI * radius;
+    let profile_length = 2.0 * quarter_arc + height;
+
+    let mut builder = MeshBuilder::new();
+    let mut rows = 0;
+    for (center_y, first_phi, arc_start) in [
+        (half_height, 0.0, 0.0),
+        (-half_height, 0.5 * PI, quarter_arc + height),
+    ].iter() {
+        for i in 0..(hemisphere_stacks + 1) {
+            let fraction = i as f32 / hemisphere_stacks as f32;
+            let phi = first_phi + 0.5 * PI * fraction;
+            let v = 1.0 - (arc_start + quarter_arc * fraction) / profile_length;
+            for j in 0..(sectors + 1) {
+                let theta = 2.0 * PI * j as f32 / sectors as f32;
+                let direction = spherical_direction(phi, theta);
+                let position = direction * radius + Vector3::new(0.0, *center_y, 0.0);
+                let tex_coords = Vector2::new(j as f32 / sectors as f32, v);
+                builder.vertex(position, direction, tex_coords);
+            }
+            rows += 1;
+        }
+    }
+    builder.grid(0, rows - 1, sectors);
+
+    builder.build("capsule")
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+
+    /// A key that identifies a position up to rounding, so the vertices along a
+    /// seam of a primitive compare equal.
+    fn position_key(position: &Vector3<f32>) -> [i32; 3] {
+        let quantize = |value: f32| (value * 1e4).round() as i32;
+
+        [quantize(position.x), quantize(position.y), quantize(position.z)]
+    }
+
+    /// Check that every edge of a mesh is shared by exactly two triangles that run
+    /// along it in opposite directions, so the mesh is closed, manifold, and
+    /// consistently wound.
+    fn assert_closed_manifold(mesh: &Mesh) {
+        let mut edges = HashMap::new();
+        for triangle in mesh.vertex_indices.chunks_exact(3) {
+            for k in 0..3 {
+                let a = position_key(&mesh.vertices[triangle[k] as usize].position);
+                let b = position_key(&mesh.vertices[triangle[(k + 1) % 3] as usize].position);
+                *edges.entry((a, b)).or_insert(0) += 1;
+            }
+        }
+        for (&(a, b), &count) in edges.iter() {
+            assert_eq!(count, 1, "{}: edge {:?} -> {:?} is used {} times", mesh.name, a, b, count);
+            assert_eq!(
+                edges.get(&(b, a)), Some(&1),
+                "{}: edge {:?} -> {:?} has no opposite edge", mesh.name, a, b
+            );
+        }
+    }
+
+    /// Check that the winding of every triangle agrees with its vertex normals and
+    /// points away from `inside`, the closest point inside the surface.
+    fn assert_outward_normals<F>(mesh: &Mesh, inside: F) where F: Fn(&Vector3<f32>) -> Vector3<f32> {
+        for triangle in mesh.vertex_indices.chunks_exact(3) {
+            let p0 = mesh.vertices[triangle[0] as usize].position;
+            let p1 = mesh.vertices[triangle[1] as usize].position;
+            let p2 = mesh.vertices[triangle[2] as usize].position;
+            let face_normal = (p1 - p0).cross(&(p2 - p0));
+            let centroid = (p0 + p1 + p2) / 3.0;
+            assert!(face_normal.dot(&(centroid - inside(&centroid))) > 0.0, "{}: inward face", mesh.name);
+            for &index in triangle.iter() {
+                let normal = mesh.vertices[index as usize].normal;
+                assert!(face_normal.dot(&normal) > 0.0, "{}: normal disagrees with winding", mesh.name);
+            }
+        }
+    }
+
+    fn assert_unit_tangent_frames(mesh: &Mesh) {
+        for vertex in mesh.vertices.iter() {
+            assert!((vertex.normal.magnitude() - 1.0).abs() < 1e-4, "{}: normal {:?}", mesh.name, vertex.normal);
+            assert!((vertex.tangent.magnitude() - 1.0).abs() < 1e-4, "{}: tangent {:?}", mesh.name, vertex.tangent);
+            assert!(
+                (vertex.bitangent.magnitude() - 1.0).abs() < 1e-4,
+                "{}: bitangent {:?}", mesh.name, vertex.bitangent
+            );
+            assert!(vertex.tangent.dot(&vertex.normal).abs() < 1e-4, "{}: tangent is not tangent", mesh.name);
+        }
+    }
+
+    fn assert_counts(mesh: &Mesh, vertex_count: usize, triangle_count: usize) {
+        assert_eq!(mesh.vertices.len(), vertex_count, "{}: vertex count", mesh.name);
+        assert_eq!(mesh.vertex_indices.len(), 3 * triangle_count, "{}: triangle count", mesh.name);
+    }
+
+    fn origin(_: &Vector3<f32>) -> Vector3<f32> {
+        Vector3::zero()
+    }
+
+    #[test]
+    fn test_cube() {
+        let mesh = cube(2.0);
+
+        assert_counts(&mesh, 24, 12);
+        assert_closed_manifold(&mesh);
+        assert_outward_normals(&mesh, origin);
+        assert_unit_tangent_frames(&mesh);
+        assert_eq!(mesh.aabb.min, Vector3::new(-1.0, -1.0, -1.0));
+        assert_eq!(mesh.aabb.max, Vector3::new(1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn test_uv_sphere() {
+        let mesh = uv_sphere(1.5, 16, 8);
+
+        // The quads touching the poles lose their degenerate triangle.
+        assert_counts(&mesh, 9 * 17, 2 * 16 * 7);
+        assert_closed_manifold(&mesh);
+        assert_outward_normals(&mesh, origin);
+        assert_unit_tangent_frames(&mesh);
+        for vertex in mesh.vertices.iter() {
+            assert!((vertex.position.magnitude() - 1.5).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn test_icosphere() {
+        for subdivisions in 0..3 {
+            let mesh = icosphere(1.0, subdivisions);
+
+            assert_eq!(mesh.vertex_indices.len(), 3 * 20 * 4_usize.pow(subdivisions));
+            assert_closed_manifold(&mesh);
+            assert_outward_normals(&mesh, origin);
+            assert_unit_tangent_frames(&mesh);
+        }
+    }
+
+    #[test]
+    fn test_plane() {
+        let mesh = plane(2.0, 3.0, 4, 5);
+
+        assert_counts(&mesh, 5 * 6, 2 * 4 * 5);
+        assert_unit_tangent_frames(&mesh);
+        for triangle in mesh.vertex_indices.chunks_exact(3) {
+            let p0 = mesh.vertices[triangle[0] as usize].position;
+            let p1 = mesh.vertices[triangle[1] as usize].position;
+            let p2 = mesh.vertices[triangle[2] as usize].position;
+            assert!((p1 - p0).cross(&(p2 - p0)).y > 0.0);
+        }
+    }
+
+    #[test]
+    fn test_cylinder() {
+        let mesh = cylinder(1.0, 2.0, 12);
+
+        assert_counts(&mesh, 2 * 13 + 2 * 14, 2 * 12 + 2 * 12);
+        assert_closed_manifold(&mesh);
+        assert_outward_normals(&mesh, origin);
+        assert_unit_tangent_frames(&mesh);
+    }
+
+    #[test]
+    fn test_cone() {
+        let mesh = cone(1.0, 2.0, 12);
+
+        // The quads touching the apex lose their degenerate triangle.
+        assert_counts(&mesh, 2 * 13 + 14, 12 + 12);
+        assert_closed_manifold(&mesh);
+        assert_outward_normals(&mesh, origin);
+        assert_unit_tangent_frames(&mesh);
+    }
+
+    #[test]
+    fn test_torus() {
+        let mesh = torus(2.0, 0.5, 24, 12);
+        // The closest point on the circle running through the middle of the tube.
+        let ring = |point: &Vector3<f32>| Vector3::new(point.x, 0.0, point.z).normalize() * 2.0;
+
+        assert_counts(&mesh, 13 * 25, 2 * 24 * 12);
+        assert_closed_manifold(&mesh);
+        assert_outward_normals(&mesh, ring);
+        assert_unit_tangent_frames(&mesh);
+    }
+
+    #[test]
+    fn test_capsule() {
+        let mesh = capsule(0.5, 1.0, 16, 4);
+        // The closest point on the segment running through the middle of the capsule.
+        let axis = |point: &Vector3<f32>| Vector3::new(0.0, f32::max(-0.5, f32::min(0.5, point.y)), 0.0);
+
+        assert_counts(&mesh, 2 * 5 * 17, 2 * 16 * 9 - 2 * 16);
+        assert_closed_manifold(&mesh);
+        assert_outward_normals(&mesh, axis);
+        assert_unit_tangent_frames(&mesh);
+    }
+}
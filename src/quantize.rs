@@ -0,0 +1,740 @@
+use crate::backend::{
+    AttributeType,
+    VertexAttribute,
+    VertexLayout,
+};
+use crate::bounds::{
+    Aabb,
+};
+use crate::model::{
+    Mesh,
+    Model,
+    Vertex,
+    VertexStreams,
+};
+use cglinalg::{
+    Vector3,
+};
+use std::fmt;
+use std::mem;
+
+
+/// The layout vertices are stored in on the GPU.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum VertexFormat {
+    /// Every attribute as 32-bit floats, exactly as in `model::Vertex`.
+    Float32,
+    /// Positions as half floats relative to the mesh bounding box, octahedral
+    /// encoded normals and tangents, half float texture coordinates, 8-bit colors,
+    /// and the bitangent reconstructed from a sign bit.
+    PackedHalf,
+    /// Like `PackedHalf`, except that positions are normalized 16-bit integers
+    /// relative to the mesh bounding box, giving uniform precision across the box.
+    PackedSnorm16,
+}
+
+impl VertexFormat {
+    /// The size of one vertex in bytes with the optional attributes of `streams`.
+    pub fn vertex_size(self, streams: VertexStreams) -> usize {
+        attributes_end(&self.attributes(streams))
+    }
+
+    /// The layout of one vertex with the optional attributes of `streams`. The
+    /// optional attributes follow the ones every vertex has, colors first.
+    pub fn attributes(self, streams: VertexStreams) -> Vec<VertexAttribute> {
+        let (mut attributes, color_type, tex_coords2_type) = match self {
+            VertexFormat::Float32 => {
+                let attributes = Vertex::attributes().into_iter()
+                    .filter(|attribute| attribute.name != "aColor" && attribute.name != "aTexCoords2")
+                    .collect();
+                (attributes, AttributeType::Float, AttributeType::Float)
+            }
+            VertexFormat::PackedHalf | VertexFormat::PackedSnorm16 => {
+                let (position_type, position_normalized) = if self == VertexFormat::PackedSnorm16 {
+                    (AttributeType::Short, true)
+                } else {
+                    (AttributeType::HalfFloat, false)
+                };
+                let attributes = vec![
+                    VertexAttribute::new("aPos", 4, position_type, position_normalized, PACKED_POSITION_OFFSET),
+                    VertexAttribute::new("aNormal", 2, AttributeType::Short, true, PACKED_NORMAL_OFFSET),
+                    VertexAttribute::new("aTangent", 2, AttributeType::Short, true, PACKED_TANGENT_OFFSET),
+                    VertexAttribute::new("aTexCoords", 2, AttributeType::HalfFloat, false, PACKED_TEX_COORDS_OFFSET),
+                ];
+                (attributes, AttributeType::UnsignedByte, AttributeType::HalfFloat)
+            }
+        };
+        if streams.colors {
+            let offset = attributes_end(&attributes);
+            let normalized = color_type != AttributeType::Float;
+            attributes.push(VertexAttribute::new("aColor", 4, color_type, normalized, offset));
+        }
+        if streams.tex_coords2 {
+            let offset = attributes_end(&attributes);
+            attributes.push(VertexAttribute::new("aTexCoords2", 2, tex_coords2_type, false, offset));
+        }
+
+        attributes
+    }
+}
+
+/// The end of the last of a list of attributes laid out one after the other.
+#[inline]
+fn attributes_end(attributes: &[VertexAttribute]) -> usize {
+    attributes.last()
+        .map(|attribute| attribute.offset + attribute.components * attribute.attribute_type.size())
+        .unwrap_or(0)
+}
+
+/// The byte offsets of each attribute inside a packed vertex. Positions take four
+/// components so that the bitangent sign can ride along in the fourth one.
+pub const PACKED_POSITION_OFFSET: usize = 0;
+pub const PACKED_NORMAL_OFFSET: usize = 8;
+pub const PACKED_TANGENT_OFFSET: usize = 12;
+pub const PACKED_TEX_COORDS_OFFSET: usize = 16;
+
+/// The values of the optional attributes of vertices without them. Opaque white
+/// leaves the shaded color unchanged.
+pub const DEFAULT_COLOR_ATTRIBUTE: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+pub const DEFAULT_TEX_COORDS2_ATTRIBUTE: [f32; 4] = [0.0, 0.0, 0.0, 1.0];
+
+
+/// Convert a 32-bit float to the bit pattern of the nearest 16-bit half float,
+/// rounding ties to even.
+pub fn f32_to_f16(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x007f_ffff;
+
+    if exponent == 0xff {
+        // Infinity stays infinity, and NaN stays a quiet NaN.
+        let nan_bit = if mantissa != 0 { 0x0200 } else { 0 };
+        return sign | 0x7c00 | nan_bit;
+    }
+
+    let half_exponent = exponent - 127 + 15;
+    if half_exponent >= 0x1f {
+        return sign | 0x7c00;
+    }
+
+    if half_exponent <= 0 {
+        // The value is a half float subnormal, or rounds down to zero.
+        if half_exponent < -10 {
+            return sign;
+        }
+        let full_mantissa = mantissa | 0x0080_0000;
+        let shift = (14 - half_exponent) as u32;
+        let half_mantissa = full_mantissa >> shift;
+        let remainder = full_mantissa & ((1 << shift) - 1);
+        let halfway = 1 << (shift - 1);
+        let rounded = if remainder > halfway || (remainder == halfway && (half_mantissa & 1) != 0) {
+            half_mantissa + 1
+        } else {
+            half_mantissa
+        };
+
+        return sign | rounded as u16;
+    }
+
+    let half = ((half_exponent as u32) << 10) | (mantissa >> 13);
+    let remainder = mantissa & 0x1fff;
+    // A carry out of the mantissa correctly rolls over into the exponent.
+    let rounded = if remainder > 0x1000 || (remainder == 0x1000 && (half & 1) != 0) {
+        half + 1
+    } else {
+        half
+    };
+
+    sign | rounded as u16
+}
+
+/// Convert a float in the range `[-1, 1]` to a normalized signed 16-bit integer.
+#[inline]
+pub fn f32_to_snorm16(value: f32) -> i16 {
+    let clamped = f32::max(-1.0, f32::min(1.0, value));
+
+    f32::round(clamped * 32767.0) as i16
+}
+
+/// Convert a float in the range `[0, 1]` to a normalized unsigned 8-bit integer.
+#[inline]
+pub fn f32_to_unorm8(value: f32) -> u8 {
+    let clamped = f32::max(0.0, f32::min(1.0, value));
+
+    f32::round(clamped * 255.0) as u8
+}
+
+#[inline]
+fn sign_not_zero(value: f32) -> f32 {
+    if value >= 0.0 { 1.0 } else { -1.0 }
+}
+
+/// Encode a unit vector as a point on the octahedral map, stored as two normalized
+/// signed 16-bit integers.
+pub fn octahedral_encode(vector: &Vector3<f32>) -> [i16; 2] {
+    let l1_norm = vector.x.abs() + vector.y.abs() + vector.z.abs();
+    if l1_norm <= 0.0 || !l1_norm.is_finite() {
+        return [0, 0];
+    }
+
+    let u = vector.x / l1_norm;
+    let v = vector.y / l1_norm;
+    let (u, v) = if vector.z < 0.0 {
+        ((1.0 - v.abs()) * sign_not_zero(u), (1.0 - u.abs()) * sign_not_zero(v))
+    } else {
+        (u, v)
+    };
+
+    [f32_to_snorm16(u), f32_to_snorm16(v)]
+}
+
+/// Compute the handedness of a vertex's tangent frame. The bitangent is reconstructed
+/// in the shader as `sign * cross(normal, tangent)`.
+#[inline]
+fn bitangent_sign(vertex: &Vertex) -> f32 {
+    sign_not_zero(vertex.normal.cross(&vertex.tangent).dot(&vertex.bitangent))
+}
+
+#[inline]
+fn write_u16(data: &mut Vec<u8>, value: u16) {
+    data.extend_from_slice(&value.to_le_bytes());
+}
+
+#[inline]
+fn write_i16(data: &mut Vec<u8>, value: i16) {
+    data.extend_from_slice(&value.to_le_bytes());
+}
+
+/// The vertex data of a mesh packed into one of the vertex formats.
+#[derive(Clone, Debug)]
+pub struct PackedMesh {
+    pub name: String,
+    pub format: VertexFormat,
+    /// The optional attributes stored in the vertex data.
+    pub streams: VertexStreams,
+    pub data: Vec<u8>,
+    pub vertex_count: usize,
+    pub vertex_indices: Vec<u32>,
+    /// Decoded positions are `position_offset + position_scale * encoded_position`.
+    pub position_offset: Vector3<f32>,
+    pub position_scale: Vector3<f32>,
+}
+
+impl PackedMesh {
+    /// The size of one vertex in bytes.
+    #[inline]
+    pub fn stride(&self) -> usize {
+        self.format.vertex_size(self.streams)
+    }
+
+    /// The layout of one vertex of the packed vertex data.
+    #[inline]
+    pub fn attributes(&self) -> Vec<VertexAttribute> {
+        self.format.attributes(self.streams)
+    }
+
+    /// The constant values of the optional attributes missing from the vertex data.
+    pub fn constant_attributes(&self) -> Vec<(&'static str, [f32; 4])> {
+        let mut constant_attributes = vec![];
+        if !self.streams.colors {
+            constant_attributes.push(("aColor", DEFAULT_COLOR_ATTRIBUTE));
+        }
+        if !self.streams.tex_coords2 {
+            constant_attributes.push(("aTexCoords2", DEFAULT_TEX_COORDS2_ATTRIBUTE));
+        }
+
+        constant_attributes
+    }
+
+    /// The number of bytes the packed vertex data saves over `model::Vertex`.
+    #[inline]
+    pub fn memory_saving(&self) -> usize {
+        self.vertex_count * mem::size_of::<Vertex>() - self.data.len()
+    }
+}
+
+/// Pack the vertices of a mesh into a vertex format, leaving out the optional
+/// attributes the mesh does not use. Positions in the compressed formats are
+/// encoded relative to the bounding box of the mesh so that the full precision of
+/// the encoding is spent on the space the mesh actually occupies.
+#[inline]
+pub fn pack_mesh(mesh: &Mesh, format: VertexFormat) -> PackedMesh {
+    pack_vertices(&mesh.name, &mesh.vertices, &mesh.vertex_indices, format)
+}
+
+/// Pack a list of indexed vertices into a vertex format, like `pack_mesh`.
+pub fn pack_vertices(
+    name: &str,
+    vertices: &[Vertex],
+    vertex_indices: &[u32],
+    format: VertexFormat) -> PackedMesh
+{
+    let streams = VertexStreams::of(vertices.iter());
+    let aabb = Aabb::from_points(vertices.iter().map(|vertex| &vertex.position));
+    let aabb = if aabb.is_empty() {
+        Aabb::new(Vector3::zero(), Vector3::zero())
+    } else {
+        aabb
+    };
+    let position_offset = aabb.center();
+    let half_extents = aabb.extents() * 0.5;
+    let position_scale = Vector3::new(
+        if half_extents.x > 0.0 { half_extents.x } else { 1.0 },
+        if half_extents.y > 0.0 { half_extents.y } else { 1.0 },
+        if half_extents.z > 0.0 { half_extents.z } else { 1.0 },
+    );
+
+    let mut data = Vec::with_capacity(vertices.len() * format.vertex_size(streams));
+    for vertex in vertices.iter() {
+        match format {
+            VertexFormat::Float32 => {
+                for value in [
+                    vertex.position.x, vertex.position.y, vertex.position.z,
+                    vertex.normal.x, vertex.normal.y, vertex.normal.z,
+                    vertex.tex_coords.x, vertex.tex_coords.y,
+                    vertex.tangent.x, vertex.tangent.y, vertex.tangent.z,
+                    vertex.bitangent.x, vertex.bitangent.y, vertex.bitangent.z,
+                ].iter() {
+                    data.extend_from_slice(&value.to_le_bytes());
+                }
+                if streams.colors {
+                    for value in [vertex.color.x, vertex.color.y, vertex.color.z, vertex.color.w].iter() {
+                        data.extend_from_slice(&value.to_le_bytes());
+                    }
+                }
+                if streams.tex_coords2 {
+                    data.extend_from_slice(&vertex.tex_coords2.x.to_le_bytes());
+                    data.extend_from_slice(&vertex.tex_coords2.y.to_le_bytes());
+                }
+            }
+            VertexFormat::PackedHalf | VertexFormat::PackedSnorm16 => {
+                let relative = [
+                    (vertex.position.x - position_offset.x) / position_scale.x,
+                    (vertex.position.y - position_offset.y) / position_scale.y,
+                    (vertex.position.z - position_offset.z) / position_scale.z,
+                    bitangent_sign(vertex),
+                ];
+                for value in relative.iter() {
+                    if format == VertexFormat::PackedHalf {
+                        write_u16(&mut data, f32_to_f16(*value));
+                    } else {
+                        write_i16(&mut data, f32_to_snorm16(*value));
+                    }
+                }
+                let normal = octahedral_encode(&vertex.normal);
+                write_i16(&mut data, normal[0]);
+                write_i16(&mut data, normal[1]);
+                let tangent = octahedral_encode(&vertex.tangent);
+                write_i16(&mut data, tangent[0]);
+                write_i16(&mut data, tangent[1]);
+                write_u16(&mut data, f32_to_f16(vertex.tex_coords.x));
+                write_u16(&mut data, f32_to_f16(vertex.tex_coords.y));
+                if streams.colors {
+                    data.push(f32_to_unorm8(vertex.color.x));
+                    data.push(f32_to_unorm8(vertex.color.y));
+                    data.push(f32_to_unorm8(vertex.color.z));
+                    data.push(f32_to_unorm8(vertex.color.w));
+                }
+                if streams.tex_coords2 {
+                    write_u16(&mut data, f32_to_f16(vertex.tex_coords2.x));
+                    write_u16(&mut data, f32_to_f16(vertex.tex_coords2.y));
+                }
+            }
+        }
+    }
+
+    let (position_offset, position_scale) = match format {
+        VertexFormat::Float32 => (Vector3::zero(), Vector3::new(1.0, 1.0, 1.0)),
+        _ => (position_offset, position_scale),
+    };
+
+    PackedMesh {
+        name: name.to_owned(),
+        format: format,
+        streams: streams,
+        data: data,
+        vertex_count: vertices.len(),
+        vertex_indices: vertex_indices.to_vec(),
+        position_offset: position_offset,
+        position_scale: position_scale,
+    }
+}
+
+/// The vertex memory used by a model in its selected vertex format compared to
+/// `model::Vertex` with every attribute.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct VertexMemoryReport {
+    pub format: VertexFormat,
+    pub vertex_count: usize,
+    pub unpacked_bytes: usize,
+    pub packed_bytes: usize,
+}
+
+impl VertexMemoryReport {
+    pub fn saved_bytes(&self) -> usize {
+        self.unpacked_bytes - self.packed_bytes
+    }
+}
+
+impl fmt::Display for VertexMemoryReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let percent = if self.unpacked_bytes > 0 {
+            100.0 * self.saved_bytes() as f64 / self.unpacked_bytes as f64
+        } else {
+            0.0
+        };
+        write!(f,
+            "{} vertices in format {:?}: {} bytes instead of {} bytes, saving {} bytes ({:.1}%)",
+            self.vertex_count, self.format, self.packed_bytes, self.unpacked_bytes,
+            self.saved_bytes(), percent
+        )
+    }
+}
+
+/// Compute how much vertex memory a model saves by using its selected vertex format
+/// and leaving out the optional attributes its meshes do not use.
+pub fn vertex_memory_report(model: &Model) -> VertexMemoryReport {
+    let vertex_count: usize = model.meshes.iter()
+        .map(|mesh| mesh.vertices.len())
+        .sum();
+    let packed_bytes: usize = model.meshes.iter()
+        .map(|mesh| mesh.vertices.len() * model.vertex_format.vertex_size(mesh.streams()))
+        .sum();
+
+    VertexMemoryReport {
+        format: model.vertex_format,
+        vertex_count: vertex_count,
+        unpacked_bytes: vertex_count * mem::size_of::<Vertex>(),
+        packed_bytes: packed_bytes,
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives;
+    use cglinalg::{
+        Magnitude,
+        Vector2,
+        Vector4,
+    };
+
+
+    const FORMATS: [VertexFormat; 3] = [
+        VertexFormat::Float32,
+        VertexFormat::PackedHalf,
+        VertexFormat::PackedSnorm16,
+    ];
+
+    /// Convert the bit pattern of a 16-bit half float to a 32-bit float.
+    fn f16_to_f32(half: u16) -> f32 {
+        let sign = if half & 0x8000 != 0 { -1.0 } else { 1.0 };
+        let exponent = ((half >> 10) & 0x1f) as i32;
+        let mantissa = (half & 0x03ff) as f32;
+        match exponent {
+            0 => sign * mantissa * f32::powi(2.0, -24),
+            0x1f if mantissa == 0.0 => sign * f32::INFINITY,
+            0x1f => f32::NAN,
+            _ => sign * (1.0 + mantissa / 1024.0) * f32::powi(2.0, exponent - 15),
+        }
+    }
+
+    fn snorm16_to_f32(value: i16) -> f32 {
+        f32::max(value as f32 / 32767.0, -1.0)
+    }
+
+    /// Decode a point on the octahedral map the same way the vertex shader does.
+    fn octahedral_decode(encoded: [i16; 2]) -> Vector3<f32> {
+        let mut vector = Vector3::new(snorm16_to_f32(encoded[0]), snorm16_to_f32(encoded[1]), 0.0);
+        vector.z = 1.0 - vector.x.abs() - vector.y.abs();
+        let t = f32::max(-vector.z, 0.0);
+        vector.x += if vector.x >= 0.0 { -t } else { t };
+        vector.y += if vector.y >= 0.0 { -t } else { t };
+
+        vector.normalize()
+    }
+
+    fn read_u16(data: &[u8], offset: usize) -> u16 {
+        u16::from_le_bytes([data[offset], data[offset + 1]])
+    }
+
+    fn read_i16(data: &[u8], offset: usize) -> i16 {
+        i16::from_le_bytes([data[offset], data[offset + 1]])
+    }
+
+    fn read_f32(data: &[u8], offset: usize) -> f32 {
+        f32::from_le_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]])
+    }
+
+    /// Unit vectors spread evenly over the sphere on a Fibonacci lattice, along
+    /// with the axes and the diagonals where the octahedral map folds.
+    fn sphere_of_normals(count: usize) -> Vec<Vector3<f32>> {
+        let mut normals = vec![
+            Vector3::unit_x(), -Vector3::unit_x(),
+            Vector3::unit_y(), -Vector3::unit_y(),
+            Vector3::unit_z(), -Vector3::unit_z(),
+            Vector3::new(1.0, 1.0, -1.0).normalize(),
+            Vector3::new(-1.0, 1.0, -1.0).normalize(),
+            Vector3::new(1.0, -1.0, 0.0).normalize(),
+        ];
+        let golden_angle = std::f32::consts::PI * (3.0 - f32::sqrt(5.0));
+        for i in 0..count {
+            let z = 1.0 - 2.0 * (i as f32 + 0.5) / count as f32;
+            let radius = f32::sqrt(1.0 - z * z);
+            let angle = golden_angle * i as f32;
+            normals.push(Vector3::new(radius * f32::cos(angle), radius * f32::sin(angle), z));
+        }
+
+        normals
+    }
+
+    fn frame(bitangent: Vector3<f32>) -> Vertex {
+        Vertex {
+            position: Vector3::zero(),
+            normal: Vector3::unit_z(),
+            tex_coords: Vector2::zero(),
+            tangent: Vector3::unit_x(),
+            bitangent: bitangent,
+            color: Vertex::default_color(),
+            tex_coords2: Vector2::zero(),
+        }
+    }
+
+    fn all_streams() -> Vec<VertexStreams> {
+        let mut all_streams = vec![];
+        for &colors in [false, true].iter() {
+            for &tex_coords2 in [false, true].iter() {
+                all_streams.push(VertexStreams { colors: colors, tex_coords2: tex_coords2 });
+            }
+        }
+
+        all_streams
+    }
+
+    #[test]
+    fn test_attributes_cover_vertex() {
+        for &format in FORMATS.iter() {
+            for &streams in all_streams().iter() {
+                let mut end = 0;
+                for attribute in format.attributes(streams).iter() {
+                    assert_eq!(attribute.offset, end, "{:?} {:?}: `{}` is misplaced", format, streams, attribute.name);
+                    end = attribute.offset + attribute.components * attribute.attribute_type.size();
+                }
+
+                assert_eq!(end, format.vertex_size(streams));
+            }
+        }
+    }
+
+    #[test]
+    fn test_float32_with_every_stream_is_vertex() {
+        let streams = VertexStreams { colors: true, tex_coords2: true };
+
+        assert_eq!(VertexFormat::Float32.attributes(streams), Vertex::attributes());
+        assert_eq!(VertexFormat::Float32.vertex_size(streams), Vertex::stride());
+    }
+
+    #[test]
+    fn test_pack_mesh_leaves_out_missing_streams() {
+        let mut mesh = primitives::cube(1.0);
+        for &format in FORMATS.iter() {
+            let packed_mesh = pack_mesh(&mesh, format);
+
+            assert_eq!(packed_mesh.streams, VertexStreams::default());
+            assert_eq!(packed_mesh.data.len(), mesh.vertices.len() * packed_mesh.stride());
+            assert!(packed_mesh.attributes().iter().all(|attribute| {
+                attribute.name != "aColor" && attribute.name != "aTexCoords2"
+            }));
+            assert_eq!(packed_mesh.constant_attributes(), vec![
+                ("aColor", DEFAULT_COLOR_ATTRIBUTE),
+                ("aTexCoords2", DEFAULT_TEX_COORDS2_ATTRIBUTE),
+            ]);
+        }
+
+        mesh.vertices[0].color = Vector4::new(1.0, 0.0, 0.0, 1.0);
+        for &format in FORMATS.iter() {
+            let packed_mesh = pack_mesh(&mesh, format);
+
+            assert_eq!(packed_mesh.streams, VertexStreams { colors: true, tex_coords2: false });
+            assert_eq!(packed_mesh.data.len(), mesh.vertices.len() * packed_mesh.stride());
+            assert_eq!(packed_mesh.constant_attributes(), vec![("aTexCoords2", DEFAULT_TEX_COORDS2_ATTRIBUTE)]);
+        }
+    }
+
+    #[test]
+    fn test_f32_to_f16_values() {
+        assert_eq!(f32_to_f16(0.0), 0x0000);
+        assert_eq!(f32_to_f16(-0.0), 0x8000);
+        assert_eq!(f32_to_f16(1.0), 0x3c00);
+        assert_eq!(f32_to_f16(-2.0), 0xc000);
+        assert_eq!(f32_to_f16(65504.0), 0x7bff);
+        // Past the largest half float, values round to infinity.
+        assert_eq!(f32_to_f16(65520.0), 0x7c00);
+        assert_eq!(f32_to_f16(1.0e6), 0x7c00);
+        assert_eq!(f32_to_f16(-1.0e6), 0xfc00);
+        // Ties round to even.
+        assert_eq!(f32_to_f16(1.0 + f32::powi(2.0, -11)), 0x3c00);
+        assert_eq!(f32_to_f16(1.0 + 3.0 * f32::powi(2.0, -11)), 0x3c02);
+    }
+
+    #[test]
+    fn test_f32_to_f16_subnormals() {
+        let smallest = f32::powi(2.0, -24);
+
+        assert_eq!(f32_to_f16(smallest), 0x0001);
+        assert_eq!(f32_to_f16(-smallest), 0x8001);
+        assert_eq!(f32_to_f16(1023.0 * smallest), 0x03ff);
+        // The smallest normal half float.
+        assert_eq!(f32_to_f16(1024.0 * smallest), 0x0400);
+        // Halfway to the smallest subnormal rounds to even, which is zero.
+        assert_eq!(f32_to_f16(0.5 * smallest), 0x0000);
+        assert_eq!(f32_to_f16(0.75 * smallest), 0x0001);
+        assert_eq!(f32_to_f16(1.5 * smallest), 0x0002);
+        assert_eq!(f32_to_f16(0.25 * smallest), 0x0000);
+        assert_eq!(f32_to_f16(f32::MIN_POSITIVE), 0x0000);
+    }
+
+    #[test]
+    fn test_f32_to_f16_infinity_and_nan() {
+        assert_eq!(f32_to_f16(f32::INFINITY), 0x7c00);
+        assert_eq!(f32_to_f16(f32::NEG_INFINITY), 0xfc00);
+
+        let nan = f32_to_f16(f32::NAN);
+        assert_eq!(nan & 0x7c00, 0x7c00);
+        assert_ne!(nan & 0x03ff, 0);
+        assert!(f16_to_f32(nan).is_nan());
+    }
+
+    #[test]
+    fn test_f32_to_f16_round_trip() {
+        // Every half float other than NaN converts to a float and back unchanged.
+        for half in 0..=u16::MAX {
+            if half & 0x7c00 == 0x7c00 && half & 0x03ff != 0 {
+                continue;
+            }
+
+            assert_eq!(f32_to_f16(f16_to_f32(half)), half, "half float {:#06x}", half);
+        }
+    }
+
+    #[test]
+    fn test_f32_to_f16_relative_error() {
+        let mut value = 1.0e-4_f32;
+        while value < 65504.0 {
+            let result = f16_to_f32(f32_to_f16(value));
+
+            assert!((result - value).abs() <= value * f32::powi(2.0, -11), "{} became {}", value, result);
+            value *= 1.0137;
+        }
+    }
+
+    #[test]
+    fn test_octahedral_round_trip_error() {
+        for normal in sphere_of_normals(10000).iter() {
+            let result = octahedral_decode(octahedral_encode(normal));
+
+            assert!((result - normal).magnitude() <= 1.0e-4, "{:?} became {:?}", normal, result);
+        }
+    }
+
+    #[test]
+    fn test_octahedral_encode_degenerate() {
+        assert_eq!(octahedral_encode(&Vector3::zero()), [0, 0]);
+        assert_eq!(octahedral_encode(&Vector3::new(f32::NAN, 0.0, 1.0)), [0, 0]);
+        assert_eq!(octahedral_encode(&Vector3::new(f32::INFINITY, 0.0, 0.0)), [0, 0]);
+    }
+
+    #[test]
+    fn test_bitangent_sign() {
+        assert_eq!(bitangent_sign(&frame(Vector3::unit_y())), 1.0);
+        assert_eq!(bitangent_sign(&frame(-Vector3::unit_y())), -1.0);
+        // A frame without a bitangent counts as right handed.
+        assert_eq!(bitangent_sign(&frame(Vector3::zero())), 1.0);
+    }
+
+    #[test]
+    fn test_pack_mesh_stores_bitangent_sign() {
+        let vertices = vec![frame(Vector3::unit_y()), frame(-Vector3::unit_y())];
+        let half = pack_vertices("frames", &vertices, &[], VertexFormat::PackedHalf);
+        let snorm = pack_vertices("frames", &vertices, &[], VertexFormat::PackedSnorm16);
+        let sign_offset = PACKED_POSITION_OFFSET + 6;
+
+        assert_eq!(read_u16(&half.data, sign_offset), 0x3c00);
+        assert_eq!(read_u16(&half.data, half.stride() + sign_offset), 0xbc00);
+        assert_eq!(read_i16(&snorm.data, sign_offset), 32767);
+        assert_eq!(read_i16(&snorm.data, snorm.stride() + sign_offset), -32767);
+    }
+
+    #[test]
+    fn test_pack_mesh_float32() {
+        let mesh = primitives::cube(2.0);
+        let packed_mesh = pack_mesh(&mesh, VertexFormat::Float32);
+
+        assert_eq!(packed_mesh.vertex_count, mesh.vertices.len());
+        assert_eq!(packed_mesh.vertex_indices, mesh.vertex_indices);
+        assert_eq!(packed_mesh.position_offset, Vector3::zero());
+        assert_eq!(packed_mesh.position_scale, Vector3::new(1.0, 1.0, 1.0));
+        for (i, vertex) in mesh.vertices.iter().enumerate() {
+            let base = i * packed_mesh.stride();
+            let position = Vector3::new(
+                read_f32(&packed_mesh.data, base),
+                read_f32(&packed_mesh.data, base + 4),
+                read_f32(&packed_mesh.data, base + 8),
+            );
+
+            assert_eq!(position, vertex.position);
+        }
+    }
+
+    #[test]
+    fn test_pack_mesh_decodes_to_mesh() {
+        let mesh = primitives::uv_sphere(3.0, 16, 8);
+        for &format in [VertexFormat::PackedHalf, VertexFormat::PackedSnorm16].iter() {
+            let packed_mesh = pack_mesh(&mesh, format);
+
+            assert_eq!(packed_mesh.vertex_indices, mesh.vertex_indices);
+            assert_eq!(packed_mesh.data.len(), mesh.vertices.len() * packed_mesh.stride());
+            for (i, vertex) in mesh.vertices.iter().enumerate() {
+                let base = i * packed_mesh.stride();
+                let decode = |offset: usize| {
+                    if format == VertexFormat::PackedHalf {
+                        f16_to_f32(read_u16(&packed_mesh.data, offset))
+                    } else {
+                        snorm16_to_f32(read_i16(&packed_mesh.data, offset))
+                    }
+                };
+                let encoded = Vector3::new(decode(base), decode(base + 2), decode(base + 4));
+                let position = packed_mesh.position_offset + Vector3::new(
+                    packed_mesh.position_scale.x * encoded.x,
+                    packed_mesh.position_scale.y * encoded.y,
+                    packed_mesh.position_scale.z * encoded.z,
+                );
+                let normal = octahedral_decode([
+                    read_i16(&packed_mesh.data, base + PACKED_NORMAL_OFFSET),
+                    read_i16(&packed_mesh.data, base + PACKED_NORMAL_OFFSET + 2),
+                ]);
+                let tex_coords = Vector2::new(
+                    f16_to_f32(read_u16(&packed_mesh.data, base + PACKED_TEX_COORDS_OFFSET)),
+                    f16_to_f32(read_u16(&packed_mesh.data, base + PACKED_TEX_COORDS_OFFSET + 2)),
+                );
+
+                assert!(
+                    (position - vertex.position).magnitude() <= 3.0e-3,
+                    "{:?}: {:?} became {:?}", format, vertex.position, position
+                );
+                assert!((normal - vertex.normal.normalize()).magnitude() <= 1.0e-4);
+                assert!((tex_coords - vertex.tex_coords).magnitude() <= 1.0e-3);
+            }
+        }
+    }
+
+    #[test]
+    fn test_pack_mesh_flat_axis() {
+        let packed_mesh = pack_mesh(&primitives::plane(4.0, 2.0, 2, 2), VertexFormat::PackedSnorm16);
+
+        assert_eq!(packed_mesh.position_offset, Vector3::zero());
+        assert_eq!(packed_mesh.position_scale, Vector3::new(2.0, 1.0, 1.0));
+    }
+}
@@ -0,0 +1,269 @@
+use crate::gl;
+use crate::backend;
+use crate::backend::{
+    OpenGLContext,
+    ShaderHandle,
+    Texture2D,
+    Uniform,
+    TextureImage2D,
+    VertexBuffers,
+    ColorType,
+};
+use crate::model::{
+    Mesh,
+    Model,
+    TextureKind,
+    VertexStreams,
+};
+use crate::quantize;
+use crate::quantize::{
+    PackedMesh,
+    VertexFormat,
+};
+use cglinalg::{
+    Matrix4,
+    Vector3,
+};
+use gl::types::{
+    GLfloat,
+    GLint,
+};
+use log::{
+    info,
+};
+use std::collections::{
+    HashMap,
+};
+use std::ptr;
+
+
+/// Every kind of texture a mesh can have, in the order of the texture units they
+/// are bound to.
+pub const TEXTURE_KINDS: [TextureKind; 5] = [
+    TextureKind::Ambient,
+    TextureKind::Diffuse,
+    TextureKind::Specular,
+    TextureKind::Bump,
+    TextureKind::Emission,
+];
+
+/// The name of the sampler uniform a kind of texture is bound to.
+pub fn sampler_name(kind: TextureKind) -> &'static str {
+    match kind {
+        TextureKind::Ambient => "texture_ambient1",
+        TextureKind::Diffuse => "texture_diffuse1",
+        TextureKind::Specular => "texture_specular1",
+        TextureKind::Bump => "texture_bump1",
+        TextureKind::Emission => "texture_emission1",
+    }
+}
+
+/// The texture unit a kind of texture is bound to.
+#[inline]
+pub fn texture_unit(kind: TextureKind) -> u32 {
+    TEXTURE_KINDS.iter().position(|&other| other == kind).unwrap() as u32
+}
+
+/// The specular exponent of the Blinn-Phong highlights of a model.
+pub const DEFAULT_SHININESS: f32 = 32.0;
+
+/// Clear the color and depth buffers of the default framebuffer.
+pub fn clear_frame(clear_color: &[f32; 4], clear_depth: &[f32; 4]) {
+    unsafe {
+        gl::ClearBufferfv(gl::COLOR, 0, &clear_color[0] as *const GLfloat);
+        gl::ClearBufferfv(gl::DEPTH, 0, &clear_depth[0] as *const GLfloat);
+    }
+}
+
+/// The GPU objects holding one mesh of a model.
+#[derive(Debug)]
+pub struct GpuMesh {
+    pub buffers: VertexBuffers,
+    /// The textures of the mesh, as indices into the textures of the renderer.
+    pub texture_indices: HashMap<TextureKind, u32>,
+    /// The optional attributes in the vertex buffer of the mesh.
+    pub streams: VertexStreams,
+    /// Decoded positions are `position_offset + position_scale * encoded_position`.
+    pub position_offset: Vector3<f32>,
+    pub position_scale: Vector3<f32>,
+}
+
+/// Send packed vertex data to the GPU. The optional attributes missing from the
+/// vertex data get constant values, so a shader reading them still sees defaults.
+pub fn send_to_gpu_packed_mesh(
+    context: &OpenGLContext,
+    shader: &ShaderHandle,
+    packed_mesh: &PackedMesh) -> VertexBuffers
+{
+    let mut buffers = backend::send_to_gpu_vertex_data(
+        context,
+        shader,
+        &packed_mesh.data,
+        packed_mesh.stride(),
+        &packed_mesh.attributes(),
+        &packed_mesh.vertex_indices
+    );
+    for &(name, value) in packed_mesh.constant_attributes().iter() {
+        buffers.set_constant_attribute(shader, name, value);
+    }
+
+    buffers
+}
+
+/// Send a mesh to the GPU in a vertex format. The shader must be compiled for the
+/// same vertex format.
+pub fn send_to_gpu_mesh(
+    context: &OpenGLContext,
+    shader: &ShaderHandle,
+    mesh: &Mesh,
+    vertex_format: VertexFormat) -> GpuMesh
+{
+    let packed_mesh = quantize::pack_mesh(mesh, vertex_format);
+
+    GpuMesh {
+        buffers: send_to_gpu_packed_mesh(context, shader, &packed_mesh),
+        texture_indices: mesh.texture_indices.clone(),
+        streams: packed_mesh.streams,
+        position_offset: packed_mesh.position_offset,
+        position_scale: packed_mesh.position_scale,
+    }
+}
+
+/// Create a one pixel texture of a single color, to sample from in place of the
+/// textures a mesh does not have.
+fn send_to_gpu_solid_texture(context: &OpenGLContext, color: [u8; 4]) -> Result<Texture2D, String> {
+    let image = TextureImage2D::new(1, 1, ColorType::Rgba8, 4, color.to_vec());
+
+    backend::send_to_gpu_texture(context, &image, gl::REPEAT)
+}
+
+/// Draws every mesh of a model with its textures. The renderer owns the GPU
+/// objects of the model, and frees them when dropped.
+pub struct ModelRenderer {
+    shader: ShaderHandle,
+    /// The meshes of every level of detail of the model. Level zero is the original
+    /// model.
+    lods: Vec<Vec<GpuMesh>>,
+    textures: Vec<Texture2D>,
+    /// Bound in place of missing ambient, diffuse and bump maps. White leaves the
+    /// shading unchanged.
+    white_texture: Texture2D,
+    /// Bound in place of missing specular and emission maps, so the mesh has no
+    /// highlights and does not glow.
+    black_texture: Texture2D,
+    model_uniform: Uniform<Matrix4<f32>>,
+    /// Whether the mesh being drawn has second texture coordinates.
+    has_tex_coords2_uniform: Uniform<bool>,
+    /// The offset and scale uniforms of a shader that reads packed positions
+    /// relative to each mesh's bounds.
+    position_uniforms: Option<(Uniform<Vector3<f32>>, Uniform<Vector3<f32>>)>,
+}
+
+impl ModelRenderer {
+    /// Send every mesh of every level of detail and every texture of a model to the
+    /// GPU. The shader must be compiled for the vertex format of the model.
+    pub fn new(context: &OpenGLContext, shader: ShaderHandle, model: &Model) -> Result<ModelRenderer, String> {
+        let lods: Vec<Vec<GpuMesh>> = (0..(model.lods.len() + 1))
+            .map(|level| {
+                model.lod_meshes(level).iter()
+                    .map(|mesh| send_to_gpu_mesh(context, &shader, mesh, model.vertex_format))
+                    .collect()
+            })
+            .collect();
+        let mut textures = Vec::with_capacity(model.textures_loaded.len());
+        for texture in model.textures_loaded.iter() {
+            let gpu_texture = backend::send_to_gpu_texture(context, &texture.data, gl::REPEAT)?;
+            info!("Uploaded {:?} texture \"{}\" with id {}", texture.kind, texture.name, gpu_texture.id());
+            textures.push(gpu_texture);
+        }
+        let white_texture = send_to_gpu_solid_texture(context, [255, 255, 255, 255])?;
+        let black_texture = send_to_gpu_solid_texture(context, [0, 0, 0, 255])?;
+
+        // The sampler uniforms never change, so assign their texture units once.
+        shader.use_program();
+        for &kind in TEXTURE_KINDS.iter() {
+            if let Some(location) = shader.find_uniform_location(sampler_name(kind)) {
+                unsafe {
+                    gl::Uniform1i(location, texture_unit(kind) as GLint);
+                }
+            }
+        }
+        shader.set_float("material_shininess", DEFAULT_SHININESS);
+
+        let position_uniforms = if model.vertex_format != VertexFormat::Float32 {
+            Some((shader.uniform("position_offset"), shader.uniform("position_scale")))
+        } else {
+            None
+        };
+
+        Ok(ModelRenderer {
+            model_uniform: shader.uniform("model"),
+            has_tex_coords2_uniform: shader.uniform("has_tex_coords2"),
+            position_uniforms: position_uniforms,
+            shader: shader,
+            lods: lods,
+            textures: textures,
+            white_texture: white_texture,
+            black_texture: black_texture,
+        })
+    }
+
+    #[inline]
+    pub fn shader(&self) -> &ShaderHandle {
+        &self.shader
+    }
+
+    /// The meshes of a level of detail of the model. Levels past the last one give
+    /// the least detailed level.
+    #[inline]
+    pub fn meshes(&self, level: usize) -> &[GpuMesh] {
+        &self.lods[usize::min(level, self.lods.len() - 1)]
+    }
+
+    /// The number of levels of detail, counting the original model.
+    #[inline]
+    pub fn lod_count(&self) -> usize {
+        self.lods.len()
+    }
+
+    /// Bind the textures of a mesh to the texture units of their kinds.
+    fn bind_textures(&self, mesh: &GpuMesh) {
+        for &kind in TEXTURE_KINDS.iter() {
+            let default_texture = match kind {
+                TextureKind::Specular | TextureKind::Emission => &self.black_texture,
+                TextureKind::Ambient | TextureKind::Diffuse | TextureKind::Bump => &self.white_texture,
+            };
+            let texture = mesh.texture_indices.get(&kind)
+                .and_then(|&index| self.textures.get(index as usize))
+                .unwrap_or(default_texture);
+            texture.bind(texture_unit(kind));
+        }
+    }
+
+    /// Draw every mesh of a level of detail of the model, as chosen by
+    /// `Model::select_lod`. The view and projection come from the shared camera
+    /// uniform block.
+    pub fn draw(&self, model_mat: &Matrix4<f32>, level: usize) {
+        self.shader.use_program();
+        self.model_uniform.set(model_mat);
+        for mesh in self.meshes(level).iter() {
+            if mesh.buffers.index_count == 0 {
+                continue;
+            }
+            if let Some((offset_uniform, scale_uniform)) = self.position_uniforms.as_ref() {
+                offset_uniform.set(&mesh.position_offset);
+                scale_uniform.set(&mesh.position_scale);
+            }
+            self.has_tex_coords2_uniform.set(&mesh.streams.tex_coords2);
+            self.bind_textures(mesh);
+            mesh.buffers.bind();
+            unsafe {
+                gl::DrawElements(gl::TRIANGLES, mesh.buffers.index_count as GLint, gl::UNSIGNED_INT, ptr::null());
+            }
+        }
+        unsafe {
+            gl::BindVertexArray(0);
+            gl::ActiveTexture(gl::TEXTURE0);
+        }
+    }
+}
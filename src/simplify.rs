@@ -0,0 +1,695 @@
+use crate::geometry::{
+    VectorKey,
+    vector_key,
+};
+use crate::model::{
+    Mesh,
+    Model,
+    ModelLod,
+};
+use cglinalg::{
+    Magnitude,
+    Vector3,
+};
+use std::cmp::{
+    Ordering,
+};
+use std::collections::{
+    BinaryHeap,
+    HashMap,
+};
+
+
+/// Collapses that would turn a vertex normal by more than this (as a cosine) are
+/// rejected so that hard shading features survive simplification.
+const NORMAL_COLLAPSE_THRESHOLD: f32 = 0.5;
+
+/// The stopping criteria for mesh simplification. Simplification stops as soon as
+/// either the triangle count reaches the target, or the next edge collapse would
+/// introduce an error larger than `max_error`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SimplifyOptions {
+    pub target_triangle_count: usize,
+    /// The largest acceptable distance, in model units, between the simplified
+    /// surface and the original surface.
+    pub max_error: f32,
+}
+
+impl SimplifyOptions {
+    pub fn new(target_triangle_count: usize, max_error: f32) -> SimplifyOptions {
+        SimplifyOptions {
+            target_triangle_count: target_triangle_count,
+            max_error: max_error,
+        }
+    }
+}
+
+/// The result of simplifying a mesh.
+#[derive(Clone, Debug)]
+pub struct SimplifiedMesh {
+    pub mesh: Mesh,
+    /// The largest error introduced by any edge collapse, in model units.
+    pub error: f32,
+}
+
+/// A symmetric 4x4 error quadric stored as its upper triangle.
+#[derive(Copy, Clone, Debug, Default)]
+struct Quadric {
+    a2: f64, ab: f64, ac: f64, ad: f64,
+    b2: f64, bc: f64, bd: f64,
+    c2: f64, cd: f64,
+    d2: f64,
+}
+
+impl Quadric {
+    /// Construct the quadric measuring the squared distance to the plane
+    /// `a*x + b*y + c*z + d = 0`, where `(a, b, c)` has unit length.
+    fn from_plane(a: f64, b: f64, c: f64, d: f64) -> Quadric {
+        Quadric {
+            a2: a * a, ab: a * b, ac: a * c, ad: a * d,
+            b2: b * b, bc: b * c, bd: b * d,
+            c2: c * c, cd: c * d,
+            d2: d * d,
+        }
+    }
+
+    fn add(&mut self, other: &Quadric) {
+        self.a2 += other.a2; self.ab += other.ab; self.ac += other.ac; self.ad += other.ad;
+        self.b2 += other.b2; self.bc += other.bc; self.bd += other.bd;
+        self.c2 += other.c2; self.cd += other.cd;
+        self.d2 += other.d2;
+    }
+
+    fn sum(&self, other: &Quadric) -> Quadric {
+        let mut result = *self;
+        result.add(other);
+
+        result
+    }
+
+    fn evaluate(&self, point: &Vector3<f32>) -> f64 {
+        let x = point.x as f64;
+        let y = point.y as f64;
+        let z = point.z as f64;
+        let error = self.a2 * x * x + 2.0 * self.ab * x * y + 2.0 * self.ac * x * z + 2.0 * self.ad * x +
+            self.b2 * y * y + 2.0 * self.bc * y * z + 2.0 * self.bd * y +
+            self.c2 * z * z + 2.0 * self.cd * z +
+            self.d2;
+
+        // Rounding can push the error of a point lying on every plane slightly negative.
+        f64::max(error, 0.0)
+    }
+}
+
+/// A candidate half-edge collapse moving vertex `from` onto vertex `to`. The
+/// versions record the state of both vertices when the candidate was queued, so
+/// candidates made stale by later collapses can be skipped.
+#[derive(Copy, Clone, Debug)]
+struct Collapse {
+    cost: f64,
+    from: u32,
+    to: u32,
+    from_version: u32,
+    to_version: u32,
+}
+
+impl PartialEq for Collapse {
+    fn eq(&self, other: &Collapse) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Collapse {}
+
+impl PartialOrd for Collapse {
+    fn partial_cmp(&self, other: &Collapse) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Collapse {
+    fn cmp(&self, other: &Collapse) -> Ordering {
+        // Reversed so that the binary heap pops the cheapest collapse first.
+        other.cost.partial_cmp(&self.cost).unwrap_or(Ordering::Equal)
+            .then_with(|| other.from.cmp(&self.from))
+            .then_with(|| other.to.cmp(&self.to))
+    }
+}
+
+/// The working state of the simplifier.
+struct Simplifier<'a> {
+    mesh: &'a Mesh,
+    triangles: Vec<[u32; 3]>,
+    triangle_alive: Vec<bool>,
+    alive_triangle_count: usize,
+    incident: Vec<Vec<usize>>,
+    quadrics: Vec<Quadric>,
+    locked: Vec<bool>,
+    removed: Vec<bool>,
+    versions: Vec<u32>,
+    heap: BinaryHeap<Collapse>,
+}
+
+impl<'a> Simplifier<'a> {
+    fn new(mesh: &'a Mesh) -> Simplifier<'a> {
+        let vertex_count = mesh.vertices.len();
+        let triangles: Vec<[u32; 3]> = mesh.vertex_indices.chunks_exact(3)
+            .filter(|indices| indices.iter().all(|&index| (index as usize) < vertex_count))
+            .map(|indices| [indices[0], indices[1], indices[2]])
+            .collect();
+        let triangle_count = triangles.len();
+
+        let mut incident = vec![vec![]; vertex_count];
+        let mut quadrics = vec![Quadric::default(); vertex_count];
+        for (triangle, indices) in triangles.iter().enumerate() {
+            for &index in indices.iter() {
+                incident[index as usize].push(triangle);
+            }
+
+            let p0 = mesh.vertices[indices[0] as usize].position;
+            let p1 = mesh.vertices[indices[1] as usize].position;
+            let p2 = mesh.vertices[indices[2] as usize].position;
+            let normal = (p1 - p0).cross(&(p2 - p0));
+            let magnitude = normal.magnitude();
+            if magnitude <= 0.0 || !magnitude.is_finite() {
+                continue;
+            }
+            let normal = normal / magnitude;
+            let d = -normal.dot(&p0);
+            let quadric = Quadric::from_plane(normal.x as f64, normal.y as f64, normal.z as f64, d as f64);
+            for &index in indices.iter() {
+                quadrics[index as usize].add(&quadric);
+            }
+        }
+
+        // Vertices sharing a position with another vertex sit on a UV seam or a hard
+        // normal edge, and vertices on a border edge sit on a hole or a material
+        // boundary. Locking them in place keeps all of these features intact.
+        let mut group_sizes: HashMap<VectorKey, usize> = HashMap::new();
+        for vertex in mesh.vertices.iter() {
+            *group_sizes.entry(vector_key(&vertex.position)).or_insert(0) += 1;
+        }
+        let mut edge_counts: HashMap<(VectorKey, VectorKey), usize> = HashMap::new();
+        for indices in triangles.iter() {
+            for i in 0..3 {
+                let key_a = vector_key(&mesh.vertices[indices[i] as usize].position);
+                let key_b = vector_key(&mesh.vertices[indices[(i + 1) % 3] as usize].position);
+                let edge = if key_a < key_b { (key_a, key_b) } else { (key_b, key_a) };
+                *edge_counts.entry(edge).or_insert(0) += 1;
+            }
+        }
+        let mut locked: Vec<bool> = mesh.vertices.iter()
+            .map(|vertex| group_sizes[&vector_key(&vertex.position)] > 1)
+            .collect();
+        for indices in triangles.iter() {
+            for i in 0..3 {
+                let index_a = indices[i];
+                let index_b = indices[(i + 1) % 3];
+                let key_a = vector_key(&mesh.vertices[index_a as usize].position);
+                let key_b = vector_key(&mesh.vertices[index_b as usize].position);
+                let edge = if key_a < key_b { (key_a, key_b) } else { (key_b, key_a) };
+                if edge_counts[&edge] != 2 {
+                    locked[index_a as usize] = true;
+                    locked[index_b as usize] = true;
+                }
+            }
+        }
+
+        Simplifier {
+            mesh: mesh,
+            triangles: triangles,
+            triangle_alive: vec![true; triangle_count],
+            alive_triangle_count: triangle_count,
+            incident: incident,
+            quadrics: quadrics,
+            locked: locked,
+            removed: vec![false; vertex_count],
+            versions: vec![0; vertex_count],
+            heap: BinaryHeap::new(),
+        }
+    }
+
+    fn push_collapse(&mut self, from: u32, to: u32) {
+        if self.locked[from as usize] || from == to {
+            return;
+        }
+
+        let quadric = self.quadrics[from as usize].sum(&self.quadrics[to as usize]);
+        let cost = quadric.evaluate(&self.mesh.vertices[to as usize].position);
+        self.heap.push(Collapse {
+            cost: cost,
+            from: from,
+            to: to,
+            from_version: self.versions[from as usize],
+            to_version: self.versions[to as usize],
+        });
+    }
+
+    /// Queue every collapse along an edge incident to `vertex`, in both directions.
+    fn push_vertex_collapses(&mut self, vertex: u32) {
+        for &neighbor in self.neighbors(vertex).iter() {
+            self.push_collapse(vertex, neighbor);
+            self.push_collapse(neighbor, vertex);
+        }
+    }
+
+    fn neighbors(&self, vertex: u32) -> Vec<u32> {
+        let mut neighbors = vec![];
+        for &triangle in self.incident[vertex as usize].iter() {
+            if !self.triangle_alive[triangle] {
+                continue;
+            }
+            for &other in self.triangles[triangle].iter() {
+                if other != vertex && !neighbors.contains(&other) {
+                    neighbors.push(other);
+                }
+            }
+        }
+
+        neighbors
+    }
+
+    /// Determine whether collapsing `from` onto `to` keeps the mesh manifold, does
+    /// not flip or degenerate any triangle, and does not bend the shading normal.
+    fn can_collapse(&self, from: u32, to: u32) -> bool {
+        let normal_from = self.mesh.vertices[from as usize].normal;
+        let normal_to = self.mesh.vertices[to as usize].normal;
+        if normal_from.magnitude_squared() > 0.0 && normal_to.magnitude_squared() > 0.0 {
+            let cosine = normal_from.normalize().dot(&normal_to.normalize());
+            if cosine < NORMAL_COLLAPSE_THRESHOLD {
+                return false;
+            }
+        }
+
+        let neighbors_from = self.neighbors(from);
+        let neighbors_to = self.neighbors(to);
+        let shared = neighbors_from.iter()
+            .filter(|vertex| neighbors_to.contains(vertex))
+            .count();
+        if shared > 2 {
+            return false;
+        }
+
+        let new_position = self.mesh.vertices[to as usize].position;
+        for &triangle in self.incident[from as usize].iter() {
+            if !self.triangle_alive[triangle] {
+                continue;
+            }
+            let indices = self.triangles[triangle];
+            if indices.contains(&to) {
+                continue;
+            }
+
+            let positions = [
+                self.mesh.vertices[indices[0] as usize].position,
+                self.mesh.vertices[indices[1] as usize].position,
+                self.mesh.vertices[indices[2] as usize].position,
+            ];
+            let old_normal = (positions[1] - positions[0]).cross(&(positions[2] - positions[0]));
+            let mut new_positions = positions;
+            for i in 0..3 {
+                if indices[i] == from {
+                    new_positions[i] = new_position;
+                }
+            }
+            let new_normal = (new_positions[1] - new_positions[0]).cross(&(new_positions[2] - new_positions[0]));
+            if new_normal.magnitude_squared() <= 0.0 || old_normal.dot(&new_normal) <= 0.0 {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    fn collapse(&mut self, from: u32, to: u32) {
+        let incident_from = self.incident[from as usize].clone();
+        for &triangle in incident_from.iter() {
+            if !self.triangle_alive[triangle] {
+                continue;
+            }
+            if self.triangles[triangle].contains(&to) {
+                self.triangle_alive[triangle] = false;
+                self.alive_triangle_count -= 1;
+            } else {
+                for index in self.triangles[triangle].iter_mut() {
+                    if *index == from {
+                        *index = to;
+                    }
+                }
+                self.incident[to as usize].push(triangle);
+            }
+        }
+
+        let triangle_alive = &self.triangle_alive;
+        self.incident[to as usize].retain(|&triangle| triangle_alive[triangle]);
+        self.incident[from as usize].clear();
+        let quadric_from = self.quadrics[from as usize];
+        self.quadrics[to as usize].add(&quadric_from);
+        self.removed[from as usize] = true;
+        self.versions[to as usize] += 1;
+        self.push_vertex_collapses(to);
+    }
+
+    fn run(&mut self, options: &SimplifyOptions) -> f64 {
+        for triangle in 0..self.triangles.len() {
+            let indices = self.triangles[triangle];
+            for i in 0..3 {
+                self.push_collapse(indices[i], indices[(i + 1) % 3]);
+                self.push_collapse(indices[(i + 1) % 3], indices[i]);
+            }
+        }
+
+        let max_cost = (options.max_error as f64) * (options.max_error as f64);
+        let mut error = 0.0;
+        while self.alive_triangle_count > options.target_triangle_count {
+            let candidate = match self.heap.pop() {
+                Some(candidate) => candidate,
+                None => break,
+            };
+            let from = candidate.from as usize;
+            let to = candidate.to as usize;
+            if self.removed[from] || self.removed[to] {
+                continue;
+            }
+            if self.versions[from] != candidate.from_version || self.versions[to] != candidate.to_version {
+                continue;
+            }
+            if candidate.cost > max_cost {
+                break;
+            }
+            if !self.can_collapse(candidate.from, candidate.to) {
+                continue;
+            }
+
+            self.collapse(candidate.from, candidate.to);
+            error = f64::max(error, candidate.cost);
+        }
+
+        error
+    }
+
+    /// Assemble the surviving triangles into a new mesh, dropping unused vertices.
+    fn into_mesh(self) -> Mesh {
+        let mut remap = vec![u32::MAX; self.mesh.vertices.len()];
+        let mut vertices = vec![];
+        let mut vertex_indices = Vec::with_capacity(3 * self.alive_triangle_count);
+        for (triangle, indices) in self.triangles.iter().enumerate() {
+            if !self.triangle_alive[triangle] {
+                continue;
+            }
+            for &index in indices.iter() {
+                if remap[index as usize] == u32::MAX {
+                    remap[index as usize] = vertices.len() as u32;
+                    vertices.push(self.mesh.vertices[index as usize]);
+                }
+                vertex_indices.push(remap[index as usize]);
+            }
+        }
+
+        Mesh::with_primitives(
+            self.mesh.name.clone(),
+            vertices,
+            vertex_indices,
+            self.mesh.line_vertices.clone(),
+            self.mesh.point_vertices.clone(),
+            self.mesh.texture_indices.clone()
+        )
+    }
+}
+
+/// Simplify a mesh by collapsing edges in order of their quadric error. Every collapse
+/// moves a vertex onto one of its neighbors, so the surviving vertices keep their exact
+/// attributes. Vertices on UV seams, hard normal edges and mesh borders never move.
+pub fn simplify_mesh(mesh: &Mesh, options: &SimplifyOptions) -> SimplifiedMesh {
+    let mut simplifier = Simplifier::new(mesh);
+    let error = simplifier.run(options);
+
+    SimplifiedMesh {
+        mesh: simplifier.into_mesh(),
+        error: f64::sqrt(error) as f32,
+    }
+}
+
+/// The parameters for generating a chain of levels of detail for a model.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct LodChainOptions {
+    /// The maximum number of levels to generate, not counting the original model.
+    pub levels: usize,
+    /// The fraction of triangles each level keeps from the level before it.
+    pub reduction: f32,
+    /// The largest acceptable error of any level, as a fraction of the radius of the
+    /// model's bounding sphere.
+    pub max_relative_error: f32,
+}
+
+impl LodChainOptions {
+    pub fn new() -> LodChainOptions {
+        LodChainOptions {
+            levels: 3,
+            reduction: 0.5,
+            max_relative_error: 0.05,
+        }
+    }
+}
+
+impl Default for LodChainOptions {
+    fn default() -> LodChainOptions {
+        LodChainOptions::new()
+    }
+}
+
+/// Generate a chain of progressively simplified versions of a model and store them
+/// in `Model::lods`. Each level is simplified from the level before it. Generation
+/// stops early once a level no longer removes any triangles.
+pub fn generate_lod_chain(model: &mut Model, options: &LodChainOptions) {
+    model.lods.clear();
+    if model.bounding_sphere.is_empty() {
+        return;
+    }
+
+    let max_error = options.max_relative_error * model.bounding_sphere.radius;
+    let mut previous_meshes = model.meshes.clone();
+    let mut previous_error = 0.0;
+    for _ in 0..options.levels {
+        let previous_triangle_count: usize = previous_meshes.iter()
+            .map(|mesh| mesh.vertex_indices.len() / 3)
+            .sum();
+        let mut meshes = Vec::with_capacity(previous_meshes.len());
+        let mut level_error = 0.0;
+        for mesh in previous_meshes.iter() {
+            let triangle_count = mesh.vertex_indices.len() / 3;
+            let target = f32::ceil(triangle_count as f32 * options.reduction) as usize;
+            let simplify_options = SimplifyOptions::new(target, f32::max(max_error - previous_error, 0.0));
+            let simplified = simplify_mesh(mesh, &simplify_options);
+            level_error = f32::max(level_error, simplified.error);
+            meshes.push(simplified.mesh);
+        }
+
+        let triangle_count: usize = meshes.iter()
+            .map(|mesh| mesh.vertex_indices.len() / 3)
+            .sum();
+        if triangle_count >= previous_triangle_count {
+            break;
+        }
+
+        // Each level is simplified from the previous one, so the errors accumulate.
+        let error = previous_error + level_error;
+        model.lods.push(ModelLod::new(meshes.clone(), error));
+        previous_meshes = meshes;
+        previous_error = error;
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{
+        Vertex,
+    };
+    use crate::primitives;
+    use cglinalg::{
+        Vector2,
+    };
+
+
+    fn triangle_count(mesh: &Mesh) -> usize {
+        mesh.vertex_indices.len() / 3
+    }
+
+    fn face_normals(mesh: &Mesh) -> Vec<Vector3<f32>> {
+        mesh.vertex_indices.chunks_exact(3)
+            .map(|indices| {
+                let p0 = mesh.vertices[indices[0] as usize].position;
+                let p1 = mesh.vertices[indices[1] as usize].position;
+                let p2 = mesh.vertices[indices[2] as usize].position;
+
+                (p1 - p0).cross(&(p2 - p0))
+            })
+            .collect()
+    }
+
+    /// The number of vertices at each position of a mesh.
+    fn position_counts(mesh: &Mesh) -> HashMap<VectorKey, usize> {
+        let mut counts = HashMap::new();
+        for vertex in mesh.vertices.iter() {
+            *counts.entry(vector_key(&vertex.position)).or_insert(0) += 1;
+        }
+
+        counts
+    }
+
+    /// A flat fan around a vertex at the origin whose ring has a dent next to its
+    /// first vertex, so moving the center onto that vertex folds a triangle over.
+    fn dented_fan() -> Mesh {
+        let positions = [
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(2.0, 0.0, 0.0),
+            Vector3::new(0.5, 0.4, 0.0),
+            Vector3::new(-1.0, 1.0, 0.0),
+            Vector3::new(-1.0, -1.0, 0.0),
+            Vector3::new(1.0, -1.0, 0.0),
+        ];
+        let vertices = positions.iter()
+            .map(|position| Vertex {
+                position: *position,
+                normal: Vector3::unit_z(),
+                tex_coords: Vector2::zero(),
+                tangent: Vector3::zero(),
+                bitangent: Vector3::zero(),
+                color: Vertex::default_color(),
+                tex_coords2: Vector2::zero(),
+            })
+            .collect();
+        let vertex_indices = vec![0, 1, 2, 0, 2, 3, 0, 3, 4, 0, 4, 5, 0, 5, 1];
+
+        Mesh::new(String::from("dented_fan"), vertices, vertex_indices, HashMap::new())
+    }
+
+    #[test]
+    fn test_reaches_target_triangle_count() {
+        let mesh = primitives::uv_sphere(1.0, 32, 16);
+        let target = triangle_count(&mesh) / 2;
+        let simplified = simplify_mesh(&mesh, &SimplifyOptions::new(target, 1.0));
+
+        // Each collapse removes up to two triangles, so the count can overshoot by one.
+        assert!(triangle_count(&simplified.mesh) <= target);
+        assert!(triangle_count(&simplified.mesh) + 1 >= target);
+        assert!(simplified.error > 0.0 && simplified.error <= 1.0);
+    }
+
+    #[test]
+    fn test_stops_at_max_error() {
+        let mesh = primitives::uv_sphere(1.0, 32, 16);
+        let max_error = 0.02;
+        let simplified = simplify_mesh(&mesh, &SimplifyOptions::new(0, max_error));
+        let unbounded = simplify_mesh(&mesh, &SimplifyOptions::new(0, 1.0));
+
+        assert!(simplified.error > 0.0 && simplified.error <= max_error);
+        assert!(triangle_count(&simplified.mesh) < triangle_count(&mesh));
+        assert!(triangle_count(&simplified.mesh) > triangle_count(&unbounded.mesh));
+    }
+
+    #[test]
+    fn test_zero_error_keeps_curved_surface() {
+        let mesh = primitives::uv_sphere(1.0, 16, 8);
+        let simplified = simplify_mesh(&mesh, &SimplifyOptions::new(0, 0.0));
+
+        assert_eq!(triangle_count(&simplified.mesh), triangle_count(&mesh));
+        assert_eq!(simplified.error, 0.0);
+    }
+
+    #[test]
+    fn test_flat_interior_collapses_and_border_stays() {
+        let mesh = primitives::plane(1.0, 1.0, 8, 8);
+        let simplified = simplify_mesh(&mesh, &SimplifyOptions::new(0, 0.0));
+        let result_positions = position_counts(&simplified.mesh);
+
+        assert!(triangle_count(&simplified.mesh) < triangle_count(&mesh));
+        assert_eq!(simplified.error, 0.0);
+        for vertex in mesh.vertices.iter() {
+            let position = vertex.position;
+            if position.x.abs() == 0.5 || position.z.abs() == 0.5 {
+                let key = vector_key(&position);
+                assert!(result_positions.contains_key(&key), "border vertex {:?} moved", position);
+            }
+        }
+    }
+
+    #[test]
+    fn test_seam_vertices_stay() {
+        let mesh = primitives::uv_sphere(1.0, 32, 16);
+        let simplified = simplify_mesh(&mesh, &SimplifyOptions::new(0, 1.0));
+        let positions = position_counts(&mesh);
+        let result_positions = position_counts(&simplified.mesh);
+
+        // Away from the poles, the vertices sharing a position with another vertex
+        // are the two sides of the UV seam, and both sides survive.
+        for vertex in mesh.vertices.iter() {
+            let key = vector_key(&vertex.position);
+            if positions[&key] > 1 && vertex.position.y.abs() < 1.0 {
+                assert_eq!(result_positions.get(&key), Some(&2), "seam vertex {:?} was removed", vertex.position);
+            }
+        }
+        // Collapses move vertices onto their neighbors, so every surviving vertex
+        // keeps its original attributes.
+        for vertex in simplified.mesh.vertices.iter() {
+            assert!(mesh.vertices.contains(vertex));
+        }
+    }
+
+    #[test]
+    fn test_no_flipped_triangles() {
+        let mesh = primitives::uv_sphere(1.0, 32, 16);
+        let simplified = simplify_mesh(&mesh, &SimplifyOptions::new(triangle_count(&mesh) / 2, 1.0));
+        let normals = face_normals(&simplified.mesh);
+
+        for (normal, indices) in normals.iter().zip(simplified.mesh.vertex_indices.chunks_exact(3)) {
+            let centroid = indices.iter()
+                .fold(Vector3::zero(), |sum, &index| sum + simplified.mesh.vertices[index as usize].position);
+
+            assert!(normal.dot(&centroid) > 0.0, "a triangle faces into the sphere");
+        }
+    }
+
+    #[test]
+    fn test_can_collapse_rejects_flip() {
+        let mesh = dented_fan();
+        let simplifier = Simplifier::new(&mesh);
+
+        assert!(!simplifier.can_collapse(0, 1));
+        assert!(simplifier.can_collapse(0, 4));
+    }
+
+    #[test]
+    fn test_simplify_avoids_flip() {
+        let mesh = dented_fan();
+        let simplified = simplify_mesh(&mesh, &SimplifyOptions::new(0, 1.0));
+
+        assert_eq!(triangle_count(&simplified.mesh), 3);
+        assert!(face_normals(&simplified.mesh).iter().all(|normal| normal.z > 0.0));
+    }
+
+    #[test]
+    fn test_lod_chain_gets_coarser() {
+        let mut model = Model::new(
+            String::from("sphere"), vec![primitives::uv_sphere(1.0, 32, 16)], vec![], false
+        );
+        let options = LodChainOptions::new();
+        generate_lod_chain(&mut model, &options);
+
+        assert!(!model.lods.is_empty());
+        assert!(model.lods.len() <= options.levels);
+        let mut previous_triangle_count = triangle_count(&model.meshes[0]);
+        let mut previous_error = 0.0;
+        for lod in model.lods.iter() {
+            let triangle_count = triangle_count(&lod.meshes[0]);
+
+            assert!(triangle_count < previous_triangle_count);
+            assert!(lod.error >= previous_error);
+            assert!(lod.error <= options.max_relative_error * model.bounding_sphere.radius + 1e-6);
+            previous_triangle_count = triangle_count;
+            previous_error = lod.error;
+        }
+    }
+}
@@ -0,0 +1,775 @@
+use crate::geometry::{
+    VectorKey,
+    vector_key,
+};
+use crate::model::{
+    Mesh,
+    Model,
+    Vertex,
+};
+use cglinalg::{
+    Magnitude,
+    Vector2,
+    Vector3,
+    Vector4,
+};
+use std::cmp::{
+    Ordering,
+};
+use std::collections::{
+    HashMap,
+};
+
+
+/// The subdivision rules to refine a mesh with.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum SubdivisionScheme {
+    /// Loop subdivision, which splits every triangle into four triangles.
+    Loop,
+    /// Catmull-Clark subdivision, which splits every polygon into quads. Pairs of
+    /// triangles that came from triangulating a quad are merged back into that quad
+    /// before subdividing.
+    CatmullClark,
+}
+
+/// The parameters for subdividing a mesh.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SubdivisionOptions {
+    pub scheme: SubdivisionScheme,
+    /// The number of times to apply the subdivision rules.
+    pub levels: usize,
+}
+
+impl SubdivisionOptions {
+    pub fn new(scheme: SubdivisionScheme, levels: usize) -> SubdivisionOptions {
+        SubdivisionOptions {
+            scheme: scheme,
+            levels: levels,
+        }
+    }
+}
+
+/// A mesh made of arbitrary polygons, used as the working state between levels.
+struct PolygonMesh {
+    vertices: Vec<Vertex>,
+    faces: Vec<Vec<u32>>,
+}
+
+impl PolygonMesh {
+    fn from_triangles(mesh: &Mesh) -> PolygonMesh {
+        PolygonMesh {
+            vertices: mesh.vertices.clone(),
+            faces: triangles(mesh).iter().map(|triangle| triangle.to_vec()).collect(),
+        }
+    }
+
+    /// Construct a polygon mesh from a triangulated mesh, merging pairs of triangles
+    /// that share an edge back into quads, in any order. Each triangle pairs with the
+    /// first unpaired neighbor it can merge with, trying its longest edge first since
+    /// that is the diagonal when the triangle is half of a quad.
+    fn from_quad_dominant(mesh: &Mesh) -> PolygonMesh {
+        let triangles = triangles(mesh);
+        let mut edge_triangles = HashMap::new();
+        for (t, triangle) in triangles.iter().enumerate() {
+            for k in 0..3 {
+                edge_triangles.entry((triangle[k], triangle[(k + 1) % 3])).or_insert(t);
+            }
+        }
+
+        let edge_length_squared = |triangle: &[u32; 3], k: usize| {
+            let a = mesh.vertices[triangle[k] as usize].position;
+            let b = mesh.vertices[triangle[(k + 1) % 3] as usize].position;
+
+            (b - a).magnitude_squared()
+        };
+        let mut paired = vec![false; triangles.len()];
+        let mut faces = Vec::with_capacity(triangles.len());
+        for (t, triangle) in triangles.iter().enumerate() {
+            if paired[t] {
+                continue;
+            }
+            paired[t] = true;
+
+            let mut edges = [0, 1, 2];
+            edges.sort_by(|&k1, &k2| {
+                edge_length_squared(triangle, k2)
+                    .partial_cmp(&edge_length_squared(triangle, k1))
+                    .unwrap_or(Ordering::Equal)
+            });
+            let merged = edges.iter()
+                .filter_map(|&k| {
+                    // The neighbor runs along the shared edge in the opposite direction.
+                    let &other = edge_triangles.get(&(triangle[(k + 1) % 3], triangle[k]))?;
+                    if paired[other] {
+                        return None;
+                    }
+
+                    merge_triangles(&mesh.vertices, triangle, &triangles[other]).map(|quad| (other, quad))
+                })
+                .next();
+            match merged {
+                Some((other, quad)) => {
+                    paired[other] = true;
+                    faces.push(quad.to_vec());
+                }
+                None => faces.push(triangle.to_vec()),
+            }
+        }
+
+        PolygonMesh {
+            vertices: mesh.vertices.clone(),
+            faces: faces,
+        }
+    }
+
+    /// Triangulate the polygons and recompute the vertex normals and tangent frames
+    /// from the refined surface.
+    fn into_mesh(self, source: &Mesh) -> Mesh {
+        let mut vertex_indices = Vec::with_capacity(3 * self.faces.len());
+        for face in self.faces.iter() {
+            for i in 1..(face.len() - 1) {
+                vertex_indices.push(face[0]);
+                vertex_indices.push(face[i]);
+                vertex_indices.push(face[i + 1]);
+            }
+        }
+
+        let mut vertices = self.vertices;
+        update_normals(&mut vertices, &vertex_indices);
+
+        Mesh::with_primitives(
+            source.name.clone(),
+            vertices,
+            vertex_indices,
+            source.line_vertices.clone(),
+            source.point_vertices.clone(),
+            source.texture_indices.clone()
+        )
+    }
+}
+
+/// The triangles of a mesh that refer only to vertices that exist.
+fn triangles(mesh: &Mesh) -> Vec<[u32; 3]> {
+    let vertex_count = mesh.vertices.len();
+    mesh.vertex_indices.chunks_exact(3)
+        .filter(|indices| indices.iter().all(|&index| (index as usize) < vertex_count))
+        .map(|indices| [indices[0], indices[1], indices[2]])
+        .collect()
+}
+
+/// Merge two triangles sharing an edge into a quad with the same winding. The
+/// triangles are not merged if they face away from each other.
+fn merge_triangles(vertices: &[Vertex], first: &[u32; 3], second: &[u32; 3]) -> Option<[u32; 4]> {
+    for k in 0..3 {
+        let a = first[k];
+        let b = first[(k + 1) % 3];
+        for j in 0..3 {
+            if second[j] != b || second[(j + 1) % 3] != a {
+                continue;
+            }
+            let opposite = second[(j + 2) % 3];
+            if first.contains(&opposite) {
+                return None;
+            }
+
+            let first_normal = face_normal(vertices, first);
+            let second_normal = face_normal(vertices, second);
+            if first_normal.dot(&second_normal) <= 0.0 {
+                return None;
+            }
+
+            return Some([b, first[(k + 2) % 3], a, opposite]);
+        }
+    }
+
+    None
+}
+
+#[inline]
+fn face_normal(vertices: &[Vertex], triangle: &[u32; 3]) -> Vector3<f32> {
+    let p0 = vertices[triangle[0] as usize].position;
+    let p1 = vertices[triangle[1] as usize].position;
+    let p2 = vertices[triangle[2] as usize].position;
+
+    (p1 - p0).cross(&(p2 - p0))
+}
+
+#[inline]
+fn edge_key(a: usize, b: usize) -> (usize, usize) {
+    if a < b { (a, b) } else { (b, a) }
+}
+
+/// The vertices a polygon's edge runs between, starting at `corner`.
+#[inline]
+fn corner_edge(face: &[u32], corner: usize) -> (u32, u32) {
+    (face[corner], face[(corner + 1) % face.len()])
+}
+
+/// An edge between two positions of a polygon mesh.
+struct Edge {
+    /// The faces using the edge, each with the corner the edge starts at.
+    faces: Vec<(usize, usize)>,
+    /// Sharp edges are subdivided as a curve instead of as part of the surface.
+    sharp: bool,
+}
+
+/// The connectivity of a polygon mesh. Vertices are split along UV seams and hard
+/// edges, so connectivity is recovered from positions instead of vertex indices.
+struct Topology {
+    positions: Vec<Vector3<f32>>,
+    position_ids: Vec<usize>,
+    edges: HashMap<(usize, usize), Edge>,
+    neighbors: Vec<Vec<usize>>,
+    sharp_neighbors: Vec<Vec<usize>>,
+    vertex_faces: Vec<Vec<usize>>,
+}
+
+impl Topology {
+    fn new(mesh: &PolygonMesh) -> Topology {
+        let mut positions = vec![];
+        let mut position_lookup = HashMap::new();
+        let position_ids: Vec<usize> = mesh.vertices.iter()
+            .map(|vertex| {
+                *position_lookup.entry(vector_key(&vertex.position)).or_insert_with(|| {
+                    positions.push(vertex.position);
+                    positions.len() - 1
+                })
+            })
+            .collect();
+
+        let mut edges = HashMap::new();
+        let mut vertex_faces = vec![vec![]; positions.len()];
+        for (f, face) in mesh.faces.iter().enumerate() {
+            for corner in 0..face.len() {
+                let (a, b) = corner_edge(face, corner);
+                let position_a = position_ids[a as usize];
+                let position_b = position_ids[b as usize];
+                if !vertex_faces[position_a].contains(&f) {
+                    vertex_faces[position_a].push(f);
+                }
+                if position_a == position_b {
+                    continue;
+                }
+                let edge = edges.entry(edge_key(position_a, position_b)).or_insert(Edge {
+                    faces: vec![],
+                    sharp: false,
+                });
+                edge.faces.push((f, corner));
+            }
+        }
+
+        // Borders and non-manifold edges are sharp, and so are hard edges, where the
+        // faces on either side disagree about the normals at both ends of the edge.
+        for edge in edges.values_mut() {
+            edge.sharp = if edge.faces.len() != 2 {
+                true
+            } else {
+                let (f0, c0) = edge.faces[0];
+                let (f1, c1) = edge.faces[1];
+                let (a0, b0) = corner_edge(&mesh.faces[f0], c0);
+                let (a1, b1) = corner_edge(&mesh.faces[f1], c1);
+                let (a1, b1) = if position_ids[a1 as usize] == position_ids[a0 as usize] {
+                    (a1, b1)
+                } else {
+                    (b1, a1)
+                };
+                let normal_key = |index: u32| vector_key(&mesh.vertices[index as usize].normal);
+
+                normal_key(a0) != normal_key(a1) && normal_key(b0) != normal_key(b1)
+            };
+        }
+
+        let mut neighbors = vec![vec![]; positions.len()];
+        let mut sharp_neighbors = vec![vec![]; positions.len()];
+        for (&(a, b), edge) in edges.iter() {
+            neighbors[a].push(b);
+            neighbors[b].push(a);
+            if edge.sharp {
+                sharp_neighbors[a].push(b);
+                sharp_neighbors[b].push(a);
+            }
+        }
+        // Sort the neighbors so that the results do not depend on hash map order.
+        for list in neighbors.iter_mut().chain(sharp_neighbors.iter_mut()) {
+            list.sort();
+        }
+
+        Topology {
+            positions: positions,
+            position_ids: position_ids,
+            edges: edges,
+            neighbors: neighbors,
+            sharp_neighbors: sharp_neighbors,
+            vertex_faces: vertex_faces,
+        }
+    }
+
+    /// The new position of a vertex touching sharp edges, which both schemes share.
+    /// A vertex on exactly two sharp edges follows the cubic B-spline curve they form,
+    /// unless it is the corner of a single face. A vertex on more than two sharp edges
+    /// is a corner too, and corners stay in place. Vertices on fewer than two sharp
+    /// edges follow the smooth rule of the scheme.
+    fn crease_point(&self, position: usize) -> Option<Vector3<f32>> {
+        let vertex = self.positions[position];
+        match self.sharp_neighbors[position].len() {
+            0 | 1 => None,
+            2 if self.vertex_faces[position].len() > 1 => {
+                let a = self.positions[self.sharp_neighbors[position][0]];
+                let b = self.positions[self.sharp_neighbors[position][1]];
+                Some(vertex * 0.75 + (a + b) * 0.125)
+            }
+            _ => Some(vertex),
+        }
+    }
+
+    #[inline]
+    fn midpoint(&self, edge: (usize, usize)) -> Vector3<f32> {
+        (self.positions[edge.0] + self.positions[edge.1]) * 0.5
+    }
+}
+
+/// Average the attributes of a set of vertices, placing the result at `position`.
+/// Attributes are interpolated linearly within each face, so texture coordinates
+/// keep their seams.
+fn blend_vertices(vertices: &[Vertex], indices: &[u32], position: Vector3<f32>) -> Vertex {
+    let weight = 1.0 / indices.len() as f32;
+    let mut normal = Vector3::zero();
+    let mut tex_coords = Vector2::zero();
+    let mut tangent = Vector3::zero();
+    let mut bitangent = Vector3::zero();
+    let mut color = Vector4::zero();
+    let mut tex_coords2 = Vector2::zero();
+    for &index in indices.iter() {
+        let vertex = &vertices[index as usize];
+        normal += vertex.normal * weight;
+        tex_coords += vertex.tex_coords * weight;
+        tangent += vertex.tangent * weight;
+        bitangent += vertex.bitangent * weight;
+        color += vertex.color * weight;
+        tex_coords2 += vertex.tex_coords2 * weight;
+    }
+
+    Vertex {
+        position: position,
+        normal: normal,
+        tex_coords: tex_coords,
+        tangent: tangent,
+        bitangent: bitangent,
+        color: color,
+        tex_coords2: tex_coords2,
+    }
+}
+
+/// Collects the vertices and faces of the next subdivision level. Every new vertex
+/// is created once per source vertex, source vertex pair, or source face, so the
+/// refined mesh stays indexed and stays split wherever the source mesh was split.
+struct LevelBuilder<'a> {
+    source: &'a PolygonMesh,
+    topology: &'a Topology,
+    vertices: Vec<Vertex>,
+    vertex_remap: Vec<u32>,
+    edge_vertices: HashMap<(u32, u32), u32>,
+    faces: Vec<Vec<u32>>,
+}
+
+impl<'a> LevelBuilder<'a> {
+    fn new(source: &'a PolygonMesh, topology: &'a Topology) -> LevelBuilder<'a> {
+        LevelBuilder {
+            source: source,
+            topology: topology,
+            vertices: vec![],
+            vertex_remap: vec![u32::MAX; source.vertices.len()],
+            edge_vertices: HashMap::new(),
+            faces: vec![],
+        }
+    }
+
+    fn vertex_point(&mut self, vertex: u32, vertex_points: &[Vector3<f32>]) -> u32 {
+        if self.vertex_remap[vertex as usize] == u32::MAX {
+            let position = vertex_points[self.topology.position_ids[vertex as usize]];
+            let mut new_vertex = self.source.vertices[vertex as usize];
+            new_vertex.position = position;
+            self.vertex_remap[vertex as usize] = self.vertices.len() as u32;
+            self.vertices.push(new_vertex);
+        }
+
+        self.vertex_remap[vertex as usize]
+    }
+
+    fn edge_point(&mut self, a: u32, b: u32, edge_points: &HashMap<(usize, usize), Vector3<f32>>) -> u32 {
+        let key = if a < b { (a, b) } else { (b, a) };
+        if let Some(&index) = self.edge_vertices.get(&key) {
+            return index;
+        }
+
+        let position_a = self.topology.position_ids[a as usize];
+        let position_b = self.topology.position_ids[b as usize];
+        let position = edge_points[&edge_key(position_a, position_b)];
+        let index = self.vertices.len() as u32;
+        self.vertices.push(blend_vertices(&self.source.vertices, &[a, b], position));
+        self.edge_vertices.insert(key, index);
+
+        index
+    }
+
+    fn face_point(&mut self, face: &[u32], position: Vector3<f32>) -> u32 {
+        let index = self.vertices.len() as u32;
+        self.vertices.push(blend_vertices(&self.source.vertices, face, position));
+
+        index
+    }
+
+    fn finish(self) -> PolygonMesh {
+        PolygonMesh {
+            vertices: self.vertices,
+            faces: self.faces,
+        }
+    }
+}
+
+/// Apply one level of Loop subdivision to a triangle mesh.
+fn subdivide_loop(mesh: &PolygonMesh) -> PolygonMesh {
+    let topology = Topology::new(mesh);
+
+    let vertex_points: Vec<Vector3<f32>> = (0..topology.positions.len())
+        .map(|position| {
+            topology.crease_point(position).unwrap_or_else(|| {
+                let vertex = topology.positions[position];
+                let neighbors = &topology.neighbors[position];
+                let valence = neighbors.len();
+                if valence == 0 {
+                    return vertex;
+                }
+                let beta = if valence == 3 { 3.0 / 16.0 } else { 3.0 / (8.0 * valence as f32) };
+                let mut sum = Vector3::zero();
+                for &neighbor in neighbors.iter() {
+                    sum += topology.positions[neighbor];
+                }
+
+                vertex * (1.0 - valence as f32 * beta) + sum * beta
+            })
+        })
+        .collect();
+
+    let edge_points: HashMap<(usize, usize), Vector3<f32>> = topology.edges.iter()
+        .map(|(&key, edge)| {
+            let point = if edge.sharp {
+                topology.midpoint(key)
+            } else {
+                let mut opposite = Vector3::zero();
+                for &(f, corner) in edge.faces.iter() {
+                    let face = &mesh.faces[f];
+                    let vertex = face[(corner + 2) % face.len()];
+                    opposite += topology.positions[topology.position_ids[vertex as usize]];
+                }
+                let ends = topology.positions[key.0] + topology.positions[key.1];
+
+                ends * (3.0 / 8.0) + opposite * (1.0 / 8.0)
+            };
+
+            (key, point)
+        })
+        .collect();
+
+    let mut builder = LevelBuilder::new(mesh, &topology);
+    for face in mesh.faces.iter() {
+        let v0 = builder.vertex_point(face[0], &vertex_points);
+        let v1 = builder.vertex_point(face[1], &vertex_points);
+        let v2 = builder.vertex_point(face[2], &vertex_points);
+        let e01 = builder.edge_point(face[0], face[1], &edge_points);
+        let e12 = builder.edge_point(face[1], face[2], &edge_points);
+        let e20 = builder.edge_point(face[2], face[0], &edge_points);
+        builder.faces.push(vec![v0, e01, e20]);
+        builder.faces.push(vec![v1, e12, e01]);
+        builder.faces.push(vec![v2, e20, e12]);
+        builder.faces.push(vec![e01, e12, e20]);
+    }
+
+    builder.finish()
+}
+
+/// Apply one level of Catmull-Clark subdivision to a polygon mesh.
+fn subdivide_catmull_clark(mesh: &PolygonMesh) -> PolygonMesh {
+    let topology = Topology::new(mesh);
+
+    let face_points: Vec<Vector3<f32>> = mesh.faces.iter()
+        .map(|face| {
+            let mut sum = Vector3::zero();
+            for &vertex in face.iter() {
+                sum += topology.positions[topology.position_ids[vertex as usize]];
+            }
+
+            sum * (1.0 / face.len() as f32)
+        })
+        .collect();
+
+    let vertex_points: Vec<Vector3<f32>> = (0..topology.positions.len())
+        .map(|position| {
+            topology.crease_point(position).unwrap_or_else(|| {
+                let vertex = topology.positions[position];
+                let neighbors = &topology.neighbors[position];
+                let faces = &topology.vertex_faces[position];
+                if neighbors.is_empty() || faces.is_empty() {
+                    return vertex;
+                }
+                let valence = neighbors.len() as f32;
+                let mut face_average = Vector3::zero();
+                for &f in faces.iter() {
+                    face_average += face_points[f];
+                }
+                face_average = face_average * (1.0 / faces.len() as f32);
+                let mut edge_average = Vector3::zero();
+                for &neighbor in neighbors.iter() {
+                    edge_average += topology.midpoint((position, neighbor));
+                }
+                edge_average = edge_average * (1.0 / valence);
+
+                (face_average + edge_average * 2.0 + vertex * (valence - 3.0)) * (1.0 / valence)
+            })
+        })
+        .collect();
+
+    let edge_points: HashMap<(usize, usize), Vector3<f32>> = topology.edges.iter()
+        .map(|(&key, edge)| {
+            let point = if edge.sharp {
+                topology.midpoint(key)
+            } else {
+                let (f0, _) = edge.faces[0];
+                let (f1, _) = edge.faces[1];
+                let ends = topology.positions[key.0] + topology.positions[key.1];
+
+                (ends + face_points[f0] + face_points[f1]) * 0.25
+            };
+
+            (key, point)
+        })
+        .collect();
+
+    let mut builder = LevelBuilder::new(mesh, &topology);
+    for (f, face) in mesh.faces.iter().enumerate() {
+        let count = face.len();
+        let center = builder.face_point(face, face_points[f]);
+        let corners: Vec<u32> = face.iter()
+            .map(|&vertex| builder.vertex_point(vertex, &vertex_points))
+            .collect();
+        let edges: Vec<u32> = (0..count)
+            .map(|corner| {
+                let (a, b) = corner_edge(face, corner);
+                builder.edge_point(a, b, &edge_points)
+            })
+            .collect();
+        for corner in 0..count {
+            let previous = (corner + count - 1) % count;
+            builder.faces.push(vec![corners[corner], edges[corner], center, edges[previous]]);
+        }
+    }
+
+    builder.finish()
+}
+
+/// Recompute vertex normals from the area weighted normals of the adjacent triangles,
+/// and make the tangent frames orthogonal to the new normals. Vertices sharing a
+/// position and an interpolated normal are smoothed together, so the surface stays
+/// smooth across UV seams while hard edges stay hard.
+fn update_normals(vertices: &mut [Vertex], vertex_indices: &[u32]) {
+    let smoothing_key = |vertex: &Vertex| (vector_key(&vertex.position), vector_key(&vertex.normal));
+    let mut accumulated: HashMap<(VectorKey, VectorKey), Vector3<f32>> = HashMap::new();
+    for indices in vertex_indices.chunks_exact(3) {
+        let p0 = vertices[indices[0] as usize].position;
+        let p1 = vertices[indices[1] as usize].position;
+        let p2 = vertices[indices[2] as usize].position;
+        let face_normal = (p1 - p0).cross(&(p2 - p0));
+        for &index in indices.iter() {
+            let normal = accumulated.entry(smoothing_key(&vertices[index as usize]))
+                .or_insert(Vector3::zero());
+            *normal += face_normal;
+        }
+    }
+
+    for vertex in vertices.iter_mut() {
+        let normal = match accumulated.get(&smoothing_key(vertex)) {
+            Some(normal) if normal.magnitude_squared() > 0.0 => normal.normalize(),
+            _ if vertex.normal.magnitude_squared() > 0.0 => vertex.normal.normalize(),
+            _ => vertex.normal,
+        };
+        vertex.normal = normal;
+
+        let tangent = vertex.tangent - normal * normal.dot(&vertex.tangent);
+        if tangent.magnitude_squared() > 0.0 {
+            let tangent = tangent.normalize();
+            let bitangent = normal.cross(&tangent);
+            let sign = if bitangent.dot(&vertex.bitangent) < 0.0 { -1.0 } else { 1.0 };
+            vertex.tangent = tangent;
+            vertex.bitangent = bitangent * sign;
+        }
+    }
+}
+
+/// Subdivide a mesh, producing a new indexed mesh. Texture coordinates are
+/// interpolated linearly, borders and hard edges are kept sharp, and the normals
+/// are recomputed from the refined surface.
+pub fn subdivide_mesh(mesh: &Mesh, options: &SubdivisionOptions) -> Mesh {
+    if options.levels == 0 {
+        return mesh.clone();
+    }
+
+    let mut polygon_mesh = match options.scheme {
+        SubdivisionScheme::Loop => PolygonMesh::from_triangles(mesh),
+        SubdivisionScheme::CatmullClark => PolygonMesh::from_quad_dominant(mesh),
+    };
+    for _ in 0..options.levels {
+        polygon_mesh = match options.scheme {
+            SubdivisionScheme::Loop => subdivide_loop(&polygon_mesh),
+            SubdivisionScheme::CatmullClark => subdivide_catmull_clark(&polygon_mesh),
+        };
+    }
+
+    polygon_mesh.into_mesh(mesh)
+}
+
+/// Subdivide every mesh in a model. Levels of detail are left untouched, so subdivide
+/// a model before generating them.
+pub fn subdivide_model(model: &mut Model, options: &SubdivisionOptions) {
+    for mesh in model.meshes.iter_mut() {
+        *mesh = subdivide_mesh(mesh, options);
+    }
+    model.update_bounds();
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis;
+    use crate::bounds::{
+        Aabb,
+    };
+    use crate::primitives;
+
+
+    /// A regular tetrahedron with one vertex per corner, so it has no hard edges.
+    fn tetrahedron() -> Mesh {
+        let positions = [
+            Vector3::new(1.0, 1.0, 1.0),
+            Vector3::new(1.0, -1.0, -1.0),
+            Vector3::new(-1.0, 1.0, -1.0),
+            Vector3::new(-1.0, -1.0, 1.0),
+        ];
+        let vertices = positions.iter()
+            .map(|position| Vertex {
+                position: *position,
+                normal: position.normalize(),
+                tex_coords: Vector2::zero(),
+                tangent: Vector3::zero(),
+                bitangent: Vector3::zero(),
+                color: Vertex::default_color(),
+                tex_coords2: Vector2::zero(),
+            })
+            .collect();
+        let vertex_indices = vec![0, 1, 2, 0, 3, 1, 0, 2, 3, 1, 3, 2];
+
+        Mesh::new(String::from("tetrahedron"), vertices, vertex_indices, HashMap::new())
+    }
+
+    /// The number of distinct positions, edges and triangles of a mesh.
+    fn counts(mesh: &Mesh) -> (usize, usize, usize) {
+        let analysis = analysis::analyze_mesh(mesh);
+
+        (analysis.vertex_count, analysis.edge_count, analysis.triangle_count)
+    }
+
+    fn subdivide(mesh: &Mesh, scheme: SubdivisionScheme, levels: usize) -> Mesh {
+        subdivide_mesh(mesh, &SubdivisionOptions::new(scheme, levels))
+    }
+
+    /// Determine whether every position of a mesh lies on the surface of an axis
+    /// aligned cube with edge length two.
+    fn on_unit_cube(mesh: &Mesh) -> bool {
+        mesh.vertices.iter().all(|vertex| {
+            let position = vertex.position;
+            let distance = f32::max(position.x.abs(), f32::max(position.y.abs(), position.z.abs()));
+
+            (distance - 1.0).abs() <= 1e-6
+        })
+    }
+
+    #[test]
+    fn test_loop_tetrahedron_counts() {
+        let mesh = tetrahedron();
+
+        assert_eq!(counts(&mesh), (4, 6, 4));
+        // Loop subdivision adds a vertex per edge and splits each triangle into four.
+        assert_eq!(counts(&subdivide(&mesh, SubdivisionScheme::Loop, 1)), (10, 24, 16));
+        assert_eq!(counts(&subdivide(&mesh, SubdivisionScheme::Loop, 2)), (34, 96, 64));
+    }
+
+    #[test]
+    fn test_loop_cube_counts() {
+        let mesh = primitives::cube(2.0);
+
+        assert_eq!(counts(&mesh), (8, 18, 12));
+        assert_eq!(counts(&subdivide(&mesh, SubdivisionScheme::Loop, 1)), (26, 72, 48));
+    }
+
+    #[test]
+    fn test_catmull_clark_tetrahedron_counts() {
+        // The faces of a tetrahedron are not merged into quads, so each triangle
+        // splits into three quads, each drawn as two triangles.
+        let result = subdivide(&tetrahedron(), SubdivisionScheme::CatmullClark, 1);
+
+        assert_eq!(counts(&result), (14, 24 + 12, 24));
+    }
+
+    #[test]
+    fn test_catmull_clark_cube_counts() {
+        // The cube is merged back into six quads, which split into four quads each.
+        let mesh = primitives::cube(2.0);
+
+        assert_eq!(counts(&subdivide(&mesh, SubdivisionScheme::CatmullClark, 1)), (26, 48 + 24, 48));
+        assert_eq!(counts(&subdivide(&mesh, SubdivisionScheme::CatmullClark, 2)), (98, 192 + 96, 192));
+    }
+
+    #[test]
+    fn test_hard_edges_stay_sharp() {
+        // Every edge of the cube is hard, so subdividing keeps the corners in place
+        // and every new vertex on the faces of the cube.
+        let mesh = primitives::cube(2.0);
+        for &scheme in [SubdivisionScheme::Loop, SubdivisionScheme::CatmullClark].iter() {
+            let result = subdivide(&mesh, scheme, 2);
+
+            assert!(on_unit_cube(&result), "{:?} rounded the cube", scheme);
+            for corner in mesh.vertices.iter() {
+                assert!(result.vertices.iter().any(|vertex| vertex.position == corner.position));
+            }
+        }
+    }
+
+    #[test]
+    fn test_smooth_edges_are_not_sharp() {
+        // The tetrahedron has no hard edges, so subdividing pulls its corners inward.
+        let result = subdivide(&tetrahedron(), SubdivisionScheme::Loop, 1);
+
+        assert!(result.vertices.iter().all(|vertex| vertex.position.magnitude() < f32::sqrt(3.0)));
+    }
+
+    #[test]
+    fn test_quads_merge_out_of_order() {
+        // Interleave the halves of the quads of a grid, so that consecutive triangles
+        // belong to neighboring quads.
+        let mut mesh = primitives::plane(1.0, 1.0, 2, 2);
+        let triangles: Vec<&[u32]> = mesh.vertex_indices.chunks_exact(3).collect();
+        mesh.vertex_indices = triangles.iter().step_by(2)
+            .chain(triangles.iter().skip(1).step_by(2))
+            .map(|triangle| triangle.to_vec())
+            .collect::<Vec<_>>()
+            .concat();
+        let polygon_mesh = PolygonMesh::from_quad_dominant(&mesh);
+
+        assert_eq!(polygon_mesh.faces.len(), 4);
+        for face in polygon_mesh.faces.iter() {
+            assert_eq!(face.len(), 4);
+            let cell = Aabb::from_points(face.iter().map(|&index| &mesh.vertices[index as usize].position));
+            assert_eq!(cell.extents(), Vector3::new(0.5, 0.0, 0.5));
+        }
+    }
+}
@@ -0,0 +1,421 @@
+use crate::model::{
+    Mesh,
+    Model,
+};
+use cglinalg::{
+    Magnitude,
+    Matrix4,
+    Vector3,
+    Vector4,
+};
+
+
+/// The coordinate axis that points up in an asset's coordinate system.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum UpAxis {
+    Y,
+    Z,
+}
+
+/// The handedness of an asset's coordinate system. OpenGL uses a right-handed
+/// coordinate system.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Handedness {
+    Right,
+    Left,
+}
+
+/// The transformations to apply to a model at load time. The operations are applied
+/// in the order the fields are declared.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct MeshTransformOptions {
+    /// Convert the model from a coordinate system with this up axis to a Y-up
+    /// coordinate system.
+    pub up_axis: UpAxis,
+    /// Convert the model from a coordinate system with this handedness to a
+    /// right-handed coordinate system.
+    pub handedness: Handedness,
+    /// An arbitrary transformation to apply to the model.
+    pub matrix: Option<Matrix4<f32>>,
+    /// Translate the model so that the center of its bounding box is at the origin.
+    pub recenter: bool,
+    /// Uniformly scale the model so that the longest side of its bounding box has unit length.
+    pub normalize_scale: bool,
+    /// Reverse the winding order of every triangle.
+    pub flip_winding: bool,
+    /// Flip the V texture coordinate of every vertex.
+    pub flip_v: bool,
+}
+
+impl MeshTransformOptions {
+    /// Construct a set of transformation options that leaves a model untouched.
+    pub fn new() -> MeshTransformOptions {
+        MeshTransformOptions {
+            up_axis: UpAxis::Y,
+            handedness: Handedness::Right,
+            matrix: None,
+            recenter: false,
+            normalize_scale: false,
+            flip_winding: false,
+            flip_v: false,
+        }
+    }
+
+    /// Apply the transformations to every mesh of a model.
+    pub fn apply(&self, model: &mut Model) {
+        if self.up_axis != UpAxis::Y {
+            convert_up_axis_model(model, self.up_axis, UpAxis::Y);
+        }
+        if self.handedness != Handedness::Right {
+            convert_handedness_model(model);
+        }
+        if let Some(matrix) = self.matrix {
+            transform_model(model, &matrix);
+        }
+        if self.recenter {
+            recenter_model(model);
+        }
+        if self.normalize_scale {
+            normalize_scale_model(model);
+        }
+        if self.flip_winding {
+            for mesh in model.meshes.iter_mut() {
+                flip_winding(mesh);
+            }
+        }
+        if self.flip_v {
+            for mesh in model.meshes.iter_mut() {
+                flip_v(mesh);
+            }
+        }
+    }
+}
+
+impl Default for MeshTransformOptions {
+    fn default() -> MeshTransformOptions {
+        MeshTransformOptions::new()
+    }
+}
+
+/// Compute the determinant of the upper left 3x3 block of an affine matrix. A negative
+/// determinant means the transformation mirrors geometry.
+fn determinant3(matrix: &Matrix4<f32>) -> f32 {
+    let m = matrix;
+    m[0][0] * (m[1][1] * m[2][2] - m[2][1] * m[1][2]) -
+    m[1][0] * (m[0][1] * m[2][2] - m[2][1] * m[0][2]) +
+    m[2][0] * (m[0][1] * m[1][2] - m[1][1] * m[0][2])
+}
+
+#[inline]
+fn transform_point(matrix: &Matrix4<f32>, point: &Vector3<f32>) -> Vector3<f32> {
+    let result = matrix * Vector4::new(point.x, point.y, point.z, 1.0);
+
+    Vector3::new(result.x, result.y, result.z)
+}
+
+#[inline]
+fn transform_direction(matrix: &Matrix4<f32>, direction: &Vector3<f32>) -> Vector3<f32> {
+    let result = matrix * Vector4::new(direction.x, direction.y, direction.z, 0.0);
+    let result = Vector3::new(result.x, result.y, result.z);
+    let magnitude = result.magnitude();
+    if magnitude > 0.0 {
+        result / magnitude
+    } else {
+        result
+    }
+}
+
+/// Apply an affine transformation to a mesh. Normals are transformed by the inverse
+/// transpose of the matrix, tangents and bitangents by the matrix itself. If the
+/// transformation mirrors the mesh, the winding order of its triangles is reversed so
+/// that front faces stay front faces.
+pub fn transform_mesh(mesh: &mut Mesh, matrix: &Matrix4<f32>) {
+    let normal_matrix = matrix.inverse()
+        .map(|inverse| inverse.transpose())
+        .unwrap_or(*matrix);
+    let vertices = mesh.vertices.iter_mut()
+        .chain(mesh.line_vertices.iter_mut())
+        .chain(mesh.point_vertices.iter_mut());
+    for vertex in vertices {
+        vertex.position = transform_point(matrix, &vertex.position);
+        vertex.normal = transform_direction(&normal_matrix, &vertex.normal);
+        vertex.tangent = transform_direction(matrix, &vertex.tangent);
+        vertex.bitangent = transform_direction(matrix, &vertex.bitangent);
+    }
+    if determinant3(matrix) < 0.0 {
+        flip_winding(mesh);
+    }
+
+    mesh.update_bounds();
+}
+
+/// Apply an affine transformation to every mesh of a model, including the meshes of
+/// its levels of detail.
+pub fn transform_model(model: &mut Model, matrix: &Matrix4<f32>) {
+    for mesh in model.meshes.iter_mut() {
+        transform_mesh(mesh, matrix);
+    }
+    for lod in model.lods.iter_mut() {
+        for mesh in lod.meshes.iter_mut() {
+            transform_mesh(mesh, matrix);
+        }
+        // Scale the error along with the geometry. This is exact for uniform scales.
+        lod.error *= f32::cbrt(f32::abs(determinant3(matrix)));
+    }
+
+    model.update_bounds();
+}
+
+/// Translate a mesh so that the center of its bounding box lies at the origin.
+pub fn recenter_mesh(mesh: &mut Mesh) {
+    if mesh.aabb.is_empty() {
+        return;
+    }
+
+    let translation = Matrix4::from_affine_translation(&(-mesh.aabb.center()));
+    transform_mesh(mesh, &translation);
+}
+
+/// Translate a model so that the center of its bounding box lies at the origin. The
+/// meshes keep their positions relative to each other.
+pub fn recenter_model(model: &mut Model) {
+    if model.aabb.is_empty() {
+        return;
+    }
+
+    let translation = Matrix4::from_affine_translation(&(-model.aabb.center()));
+    transform_model(model, &translation);
+}
+
+/// Compute the uniform scale factor that makes the longest side of a box of
+/// dimensions `extents` have unit length.
+fn unit_scale_factor(extents: &Vector3<f32>) -> Option<f32> {
+    let longest = f32::max(extents.x, f32::max(extents.y, extents.z));
+    if longest > 0.0 && longest.is_finite() {
+        Some(1.0 / longest)
+    } else {
+        None
+    }
+}
+
+/// Uniformly scale a mesh about the origin so that the longest side of its bounding
+/// box has unit length. Recenter the mesh first to make it fit inside the unit box
+/// centered at the origin.
+pub fn normalize_scale_mesh(mesh: &mut Mesh) {
+    if let Some(scale) = unit_scale_factor(&mesh.aabb.extents()) {
+        transform_mesh(mesh, &Matrix4::from_affine_scale(scale));
+    }
+}
+
+/// Uniformly scale a model about the origin so that the longest side of its bounding
+/// box has unit length.
+pub fn normalize_scale_model(model: &mut Model) {
+    if let Some(scale) = unit_scale_factor(&model.aabb.extents()) {
+        transform_model(model, &Matrix4::from_affine_scale(scale));
+    }
+}
+
+/// Construct the rotation that takes a coordinate system with up axis `from` to a
+/// coordinate system with up axis `to`.
+pub fn up_axis_conversion_matrix(from: UpAxis, to: UpAxis) -> Matrix4<f32> {
+    // The matrices are written in column major order.
+    match (from, to) {
+        // (x, y, z) -> (x, z, -y)
+        (UpAxis::Z, UpAxis::Y) => Matrix4::new(
+            1.0, 0.0,  0.0, 0.0,
+            0.0, 0.0, -1.0, 0.0,
+            0.0, 1.0,  0.0, 0.0,
+            0.0, 0.0,  0.0, 1.0
+        ),
+        // (x, y, z) -> (x, -z, y)
+        (UpAxis::Y, UpAxis::Z) => Matrix4::new(
+            1.0,  0.0, 0.0, 0.0,
+            0.0,  0.0, 1.0, 0.0,
+            0.0, -1.0, 0.0, 0.0,
+            0.0,  0.0, 0.0, 1.0
+        ),
+        _ => Matrix4::identity(),
+    }
+}
+
+/// Convert a mesh between coordinate systems with different up axes.
+pub fn convert_up_axis_mesh(mesh: &mut Mesh, from: UpAxis, to: UpAxis) {
+    if from != to {
+        transform_mesh(mesh, &up_axis_conversion_matrix(from, to));
+    }
+}
+
+/// Convert a model between coordinate systems with different up axes.
+pub fn convert_up_axis_model(model: &mut Model, from: UpAxis, to: UpAxis) {
+    if from != to {
+        transform_model(model, &up_axis_conversion_matrix(from, to));
+    }
+}
+
+/// Convert a mesh between left-handed and right-handed coordinate systems by mirroring
+/// it along the Z axis. The winding order of the triangles is reversed along with it.
+pub fn convert_handedness_mesh(mesh: &mut Mesh) {
+    transform_mesh(mesh, &Matrix4::from_affine_nonuniform_scale(1.0, 1.0, -1.0));
+}
+
+/// Convert a model between left-handed and right-handed coordinate systems.
+pub fn convert_handedness_model(model: &mut Model) {
+    transform_model(model, &Matrix4::from_affine_nonuniform_scale(1.0, 1.0, -1.0));
+}
+
+/// Reverse the winding order of every triangle in a mesh.
+pub fn flip_winding(mesh: &mut Mesh) {
+    for triangle in mesh.vertex_indices.chunks_exact_mut(3) {
+        triangle.swap(1, 2);
+    }
+}
+
+/// Flip the V texture coordinate of both texture coordinate sets of every vertex in
+/// a mesh. Use this for assets authored with the texture origin in the top left
+/// corner. The bitangents are flipped along with the texture coordinates.
+pub fn flip_v(mesh: &mut Mesh) {
+    let vertices = mesh.vertices.iter_mut()
+        .chain(mesh.line_vertices.iter_mut())
+        .chain(mesh.point_vertices.iter_mut());
+    for vertex in vertices {
+        vertex.tex_coords.y = 1.0 - vertex.tex_coords.y;
+        vertex.tex_coords2.y = 1.0 - vertex.tex_coords2.y;
+        vertex.bitangent = -vertex.bitangent;
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{
+        Vertex,
+    };
+    use cglinalg::{
+        Vector2,
+    };
+    use std::collections::{
+        HashMap,
+    };
+
+
+    fn vertex(x: f32, y: f32, z: f32) -> Vertex {
+        Vertex {
+            position: Vector3::new(x, y, z),
+            normal: Vector3::zero(),
+            tex_coords: Vector2::zero(),
+            tangent: Vector3::zero(),
+            bitangent: Vector3::zero(),
+            color: Vertex::default_color(),
+            tex_coords2: Vector2::zero(),
+        }
+    }
+
+    /// A triangle cutting the corner off the positive octant, with its normal,
+    /// tangent and bitangent set from its geometry.
+    fn corner_triangle() -> Mesh {
+        let normal = Vector3::new(1.0, 1.0, 1.0).normalize();
+        let tangent = Vector3::new(1.0, -1.0, 0.0).normalize();
+        let bitangent = normal.cross(&tangent);
+        let mut vertices = vec![vertex(0.0, 0.0, 1.0), vertex(1.0, 0.0, 0.0), vertex(0.0, 1.0, 0.0)];
+        for vertex in vertices.iter_mut() {
+            vertex.normal = normal;
+            vertex.tangent = tangent;
+            vertex.bitangent = bitangent;
+        }
+
+        Mesh::new(String::from("corner"), vertices, vec![0, 1, 2], HashMap::new())
+    }
+
+    fn face_normal(mesh: &Mesh) -> Vector3<f32> {
+        let p0 = mesh.vertices[mesh.vertex_indices[0] as usize].position;
+        let p1 = mesh.vertices[mesh.vertex_indices[1] as usize].position;
+        let p2 = mesh.vertices[mesh.vertex_indices[2] as usize].position;
+
+        (p1 - p0).cross(&(p2 - p0)).normalize()
+    }
+
+    fn assert_vector_near(result: &Vector3<f32>, expected: &Vector3<f32>) {
+        assert!((result - expected).magnitude() < 1e-5, "{:?} != {:?}", result, expected);
+    }
+
+    #[test]
+    fn test_z_up_to_y_up() {
+        let mut mesh = corner_triangle();
+        mesh.vertices[0].position = Vector3::new(1.0, 2.0, 3.0);
+        mesh.vertices[0].normal = Vector3::unit_z();
+        convert_up_axis_mesh(&mut mesh, UpAxis::Z, UpAxis::Y);
+
+        assert_vector_near(&mesh.vertices[0].position, &Vector3::new(1.0, 3.0, -2.0));
+        assert_vector_near(&mesh.vertices[1].position, &Vector3::new(1.0, 0.0, 0.0));
+        assert_vector_near(&mesh.vertices[2].position, &Vector3::new(0.0, 0.0, -1.0));
+        assert_vector_near(&mesh.vertices[0].normal, &Vector3::unit_y());
+        assert_eq!(mesh.vertex_indices, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn test_up_axis_round_trip() {
+        let matrix = up_axis_conversion_matrix(UpAxis::Y, UpAxis::Z) * up_axis_conversion_matrix(UpAxis::Z, UpAxis::Y);
+
+        assert_eq!(matrix, Matrix4::identity());
+        assert_eq!(up_axis_conversion_matrix(UpAxis::Y, UpAxis::Y), Matrix4::identity());
+    }
+
+    #[test]
+    fn test_mirror_flips_winding() {
+        let mut mesh = corner_triangle();
+        convert_handedness_mesh(&mut mesh);
+
+        assert_eq!(mesh.vertex_indices, vec![0, 2, 1]);
+        assert_vector_near(&mesh.vertices[0].position, &Vector3::new(0.0, 0.0, -1.0));
+        assert_vector_near(&mesh.vertices[0].normal, &Vector3::new(1.0, 1.0, -1.0).normalize());
+        assert_vector_near(&face_normal(&mesh), &mesh.vertices[0].normal);
+    }
+
+    #[test]
+    fn test_rotation_keeps_winding() {
+        let mut mesh = corner_triangle();
+        transform_mesh(&mut mesh, &up_axis_conversion_matrix(UpAxis::Z, UpAxis::Y));
+
+        assert_eq!(mesh.vertex_indices, vec![0, 1, 2]);
+        assert_vector_near(&face_normal(&mesh), &mesh.vertices[0].normal);
+    }
+
+    #[test]
+    fn test_normals_use_inverse_transpose_under_non_uniform_scale() {
+        let mut mesh = corner_triangle();
+        transform_mesh(&mut mesh, &Matrix4::from_affine_nonuniform_scale(1.0, 4.0, 1.0));
+
+        let vertex = &mesh.vertices[0];
+        assert_vector_near(&vertex.normal, &Vector3::new(1.0, 0.25, 1.0).normalize());
+        assert_vector_near(&vertex.tangent, &Vector3::new(1.0, -4.0, 0.0).normalize());
+        assert_vector_near(&face_normal(&mesh), &vertex.normal);
+        assert!(vertex.normal.dot(&vertex.tangent).abs() < 1e-6);
+        assert_eq!(mesh.vertex_indices, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn test_transform_updates_bounds() {
+        let mut mesh = corner_triangle();
+        transform_mesh(&mut mesh, &Matrix4::from_affine_translation(&Vector3::new(1.0, 2.0, 3.0)));
+
+        assert_vector_near(&mesh.aabb.min, &Vector3::new(1.0, 2.0, 3.0));
+        assert_vector_near(&mesh.aabb.max, &Vector3::new(2.0, 3.0, 4.0));
+    }
+
+    #[test]
+    fn test_flip_v_negates_v_and_bitangent() {
+        let mut mesh = corner_triangle();
+        mesh.vertices[0].tex_coords = Vector2::new(0.25, 0.2);
+        mesh.vertices[0].tex_coords2 = Vector2::new(0.75, 0.9);
+        let bitangent = mesh.vertices[0].bitangent;
+        flip_v(&mut mesh);
+
+        assert_eq!(mesh.vertices[0].tex_coords, Vector2::new(0.25, 0.8));
+        assert!((mesh.vertices[0].tex_coords2.y - 0.1).abs() < 1e-6);
+        assert_eq!(mesh.vertices[0].tex_coords2.x, 0.75);
+        assert_eq!(mesh.vertices[0].bitangent, -bitangent);
+        assert_eq!(mesh.vertices[1].tex_coords, Vector2::new(0.0, 1.0));
+        assert_eq!(mesh.vertex_indices, vec![0, 1, 2]);
+    }
+}
@@ -0,0 +1,522 @@
+use crate::gl;
+use crate::backend::{
+    Buffer,
+    OpenGLContext,
+    ShaderHandle,
+};
+use crate::light::{
+    DirLight,
+    PointLight,
+    SpotLight,
+};
+use crate::preprocessor::{
+    ShaderDefines,
+};
+use cglinalg::{
+    Matrix4,
+    Vector3,
+};
+use log::{
+    warn,
+};
+use std::mem;
+use std::slice;
+
+
+/// The binding point of the `Camera` uniform block in `include/camera.glsl`.
+pub const CAMERA_BLOCK_BINDING: u32 = 0;
+/// The binding point of the `Lights` uniform block in `include/lights.glsl`.
+pub const LIGHTS_BLOCK_BINDING: u32 = 1;
+
+pub const CAMERA_BLOCK_NAME: &str = "Camera";
+pub const LIGHTS_BLOCK_NAME: &str = "Lights";
+
+
+/// A `#[repr(C)]` type laid out by the std140 rules, with its padding written out
+/// as fields, so its bytes copy straight into a uniform buffer.
+///
+/// # Safety
+/// Implementors must have no implicit padding bytes.
+pub unsafe trait Std140: Copy {}
+
+/// The bytes of std140 values, as a uniform buffer stores them.
+#[inline]
+pub fn std140_bytes<T: Std140>(values: &[T]) -> &[u8] {
+    unsafe {
+        slice::from_raw_parts(values.as_ptr() as *const u8, values.len() * mem::size_of::<T>())
+    }
+}
+
+/// A `vec3` followed by the `float` std140 packs into its last four bytes.
+#[inline]
+fn vec3_and_float(vector: &Vector3<f32>, value: f32) -> [f32; 4] {
+    [vector.x, vector.y, vector.z, value]
+}
+
+#[inline]
+fn matrix_columns(matrix: &Matrix4<f32>) -> [[f32; 4]; 4] {
+    let mut columns = [[0_f32; 4]; 4];
+    for column in 0..4 {
+        for row in 0..4 {
+            columns[column][row] = matrix[column][row];
+        }
+    }
+
+    columns
+}
+
+/// The contents of the `Camera` uniform block.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct CameraData {
+    pub view: [[f32; 4]; 4],
+    pub projection: [[f32; 4]; 4],
+    /// The position of the camera in world space, followed by padding.
+    pub position: [f32; 4],
+}
+
+unsafe impl Std140 for CameraData {}
+
+impl CameraData {
+    pub fn new(view: &Matrix4<f32>, projection: &Matrix4<f32>, position: &Vector3<f32>) -> CameraData {
+        CameraData {
+            view: matrix_columns(view),
+            projection: matrix_columns(projection),
+            position: [position.x, position.y, position.z, 0.0],
+        }
+    }
+}
+
+/// A `DirLight` in std140 layout. Each color and the direction are followed by
+/// padding.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct DirLightData {
+    pub direction: [f32; 4],
+    pub ambient: [f32; 4],
+    pub diffuse: [f32; 4],
+    pub specular: [f32; 4],
+}
+
+unsafe impl Std140 for DirLightData {}
+
+impl From<&DirLight<f32>> for DirLightData {
+    fn from(light: &DirLight<f32>) -> DirLightData {
+        DirLightData {
+            direction: vec3_and_float(&light.direction, 0.0),
+            ambient: vec3_and_float(&light.ambient, 0.0),
+            diffuse: vec3_and_float(&light.diffuse, 0.0),
+            specular: vec3_and_float(&light.specular, 0.0),
+        }
+    }
+}
+
+/// A `PointLight` in std140 layout. The attenuation terms fill the padding after
+/// the position and the colors.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct PointLightData {
+    pub position_constant: [f32; 4],
+    pub ambient_linear: [f32; 4],
+    pub diffuse_quadratic: [f32; 4],
+    /// The specular color, followed by padding.
+    pub specular: [f32; 4],
+}
+
+unsafe impl Std140 for PointLightData {}
+
+impl From<&PointLight<f32>> for PointLightData {
+    fn from(light: &PointLight<f32>) -> PointLightData {
+        PointLightData {
+            position_constant: vec3_and_float(&light.position, light.constant),
+            ambient_linear: vec3_and_float(&light.ambient, light.linear),
+            diffuse_quadratic: vec3_and_float(&light.diffuse, light.quadratic),
+            specular: vec3_and_float(&light.specular, 0.0),
+        }
+    }
+}
+
+/// A `SpotLight` in std140 layout. The cutoffs and the attenuation terms fill the
+/// padding after the position, the direction and the colors.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct SpotLightData {
+    pub position_cutoff: [f32; 4],
+    pub direction_outer_cutoff: [f32; 4],
+    pub ambient_constant: [f32; 4],
+    pub diffuse_linear: [f32; 4],
+    pub specular_quadratic: [f32; 4],
+}
+
+unsafe impl Std140 for SpotLightData {}
+
+impl From<&SpotLight<f32>> for SpotLightData {
+    fn from(light: &SpotLight<f32>) -> SpotLightData {
+        SpotLightData {
+            position_cutoff: vec3_and_float(&light.position, light.cutoff),
+            direction_outer_cutoff: vec3_and_float(&light.direction, light.outer_cutoff),
+            ambient_constant: vec3_and_float(&light.ambient, light.constant),
+            diffuse_linear: vec3_and_float(&light.diffuse, light.linear),
+            specular_quadratic: vec3_and_float(&light.specular, light.quadratic),
+        }
+    }
+}
+
+/// The light counts at the start of the `Lights` uniform block. GLSL booleans take
+/// four bytes in std140.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+struct LightCountsData {
+    point_light_count: i32,
+    spot_light_count: i32,
+    has_dir_light: i32,
+    _padding: i32,
+}
+
+unsafe impl Std140 for LightCountsData {}
+
+/// The layout of the `Lights` uniform block, whose light arrays hold as many lights
+/// as the shaders are compiled for.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct LightsLayout {
+    max_point_lights: usize,
+    max_spot_lights: usize,
+}
+
+impl LightsLayout {
+    /// GLSL has no empty arrays, so each array holds at least one light.
+    pub fn new(max_point_lights: usize, max_spot_lights: usize) -> LightsLayout {
+        LightsLayout {
+            max_point_lights: usize::max(max_point_lights, 1),
+            max_spot_lights: usize::max(max_spot_lights, 1),
+        }
+    }
+
+    #[inline]
+    pub fn max_point_lights(&self) -> usize {
+        self.max_point_lights
+    }
+
+    #[inline]
+    pub fn max_spot_lights(&self) -> usize {
+        self.max_spot_lights
+    }
+
+    /// The definitions that size the light arrays of `include/lights.glsl`.
+    pub fn shader_defines(&self) -> ShaderDefines {
+        ShaderDefines::new()
+            .with_define("MAX_POINT_LIGHTS", self.max_point_lights)
+            .with_define("MAX_SPOT_LIGHTS", self.max_spot_lights)
+    }
+
+    /// The size of the block in bytes.
+    pub fn size(&self) -> usize {
+        mem::size_of::<LightCountsData>() +
+        mem::size_of::<DirLightData>() +
+        self.max_point_lights * mem::size_of::<PointLightData>() +
+        self.max_spot_lights * mem::size_of::<SpotLightData>()
+    }
+
+    /// Lay out the contents of the block. Lights beyond the capacity of the arrays
+    /// are dropped with a warning.
+    pub fn encode(
+        &self,
+        dir_light: Option<&DirLight<f32>>,
+        point_lights: &[PointLight<f32>],
+        spot_lights: &[SpotLight<f32>]) -> Vec<u8>
+    {
+        if point_lights.len() > self.max_point_lights || spot_lights.len() > self.max_spot_lights {
+            warn!(
+                "Only {} point lights and {} spot lights fit in the lights uniform block, but got {} and {}.",
+                self.max_point_lights, self.max_spot_lights, point_lights.len(), spot_lights.len()
+            );
+        }
+        let point_light_count = usize::min(point_lights.len(), self.max_point_lights);
+        let spot_light_count = usize::min(spot_lights.len(), self.max_spot_lights);
+        let counts = LightCountsData {
+            point_light_count: point_light_count as i32,
+            spot_light_count: spot_light_count as i32,
+            has_dir_light: dir_light.is_some() as i32,
+            _padding: 0,
+        };
+        let dir_light_data = dir_light.map(DirLightData::from).unwrap_or_default();
+        let mut point_light_data = vec![PointLightData::default(); self.max_point_lights];
+        for (data, light) in point_light_data.iter_mut().zip(point_lights.iter()) {
+            *data = PointLightData::from(light);
+        }
+        let mut spot_light_data = vec![SpotLightData::default(); self.max_spot_lights];
+        for (data, light) in spot_light_data.iter_mut().zip(spot_lights.iter()) {
+            *data = SpotLightData::from(light);
+        }
+
+        let mut bytes = Vec::with_capacity(self.size());
+        bytes.extend_from_slice(std140_bytes(&[counts]));
+        bytes.extend_from_slice(std140_bytes(&[dir_light_data]));
+        bytes.extend_from_slice(std140_bytes(&point_light_data));
+        bytes.extend_from_slice(std140_bytes(&spot_light_data));
+        debug_assert_eq!(bytes.len(), self.size());
+
+        bytes
+    }
+}
+
+/// A uniform buffer attached to a binding point. Every program whose uniform block
+/// is assigned to that binding point reads from it.
+pub struct UniformBlockBuffer {
+    buffer: Buffer,
+    binding: u32,
+    size: usize,
+}
+
+impl UniformBlockBuffer {
+    /// Allocate a zeroed buffer of `size` bytes and attach it to `binding`.
+    pub fn new(context: &OpenGLContext, binding: u32, size: usize) -> UniformBlockBuffer {
+        let buffer = Buffer::new(context, gl::UNIFORM_BUFFER);
+        buffer.upload(&vec![0; size], gl::DYNAMIC_DRAW);
+        buffer.bind_base(binding);
+
+        UniformBlockBuffer {
+            buffer: buffer,
+            binding: binding,
+            size: size,
+        }
+    }
+
+    #[inline]
+    pub fn binding(&self) -> u32 {
+        self.binding
+    }
+
+    #[inline]
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Replace the contents of the buffer.
+    pub fn update(&self, data: &[u8]) {
+        debug_assert_eq!(data.len(), self.size);
+        self.buffer.update(0, data);
+    }
+}
+
+/// The camera and lights uniform buffers, shared by every program so that each is
+/// uploaded once per frame.
+pub struct SharedUniformBlocks {
+    camera: UniformBlockBuffer,
+    lights: UniformBlockBuffer,
+    lights_layout: LightsLayout,
+}
+
+impl SharedUniformBlocks {
+    pub fn new(context: &OpenGLContext, lights_layout: LightsLayout) -> SharedUniformBlocks {
+        SharedUniformBlocks {
+            camera: UniformBlockBuffer::new(context, CAMERA_BLOCK_BINDING, mem::size_of::<CameraData>()),
+            lights: UniformBlockBuffer::new(context, LIGHTS_BLOCK_BINDING, lights_layout.size()),
+            lights_layout: lights_layout,
+        }
+    }
+
+    #[inline]
+    pub fn lights_layout(&self) -> LightsLayout {
+        self.lights_layout
+    }
+
+    /// Assign the `Camera` and `Lights` blocks of a program, if it has them, to the
+    /// shared buffers.
+    pub fn bind_program(&self, shader: &ShaderHandle) {
+        bind_block(shader, CAMERA_BLOCK_NAME, &self.camera);
+        bind_block(shader, LIGHTS_BLOCK_NAME, &self.lights);
+    }
+
+    /// Write the matrices and the world space position of the camera. The caller
+    /// already knows the position, which spares inverting the view matrix here.
+    pub fn update_camera(&self, view: &Matrix4<f32>, projection: &Matrix4<f32>, position: &Vector3<f32>) {
+        let data = CameraData::new(view, projection, position);
+        self.camera.update(std140_bytes(&[data]));
+    }
+
+    pub fn update_lights(
+        &self,
+        dir_light: Option<&DirLight<f32>>,
+        point_lights: &[PointLight<f32>],
+        spot_lights: &[SpotLight<f32>])
+    {
+        let data = self.lights_layout.encode(dir_light, point_lights, spot_lights);
+        self.lights.update(&data);
+    }
+}
+
+/// Assign a uniform block of a program to the binding point of a buffer, warning
+/// when the block and the buffer disagree on its size.
+fn bind_block(shader: &ShaderHandle, name: &str, buffer: &UniformBlockBuffer) {
+    let block_size = match shader.reflection().find_uniform_block(name) {
+        Some(block) => block.size,
+        None => return,
+    };
+    if block_size != buffer.size() {
+        warn!(
+            "The uniform block `{}` of shader program {} has {} bytes, but its buffer has {} bytes.",
+            name, shader.id, block_size, buffer.size()
+        );
+    }
+    shader.bind_uniform_block(name, buffer.binding());
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+
+    fn read_f32(bytes: &[u8], offset: usize) -> f32 {
+        f32::from_le_bytes([bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]])
+    }
+
+    fn read_i32(bytes: &[u8], offset: usize) -> i32 {
+        i32::from_le_bytes([bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]])
+    }
+
+    fn read_vec4(bytes: &[u8], offset: usize) -> [f32; 4] {
+        [
+            read_f32(bytes, offset),
+            read_f32(bytes, offset + 4),
+            read_f32(bytes, offset + 8),
+            read_f32(bytes, offset + 12),
+        ]
+    }
+
+    fn point_light(i: usize) -> PointLight<f32> {
+        let i = i as f32;
+        PointLight::new(
+            Vector3::new(i, i + 0.5, i + 0.25),
+            1.0 + i, 2.0 + i, 3.0 + i,
+            Vector3::new(0.1, 0.1, 0.1),
+            Vector3::new(0.5, 0.5, 0.5),
+            Vector3::new(1.0, 1.0, 1.0),
+        )
+    }
+
+    fn spot_light(i: usize) -> SpotLight<f32> {
+        let i = i as f32;
+        SpotLight::new(
+            Vector3::new(i, 2.0, 3.0),
+            Vector3::new(0.0, -1.0, 0.0),
+            0.9, 0.8,
+            1.0 + i, 2.0 + i, 3.0 + i,
+            Vector3::new(0.1, 0.2, 0.3),
+            Vector3::new(0.4, 0.5, 0.6),
+            Vector3::new(0.7, 0.8, 0.9),
+        )
+    }
+
+    fn dir_light() -> DirLight<f32> {
+        DirLight::new(
+            Vector3::new(-0.2, -1.0, -0.3),
+            Vector3::new(0.05, 0.05, 0.05),
+            Vector3::new(0.4, 0.4, 0.4),
+            Vector3::new(0.5, 0.5, 0.5),
+        )
+    }
+
+    #[test]
+    fn test_std140_struct_sizes() {
+        assert_eq!(mem::size_of::<CameraData>(), 144);
+        assert_eq!(mem::size_of::<DirLightData>(), 64);
+        assert_eq!(mem::size_of::<PointLightData>(), 64);
+        assert_eq!(mem::size_of::<SpotLightData>(), 80);
+        assert_eq!(mem::size_of::<LightCountsData>(), 16);
+    }
+
+    #[test]
+    fn test_camera_data_layout() {
+        let mut view = Matrix4::zero();
+        for column in 0..4 {
+            for row in 0..4 {
+                view[column][row] = (4 * column + row) as f32;
+            }
+        }
+        let projection = Matrix4::identity();
+        let data = [CameraData::new(&view, &projection, &Vector3::new(1.0, 2.0, 3.0))];
+        let bytes = std140_bytes(&data);
+
+        assert_eq!(bytes.len(), 144);
+        for i in 0..16 {
+            assert_eq!(read_f32(bytes, 4 * i), i as f32);
+        }
+        assert_eq!(read_vec4(bytes, 64), [1.0, 0.0, 0.0, 0.0]);
+        assert_eq!(read_vec4(bytes, 112), [0.0, 0.0, 0.0, 1.0]);
+        assert_eq!(read_vec4(bytes, 128), [1.0, 2.0, 3.0, 0.0]);
+    }
+
+    #[test]
+    fn test_lights_layout_size() {
+        assert_eq!(LightsLayout::new(4, 2).size(), 16 + 64 + 4 * 64 + 2 * 80);
+        assert_eq!(LightsLayout::new(0, 0).size(), 16 + 64 + 64 + 80);
+        assert_eq!(LightsLayout::new(0, 0).max_point_lights(), 1);
+        assert_eq!(LightsLayout::new(0, 0).max_spot_lights(), 1);
+    }
+
+    #[test]
+    fn test_encode_offsets_and_strides() {
+        let layout = LightsLayout::new(2, 2);
+        let point_lights = [point_light(0), point_light(1)];
+        let spot_lights = [spot_light(0), spot_light(1)];
+        let bytes = layout.encode(Some(&dir_light()), &point_lights, &spot_lights);
+        let point_lights_offset = 16 + 64;
+        let spot_lights_offset = point_lights_offset + 2 * 64;
+
+        assert_eq!(bytes.len(), layout.size());
+        assert_eq!(read_i32(&bytes, 0), 2);
+        assert_eq!(read_i32(&bytes, 4), 2);
+        assert_eq!(read_i32(&bytes, 8), 1);
+        assert_eq!(read_vec4(&bytes, 16), [-0.2, -1.0, -0.3, 0.0]);
+        assert_eq!(read_vec4(&bytes, 16 + 48), [0.5, 0.5, 0.5, 0.0]);
+        for i in 0..2 {
+            let offset = point_lights_offset + i * 64;
+            let light = &point_lights[i];
+            assert_eq!(read_vec4(&bytes, offset), [light.position.x, light.position.y, light.position.z, light.constant]);
+            assert_eq!(read_f32(&bytes, offset + 28), light.linear);
+            assert_eq!(read_f32(&bytes, offset + 44), light.quadratic);
+            assert_eq!(read_vec4(&bytes, offset + 48), [1.0, 1.0, 1.0, 0.0]);
+        }
+        for i in 0..2 {
+            let offset = spot_lights_offset + i * 80;
+            let light = &spot_lights[i];
+            assert_eq!(read_vec4(&bytes, offset), [light.position.x, 2.0, 3.0, 0.9]);
+            assert_eq!(read_vec4(&bytes, offset + 16), [0.0, -1.0, 0.0, 0.8]);
+            assert_eq!(read_f32(&bytes, offset + 44), light.constant);
+            assert_eq!(read_f32(&bytes, offset + 60), light.linear);
+            assert_eq!(read_vec4(&bytes, offset + 64), [0.7, 0.8, 0.9, light.quadratic]);
+        }
+    }
+
+    #[test]
+    fn test_encode_truncates_extra_lights() {
+        let layout = LightsLayout::new(2, 1);
+        let point_lights: Vec<PointLight<f32>> = (0..5).map(point_light).collect();
+        let spot_lights: Vec<SpotLight<f32>> = (0..3).map(spot_light).collect();
+        let bytes = layout.encode(None, &point_lights, &spot_lights);
+        let spot_lights_offset = 16 + 64 + 2 * 64;
+
+        assert_eq!(bytes.len(), layout.size());
+        assert_eq!(read_i32(&bytes, 0), 2);
+        assert_eq!(read_i32(&bytes, 4), 1);
+        assert_eq!(read_i32(&bytes, 8), 0);
+        assert!(bytes[16..80].iter().all(|byte| *byte == 0));
+        assert_eq!(read_f32(&bytes, 16 + 64 + 64), point_lights[1].position.x);
+        assert_eq!(read_f32(&bytes, spot_lights_offset), spot_lights[0].position.x);
+    }
+
+    #[test]
+    fn test_encode_zeroes_unused_slots() {
+        let layout = LightsLayout::new(3, 2);
+        let bytes = layout.encode(None, &[point_light(1)], &[]);
+        let unused_point_lights = (16 + 64 + 64)..(16 + 64 + 3 * 64);
+        let spot_lights = (16 + 64 + 3 * 64)..layout.size();
+
+        assert_eq!(read_i32(&bytes, 0), 1);
+        assert_eq!(read_i32(&bytes, 4), 0);
+        assert!(bytes[unused_point_lights].iter().all(|byte| *byte == 0));
+        assert!(bytes[spot_lights].iter().all(|byte| *byte == 0));
+    }
+}
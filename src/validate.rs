@@ -0,0 +1,485 @@
+use crate::geometry::{
+    VectorKey,
+    vector_key,
+};
+use crate::model::{
+    Mesh,
+    Model,
+};
+use cglinalg::{
+    Magnitude,
+    Vector3,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use std::collections::{
+    HashMap,
+    HashSet,
+};
+use std::fmt;
+
+
+#[inline]
+fn is_finite(vector: &Vector3<f32>) -> bool {
+    vector.x.is_finite() && vector.y.is_finite() && vector.z.is_finite()
+}
+
+/// The problems found in a mesh by a validation pass. Triangles are identified by
+/// their index in `Mesh::vertex_indices` divided by three.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ValidationReport {
+    pub mesh_name: String,
+    pub vertex_count: usize,
+    pub triangle_count: usize,
+    /// The number of trailing indices that do not form a complete triangle.
+    pub dangling_indices: usize,
+    /// Vertices whose position has a NaN or infinite component.
+    pub non_finite_positions: Vec<u32>,
+    /// Vertices whose normal has zero length, or is not finite.
+    pub zero_length_normals: Vec<u32>,
+    /// Triangles that refer to a vertex past the end of the vertex buffer.
+    pub out_of_range_triangles: Vec<usize>,
+    /// Triangles with zero area.
+    pub degenerate_triangles: Vec<usize>,
+    /// Triangles covering the same three positions as an earlier triangle with the
+    /// same winding.
+    pub duplicate_triangles: Vec<usize>,
+    /// Vertices that no triangle refers to.
+    pub unused_vertices: Vec<u32>,
+    /// Edges, as pairs of vertex indices, shared by more than two triangles.
+    pub non_manifold_edges: Vec<(u32, u32)>,
+    /// Line segments with an endpoint whose position is not finite.
+    pub non_finite_line_segments: Vec<usize>,
+    /// Points whose position is not finite.
+    pub non_finite_points: Vec<usize>,
+}
+
+impl ValidationReport {
+    /// Determine whether the validation pass found no problems at all.
+    pub fn is_clean(&self) -> bool {
+        self.issue_count() == 0
+    }
+
+    /// The total number of problems found.
+    pub fn issue_count(&self) -> usize {
+        self.dangling_indices +
+        self.non_finite_positions.len() +
+        self.zero_length_normals.len() +
+        self.out_of_range_triangles.len() +
+        self.degenerate_triangles.len() +
+        self.duplicate_triangles.len() +
+        self.unused_vertices.len() +
+        self.non_manifold_edges.len() +
+        self.non_finite_line_segments.len() +
+        self.non_finite_points.len()
+    }
+}
+
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f,
+            "Validation report for mesh \"{}\" ({} vertices, {} triangles):",
+            self.mesh_name, self.vertex_count, self.triangle_count
+        )?;
+        writeln!(f, "  dangling indices: {}", self.dangling_indices)?;
+        writeln!(f, "  non-finite positions: {}", self.non_finite_positions.len())?;
+        writeln!(f, "  zero length normals: {}", self.zero_length_normals.len())?;
+        writeln!(f, "  out of range triangles: {}", self.out_of_range_triangles.len())?;
+        writeln!(f, "  degenerate triangles: {}", self.degenerate_triangles.len())?;
+        writeln!(f, "  duplicate triangles: {}", self.duplicate_triangles.len())?;
+        writeln!(f, "  unused vertices: {}", self.unused_vertices.len())?;
+        writeln!(f, "  non-manifold edges: {}", self.non_manifold_edges.len())?;
+        writeln!(f, "  non-finite line segments: {}", self.non_finite_line_segments.len())?;
+        writeln!(f, "  non-finite points: {}", self.non_finite_points.len())
+    }
+}
+
+/// Determine whether a triangle has zero area. Triangles whose corners share a
+/// position, or whose corners are collinear, are degenerate.
+fn is_degenerate(p0: &Vector3<f32>, p1: &Vector3<f32>, p2: &Vector3<f32>) -> bool {
+    let key0 = vector_key(p0);
+    let key1 = vector_key(p1);
+    let key2 = vector_key(p2);
+    if key0 == key1 || key1 == key2 || key2 == key0 {
+        return true;
+    }
+
+    let edge1 = p1 - p0;
+    let edge2 = p2 - p0;
+    let longest_squared = f32::max(
+        edge1.magnitude_squared(),
+        f32::max(edge2.magnitude_squared(), (p2 - p1).magnitude_squared())
+    );
+
+    edge1.cross(&edge2).magnitude() <= f32::EPSILON * longest_squared
+}
+
+/// Rotate the corners of a triangle so the smallest comes first. Rotating keeps the
+/// winding, so a triangle and its opposite facing twin, as in a two-sided leaf or
+/// card, get different keys.
+#[inline]
+fn winding_key(keys: [VectorKey; 3]) -> [VectorKey; 3] {
+    let first = (0..3).min_by_key(|&i| keys[i]).unwrap_or(0);
+
+    [keys[first], keys[(first + 1) % 3], keys[(first + 2) % 3]]
+}
+
+/// Check a mesh for problems that would produce incorrect rendering or crash
+/// later processing stages.
+pub fn validate_mesh(mesh: &Mesh) -> ValidationReport {
+    let vertex_count = mesh.vertices.len();
+    let mut report = ValidationReport {
+        mesh_name: mesh.name.clone(),
+        vertex_count: vertex_count,
+        triangle_count: mesh.vertex_indices.len() / 3,
+        dangling_indices: mesh.vertex_indices.len() % 3,
+        ..ValidationReport::default()
+    };
+
+    for (i, vertex) in mesh.vertices.iter().enumerate() {
+        if !is_finite(&vertex.position) {
+            report.non_finite_positions.push(i as u32);
+        }
+        if !is_finite(&vertex.normal) || vertex.normal.magnitude_squared() == 0.0 {
+            report.zero_length_normals.push(i as u32);
+        }
+    }
+
+    // Topology is matched by position rather than by vertex index. The loader
+    // deduplicates identical vertices, but vertices split along UV seams or hard
+    // edges keep separate indices at the same position.
+    let mut used = vec![false; vertex_count];
+    let mut seen_triangles = HashSet::new();
+    let mut edge_counts: HashMap<(VectorKey, VectorKey), (usize, (u32, u32))> = HashMap::new();
+    for (triangle, indices) in mesh.vertex_indices.chunks_exact(3).enumerate() {
+        if indices.iter().any(|&index| index as usize >= vertex_count) {
+            report.out_of_range_triangles.push(triangle);
+            continue;
+        }
+        for &index in indices.iter() {
+            used[index as usize] = true;
+        }
+
+        let p0 = &mesh.vertices[indices[0] as usize].position;
+        let p1 = &mesh.vertices[indices[1] as usize].position;
+        let p2 = &mesh.vertices[indices[2] as usize].position;
+        if !is_finite(p0) || !is_finite(p1) || !is_finite(p2) {
+            // Already reported as non-finite positions.
+            continue;
+        }
+        if is_degenerate(p0, p1, p2) {
+            report.degenerate_triangles.push(triangle);
+            continue;
+        }
+
+        let keys = [vector_key(p0), vector_key(p1), vector_key(p2)];
+        let corners = [
+            (keys[0], indices[0]),
+            (keys[1], indices[1]),
+            (keys[2], indices[2]),
+        ];
+        if !seen_triangles.insert(winding_key(keys)) {
+            report.duplicate_triangles.push(triangle);
+            continue;
+        }
+
+        for i in 0..3 {
+            let (key_a, index_a) = corners[i];
+            let (key_b, index_b) = corners[(i + 1) % 3];
+            let edge = if key_a < key_b { (key_a, key_b) } else { (key_b, key_a) };
+            let entry = edge_counts.entry(edge).or_insert((0, (index_a, index_b)));
+            entry.0 += 1;
+        }
+    }
+
+    for (i, is_used) in used.iter().enumerate() {
+        if !is_used {
+            report.unused_vertices.push(i as u32);
+        }
+    }
+
+    let mut non_manifold_edges: Vec<(u32, u32)> = edge_counts.values()
+        .filter(|(count, _)| *count > 2)
+        .map(|(_, edge)| *edge)
+        .collect();
+    non_manifold_edges.sort();
+    report.non_manifold_edges = non_manifold_edges;
+
+    for (segment, endpoints) in mesh.line_vertices.chunks_exact(2).enumerate() {
+        if !is_finite(&endpoints[0].position) || !is_finite(&endpoints[1].position) {
+            report.non_finite_line_segments.push(segment);
+        }
+    }
+    for (point, vertex) in mesh.point_vertices.iter().enumerate() {
+        if !is_finite(&vertex.position) {
+            report.non_finite_points.push(point);
+        }
+    }
+
+    report
+}
+
+/// Check every mesh in a model.
+pub fn validate_model(model: &Model) -> Vec<ValidationReport> {
+    model.meshes.iter().map(validate_mesh).collect()
+}
+
+/// Validate a mesh and fix every problem that can be fixed without changing the
+/// shape of the mesh. Triangles that are out of range, degenerate, duplicated, or
+/// that touch a non-finite position are removed, zero length normals are replaced
+/// by the area weighted normal of the adjacent triangles, and unused vertices are
+/// removed, as are line segments and points with a non-finite position. Non-manifold
+/// edges are reported but left in place. The returned report describes the mesh as
+/// it was before sanitizing it.
+pub fn sanitize_mesh(mesh: &mut Mesh) -> ValidationReport {
+    let report = validate_mesh(mesh);
+    if report.is_clean() {
+        return report;
+    }
+
+    let vertex_count = mesh.vertices.len();
+    let mut removed_triangles = vec![false; mesh.vertex_indices.len() / 3];
+    for &triangle in report.out_of_range_triangles.iter()
+        .chain(report.degenerate_triangles.iter())
+        .chain(report.duplicate_triangles.iter())
+    {
+        removed_triangles[triangle] = true;
+    }
+    let mut non_finite = vec![false; vertex_count];
+    for &vertex in report.non_finite_positions.iter() {
+        non_finite[vertex as usize] = true;
+    }
+
+    let mut vertex_indices = Vec::with_capacity(mesh.vertex_indices.len());
+    for (triangle, indices) in mesh.vertex_indices.chunks_exact(3).enumerate() {
+        if removed_triangles[triangle] {
+            continue;
+        }
+        if indices.iter().any(|&index| non_finite[index as usize]) {
+            continue;
+        }
+        vertex_indices.extend_from_slice(indices);
+    }
+
+    // Replace missing normals with the area weighted normals of the adjacent faces,
+    // matched by position so that split vertices on either side of a seam agree.
+    if !report.zero_length_normals.is_empty() {
+        let mut face_normals: HashMap<VectorKey, Vector3<f32>> = HashMap::new();
+        for indices in vertex_indices.chunks_exact(3) {
+            let p0 = mesh.vertices[indices[0] as usize].position;
+            let p1 = mesh.vertices[indices[1] as usize].position;
+            let p2 = mesh.vertices[indices[2] as usize].position;
+            let face_normal = (p1 - p0).cross(&(p2 - p0));
+            for &index in indices.iter() {
+                let key = vector_key(&mesh.vertices[index as usize].position);
+                let normal = face_normals.entry(key).or_insert(Vector3::zero());
+                *normal += face_normal;
+            }
+        }
+        for &vertex in report.zero_length_normals.iter() {
+            let vertex = &mut mesh.vertices[vertex as usize];
+            let key = vector_key(&vertex.position);
+            vertex.normal = match face_normals.get(&key) {
+                Some(normal) if normal.magnitude_squared() > 0.0 => normal.normalize(),
+                _ => Vector3::new(0.0, 1.0, 0.0),
+            };
+        }
+    }
+
+    // Compact the vertex buffer, dropping the vertices no remaining triangle uses.
+    let mut remap = vec![u32::MAX; vertex_count];
+    let mut vertices = Vec::with_capacity(vertex_count);
+    for index in vertex_indices.iter_mut() {
+        let old_index = *index as usize;
+        if remap[old_index] == u32::MAX {
+            remap[old_index] = vertices.len() as u32;
+            vertices.push(mesh.vertices[old_index]);
+        }
+        *index = remap[old_index];
+    }
+
+    mesh.vertices = vertices;
+    mesh.vertex_indices = vertex_indices;
+    mesh.line_vertices = mesh.line_vertices.chunks_exact(2)
+        .filter(|endpoints| is_finite(&endpoints[0].position) && is_finite(&endpoints[1].position))
+        .flat_map(|endpoints| endpoints.iter().copied())
+        .collect();
+    mesh.point_vertices.retain(|vertex| is_finite(&vertex.position));
+    mesh.update_bounds();
+
+    report
+}
+
+/// Sanitize every mesh in a model.
+pub fn sanitize_model(model: &mut Model) -> Vec<ValidationReport> {
+    let reports = model.meshes.iter_mut().map(sanitize_mesh).collect();
+    model.update_bounds();
+
+    reports
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{
+        Vertex,
+    };
+    use crate::primitives;
+    use cglinalg::{
+        Vector2,
+    };
+
+
+    fn vertex(x: f32, y: f32, z: f32) -> Vertex {
+        Vertex {
+            position: Vector3::new(x, y, z),
+            normal: Vector3::unit_z(),
+            tex_coords: Vector2::zero(),
+            tangent: Vector3::zero(),
+            bitangent: Vector3::zero(),
+            color: Vertex::default_color(),
+            tex_coords2: Vector2::zero(),
+        }
+    }
+
+    /// A mesh with one of every problem the validation pass looks for.
+    fn broken_mesh() -> Mesh {
+        let mut zero_normal = vertex(2.0, 0.0, 0.0);
+        zero_normal.normal = Vector3::zero();
+        let vertices = vec![
+            vertex(0.0, 0.0, 0.0),
+            vertex(1.0, 0.0, 0.0),
+            vertex(0.0, 1.0, 0.0),
+            vertex(1.0, 1.0, 0.0),
+            vertex(f32::NAN, 0.0, 0.0),
+            zero_normal,
+            vertex(5.0, 5.0, 5.0),
+            vertex(0.5, 0.0, 0.0),
+            vertex(0.0, 0.0, -1.0),
+        ];
+        let vertex_indices = vec![
+            0, 1, 2,
+            1, 3, 2,
+            // A duplicate of the first triangle.
+            0, 1, 2,
+            // The back of the first triangle, which is not a duplicate.
+            0, 2, 1,
+            0, 1, 99,
+            // Collinear corners.
+            0, 1, 7,
+            4, 1, 2,
+            // A third triangle on the edge from vertex 0 to vertex 1.
+            1, 0, 8,
+            5, 3, 1,
+            0, 1,
+        ];
+        let line_vertices = vec![
+            vertex(0.0, 0.0, 0.0), vertex(1.0, 0.0, 0.0),
+            vertex(f32::INFINITY, 0.0, 0.0), vertex(0.0, 0.0, 0.0),
+        ];
+        let point_vertices = vec![vertex(0.0, 0.0, 0.0), vertex(0.0, f32::NAN, 0.0)];
+
+        Mesh::with_primitives(
+            String::from("broken"), vertices, vertex_indices, line_vertices, point_vertices, HashMap::new()
+        )
+    }
+
+    #[test]
+    fn test_validate_clean_mesh() {
+        let report = validate_mesh(&primitives::cube(1.0));
+
+        assert!(report.is_clean(), "{}", report);
+        assert_eq!(report.issue_count(), 0);
+        assert_eq!(report.vertex_count, 24);
+        assert_eq!(report.triangle_count, 12);
+    }
+
+    #[test]
+    fn test_validate_broken_mesh() {
+        let report = validate_mesh(&broken_mesh());
+        let expected = ValidationReport {
+            mesh_name: String::from("broken"),
+            vertex_count: 9,
+            triangle_count: 9,
+            dangling_indices: 2,
+            non_finite_positions: vec![4],
+            zero_length_normals: vec![5],
+            out_of_range_triangles: vec![4],
+            degenerate_triangles: vec![5],
+            duplicate_triangles: vec![2],
+            unused_vertices: vec![6],
+            // The back of the first triangle also puts a third triangle on the edge
+            // from vertex 1 to vertex 2.
+            non_manifold_edges: vec![(0, 1), (1, 2)],
+            non_finite_line_segments: vec![1],
+            non_finite_points: vec![1],
+        };
+
+        assert_eq!(report, expected);
+        assert_eq!(report.issue_count(), 12);
+        assert!(!report.is_clean());
+    }
+
+    #[test]
+    fn test_two_sided_triangles_are_not_duplicates() {
+        let vertices = vec![vertex(0.0, 0.0, 0.0), vertex(1.0, 0.0, 0.0), vertex(0.0, 1.0, 0.0)];
+        let mut mesh = Mesh::new(String::from("card"), vertices, vec![0, 1, 2, 0, 2, 1], HashMap::new());
+        let report = sanitize_mesh(&mut mesh);
+
+        assert!(report.is_clean(), "{}", report);
+        assert_eq!(mesh.vertex_indices, vec![0, 1, 2, 0, 2, 1]);
+    }
+
+    #[test]
+    fn test_rotated_triangle_is_duplicate() {
+        let vertices = vec![vertex(0.0, 0.0, 0.0), vertex(1.0, 0.0, 0.0), vertex(0.0, 1.0, 0.0)];
+        let mesh = Mesh::new(String::from("rotated"), vertices, vec![0, 1, 2, 1, 2, 0], HashMap::new());
+
+        assert_eq!(validate_mesh(&mesh).duplicate_triangles, vec![1]);
+    }
+
+    #[test]
+    fn test_sanitize_broken_mesh() {
+        let original = broken_mesh();
+        let mut mesh = original.clone();
+        let report = sanitize_mesh(&mut mesh);
+
+        assert_eq!(report, validate_mesh(&original));
+        // The surviving vertices in order of first use are 0, 1, 2, 3, 8 and 5.
+        let kept = [0, 1, 2, 3, 8, 5];
+        assert_eq!(mesh.vertices.len(), kept.len());
+        for (vertex, &old_index) in mesh.vertices.iter().zip(kept.iter()) {
+            assert_eq!(vertex.position, original.vertices[old_index].position);
+        }
+        assert_eq!(mesh.vertex_indices, vec![0, 1, 2, 1, 3, 2, 0, 2, 1, 1, 0, 4, 5, 3, 1]);
+        // The zero normal is replaced by the normal of the triangle using it.
+        assert_eq!(mesh.vertices[5].normal, Vector3::unit_z());
+        assert_eq!(mesh.line_vertices.len(), 2);
+        assert!(mesh.line_vertices.iter().all(|vertex| is_finite(&vertex.position)));
+        assert_eq!(mesh.point_vertices.len(), 1);
+        assert!(is_finite(&mesh.point_vertices[0].position));
+
+        // Only the non-manifold edges are left in place.
+        let expected = ValidationReport {
+            mesh_name: String::from("broken"),
+            vertex_count: 6,
+            triangle_count: 5,
+            non_manifold_edges: vec![(0, 1), (1, 2)],
+            ..ValidationReport::default()
+        };
+        assert_eq!(validate_mesh(&mesh), expected);
+    }
+
+    #[test]
+    fn test_sanitize_clean_mesh_unchanged() {
+        let original = primitives::cube(1.0);
+        let mut mesh = original.clone();
+
+        assert!(sanitize_mesh(&mut mesh).is_clean());
+        assert_eq!(mesh.vertices, original.vertices);
+        assert_eq!(mesh.vertex_indices, original.vertex_indices);
+    }
+}
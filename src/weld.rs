@@ -0,0 +1,240 @@
+use crate::model;
+use crate::model::{
+    Mesh,
+    Vertex,
+};
+use cglinalg::{
+    Magnitude,
+    Vector3,
+};
+use std::collections::{
+    HashMap,
+};
+
+
+/// The cell of a uniform grid with cells of size `cell_size` containing a point.
+#[inline]
+fn grid_cell(position: &Vector3<f32>, cell_size: f32) -> [i64; 3] {
+    [
+        f32::floor(position.x / cell_size) as i64,
+        f32::floor(position.y / cell_size) as i64,
+        f32::floor(position.z / cell_size) as i64,
+    ]
+}
+
+/// Snap vertex positions that lie within `tolerance` of each other onto a single
+/// position. Each position snaps onto the first position seen within tolerance of
+/// it, which a spatial hash with cells the size of the tolerance finds by searching
+/// the neighboring cells. Only positions move; the vertices are not merged. Returns
+/// the number of vertices that moved.
+pub fn weld_positions(vertices: &mut [Vertex], tolerance: f32) -> usize {
+    if !(tolerance > 0.0) {
+        return 0;
+    }
+
+    let tolerance_squared = tolerance * tolerance;
+    let mut grid: HashMap<[i64; 3], Vec<Vector3<f32>>> = HashMap::new();
+    let mut moved = 0;
+    for vertex in vertices.iter_mut() {
+        let position = vertex.position;
+        if !(position.x.is_finite() && position.y.is_finite() && position.z.is_finite()) {
+            continue;
+        }
+
+        let cell = grid_cell(&position, tolerance);
+        let mut nearest: Option<(f32, Vector3<f32>)> = None;
+        for dx in -1..2 {
+            for dy in -1..2 {
+                for dz in -1..2 {
+                    let neighbor = [cell[0] + dx, cell[1] + dy, cell[2] + dz];
+                    if let Some(representatives) = grid.get(&neighbor) {
+                        for representative in representatives.iter() {
+                            let distance_squared = (representative - position).magnitude_squared();
+                            if distance_squared <= tolerance_squared &&
+                                nearest.map_or(true, |(best, _)| distance_squared < best)
+                            {
+                                nearest = Some((distance_squared, *representative));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        match nearest {
+            Some((_, representative)) => {
+                if representative != position {
+                    vertex.position = representative;
+                    moved += 1;
+                }
+            }
+            None => grid.entry(cell).or_insert_with(Vec::new).push(position),
+        }
+    }
+
+    moved
+}
+
+/// Determine whether welding collapsed a triangle onto a line or a point.
+#[inline]
+fn is_collapsed(p0: &Vector3<f32>, p1: &Vector3<f32>, p2: &Vector3<f32>) -> bool {
+    p0 == p1 || p1 == p2 || p2 == p0
+}
+
+/// Remove the triangles of a triangle soup that welding collapsed, where each
+/// consecutive three vertices form a triangle and `smoothing_groups` holds the
+/// smoothing group of each triangle. Returns the number of triangles removed.
+pub fn remove_collapsed_triangles(vertices: &mut Vec<Vertex>, smoothing_groups: &mut Vec<u32>) -> usize {
+    let triangle_count = vertices.len() / 3;
+    let mut kept = 0;
+    for triangle in 0..triangle_count {
+        let first = 3 * triangle;
+        if is_collapsed(&vertices[first].position, &vertices[first + 1].position, &vertices[first + 2].position) {
+            continue;
+        }
+        vertices.copy_within(first..(first + 3), 3 * kept);
+        if triangle < smoothing_groups.len() {
+            smoothing_groups[kept] = smoothing_groups[triangle];
+        }
+        kept += 1;
+    }
+    vertices.truncate(3 * kept);
+    smoothing_groups.truncate(kept);
+
+    triangle_count - kept
+}
+
+/// The changes made to a mesh by welding it.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct WeldReport {
+    /// Vertices whose position snapped onto a nearby position.
+    pub moved_vertices: usize,
+    /// Vertices removed because they became identical to another vertex.
+    pub merged_vertices: usize,
+    /// Triangles removed because welding collapsed them.
+    pub collapsed_triangles: usize,
+}
+
+/// Weld the vertex positions of a mesh within `tolerance`, merge the vertices that
+/// become identical, and remove the triangles that collapse.
+pub fn weld_mesh(mesh: &mut Mesh, tolerance: f32) -> WeldReport {
+    let vertex_count = mesh.vertices.len();
+    let triangle_count = mesh.vertex_indices.len() / 3;
+    let moved_vertices = weld_positions(&mut mesh.vertices, tolerance);
+    if moved_vertices == 0 {
+        return WeldReport::default();
+    }
+
+    let (vertices, remap) = model::deduplicate_vertices(&mesh.vertices);
+    let mut vertex_indices = Vec::with_capacity(mesh.vertex_indices.len());
+    for indices in mesh.vertex_indices.chunks_exact(3) {
+        if indices.iter().any(|&index| index as usize >= vertex_count) {
+            continue;
+        }
+        let triangle = [
+            remap[indices[0] as usize],
+            remap[indices[1] as usize],
+            remap[indices[2] as usize],
+        ];
+        let p0 = &vertices[triangle[0] as usize].position;
+        let p1 = &vertices[triangle[1] as usize].position;
+        let p2 = &vertices[triangle[2] as usize].position;
+        if is_collapsed(p0, p1, p2) {
+            continue;
+        }
+        vertex_indices.extend_from_slice(&triangle);
+    }
+
+    let report = WeldReport {
+        moved_vertices: moved_vertices,
+        merged_vertices: vertex_count - vertices.len(),
+        collapsed_triangles: triangle_count - vertex_indices.len() / 3,
+    };
+    mesh.vertices = vertices;
+    mesh.vertex_indices = vertex_indices;
+    mesh.update_bounds();
+
+    report
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives;
+    use cglinalg::{
+        Vector2,
+    };
+
+
+    fn vertex(x: f32, y: f32, z: f32) -> Vertex {
+        Vertex {
+            position: Vector3::new(x, y, z),
+            normal: Vector3::zero(),
+            tex_coords: Vector2::zero(),
+            tangent: Vector3::zero(),
+            bitangent: Vector3::zero(),
+            color: Vertex::default_color(),
+            tex_coords2: Vector2::zero(),
+        }
+    }
+
+    #[test]
+    fn test_weld_positions_within_tolerance() {
+        let mut vertices = vec![vertex(1.0, 2.0, 3.0), vertex(1.0005, 2.0, 2.9995), vertex(0.9995, 2.0005, 3.0)];
+
+        assert_eq!(weld_positions(&mut vertices, 1e-3), 2);
+        assert!(vertices.iter().all(|vertex| vertex.position == Vector3::new(1.0, 2.0, 3.0)));
+    }
+
+    #[test]
+    fn test_weld_positions_outside_tolerance() {
+        let mut vertices = vec![vertex(1.0, 2.0, 3.0), vertex(1.002, 2.0, 3.0), vertex(1.0, 2.0, 2.998)];
+        let expected = vertices.clone();
+
+        assert_eq!(weld_positions(&mut vertices, 1e-3), 0);
+        assert_eq!(vertices, expected);
+    }
+
+    #[test]
+    fn test_weld_positions_across_cells() {
+        // The positions straddle a cell boundary of the spatial hash.
+        let mut vertices = vec![vertex(0.9999e-3, 0.0, 0.0), vertex(1.0001e-3, 0.0, 0.0)];
+
+        assert_eq!(weld_positions(&mut vertices, 1e-3), 1);
+        assert_eq!(vertices[1].position, vertices[0].position);
+    }
+
+    #[test]
+    fn test_weld_positions_nonpositive_tolerance() {
+        let mut vertices = vec![vertex(0.0, 0.0, 0.0), vertex(0.0, 0.0, 0.0)];
+
+        assert_eq!(weld_positions(&mut vertices, 0.0), 0);
+        assert_eq!(weld_positions(&mut vertices, -1.0), 0);
+    }
+
+    #[test]
+    fn test_remove_collapsed_triangles() {
+        let mut vertices = vec![
+            vertex(0.0, 0.0, 0.0), vertex(1.0, 0.0, 0.0), vertex(0.0, 1.0, 0.0),
+            vertex(0.0, 0.0, 0.0), vertex(1.0e-4, 0.0, 0.0), vertex(0.0, 1.0, 0.0),
+            vertex(0.0, 0.0, 1.0), vertex(1.0, 0.0, 1.0), vertex(0.0, 1.0, 1.0),
+        ];
+        let mut smoothing_groups = vec![1, 2, 3];
+        weld_positions(&mut vertices, 1e-3);
+
+        assert_eq!(remove_collapsed_triangles(&mut vertices, &mut smoothing_groups), 1);
+        assert_eq!(vertices.len(), 6);
+        assert_eq!(smoothing_groups, vec![1, 3]);
+        assert_eq!(vertices[3].position, Vector3::new(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn test_weld_mesh_without_nearby_vertices() {
+        let mut mesh = primitives::cube(2.0);
+        let vertex_count = mesh.vertices.len();
+
+        assert_eq!(weld_mesh(&mut mesh, 1e-3), WeldReport::default());
+        assert_eq!(mesh.vertices.len(), vertex_count);
+    }
+}